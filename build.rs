@@ -0,0 +1,35 @@
+// Static builds link the libxcb archives, which unlike the shared libraries don't
+// pull in what they need themselves. Archives only resolve symbols of the ones
+// linked before them, so the extensions come first and libxcb's own needs last.
+const STATIC_LIBS: &[&str] = &[
+    "xcb-randr",
+    "xcb-shape",
+    "xcb-xinput",
+    "xcb-dpms",
+    "xcb-shm",
+    "xcb-xtest",
+    "xcb-xfixes",
+    "xcb",
+    "Xau",
+    "Xdmcp",
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=X_STATIC_LIB_DIR");
+    if std::env::var_os("CARGO_FEATURE_STATIC").is_none() {
+        return;
+    }
+    // glibc binaries stay dynamic, the archives would only end up next to the shared libxcb
+    if std::env::var("CARGO_CFG_TARGET_ENV").as_deref() != Ok("musl") {
+        println!("cargo:warning=static only applies to musl targets, linking dynamically");
+        return;
+    }
+    println!("cargo:warning=static musl builds are experimental, see README.md");
+    if let Some(dir) = std::env::var_os("X_STATIC_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={}", dir.to_string_lossy());
+    }
+    for lib in STATIC_LIBS {
+        println!("cargo:rustc-link-lib=static={}", lib);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::detection::Detector;
+use crate::logging::CaptureRecord;
+use crate::models::Config;
+
+const HISTOGRAM_BUCKET: f64 = 250.0;
+const HISTOGRAM_WIDTH: usize = 50;
+const THRESHOLDS: &[f64] = &[500.0, 1000.0, 1500.0, 2000.0, 2500.0, 3000.0, 4000.0];
+
+pub fn run(
+    path: &std::path::Path,
+    config: Config,
+    refresh_rate: Option<f64>,
+) -> std::io::Result<()> {
+    let records = crate::logging::read_records(path)?;
+    let velocities = records
+        .iter()
+        .filter_map(|record| match record {
+            CaptureRecord::Velocity { time, velocity } => Some((*time, *velocity)),
+            _ => None,
+        })
+        .collect::<Vec<(f64, f64)>>();
+
+    println!(
+        "{} records, {} velocity samples",
+        records.len(),
+        velocities.len()
+    );
+    if velocities.is_empty() {
+        return Ok(());
+    }
+
+    print_percentiles(&velocities);
+    print_timing(&velocities);
+    print_histogram(&velocities);
+    print_reveals(&velocities, &config, refresh_rate);
+    Ok(())
+}
+
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let idx = ((sorted.len() - 1) as f64 * p / 100.0).round() as usize;
+    sorted[idx]
+}
+
+fn print_percentiles(velocities: &[(f64, f64)]) {
+    let mut sorted = velocities.iter().map(|(_, v)| *v).collect::<Vec<f64>>();
+    sorted.sort_by(f64::total_cmp);
+
+    println!("\nVelocity");
+    for p in [50.0, 90.0, 99.0, 99.5, 99.9] {
+        println!("  p{:<5} {:8.1}", p, percentile(&sorted, p));
+    }
+    println!("  peak   {:8.1}", sorted[sorted.len() - 1]);
+}
+
+fn print_timing(velocities: &[(f64, f64)]) {
+    let mut deltas = velocities
+        .windows(2)
+        .map(|pair| pair[1].0 - pair[0].0)
+        .collect::<Vec<f64>>();
+    if deltas.is_empty() {
+        return;
+    }
+    deltas.sort_by(f64::total_cmp);
+    let mean = deltas.iter().sum::<f64>() / deltas.len() as f64;
+
+    println!("\nInter-event timing");
+    println!("  min    {:8.3} ms", deltas[0] * 1000.0);
+    println!("  median {:8.3} ms", percentile(&deltas, 50.0) * 1000.0);
+    println!("  mean   {:8.3} ms", mean * 1000.0);
+    println!("  max    {:8.3} ms", deltas[deltas.len() - 1] * 1000.0);
+    if mean > 0.0 {
+        println!("  rate   {:8.1} Hz", 1.0 / mean);
+    }
+}
+
+fn print_histogram(velocities: &[(f64, f64)]) {
+    let mut buckets = Vec::<usize>::new();
+    for (_, velocity) in velocities {
+        let idx = (velocity.max(0.0) / HISTOGRAM_BUCKET) as usize;
+        if buckets.len() <= idx {
+            buckets.resize(idx + 1, 0);
+        }
+        buckets[idx] += 1;
+    }
+    let max = buckets.iter().copied().max().unwrap_or(1).max(1);
+
+    println!("\nHistogram");
+    for (idx, count) in buckets.iter().enumerate() {
+        let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(max));
+        println!(
+            "  {:6.0} - {:6.0} {:7} {}",
+            idx as f64 * HISTOGRAM_BUCKET,
+            (idx + 1) as f64 * HISTOGRAM_BUCKET,
+            count,
+            bar
+        );
+    }
+}
+
+// Replays the samples through the detector at the UI loop rates
+fn count_reveals(velocities: &[(f64, f64)], config: Config, fps_visible: f64) -> usize {
+    let fps_hidden = crate::HIDDEN_INTERVAL.as_secs_f64();
+    let expiry = config.motion_expiry().as_secs_f64();
+
+    let start = std::time::Instant::now();
+    let mut detector = Detector::new(config);
+    let mut visible = false;
+    let mut reveals = 0;
+    let mut next = 0;
+    let mut latest: Option<(f64, f64)> = None;
+    let mut time = velocities[0].0;
+    let end = velocities[velocities.len() - 1].0 + expiry;
+
+    while time <= end {
+        while next < velocities.len() && velocities[next].0 <= time {
+            latest = Some(velocities[next]);
+            next += 1;
+        }
+        let velocity = match latest {
+            Some((at, velocity)) if time - at <= expiry => velocity,
+            _ => 0.0,
+        };
+
+        // The replay runs faster than the capture, the envelopes follow the capture time
+        let at = start + std::time::Duration::from_secs_f64(time - velocities[0].0);
+        let revealed = detector.update_at(velocity, at);
+        if revealed && !visible {
+            reveals += 1;
+        }
+        visible = revealed;
+        time += if visible { fps_visible } else { fps_hidden };
+    }
+    reveals
+}
+
+fn print_reveals(velocities: &[(f64, f64)], config: &Config, refresh_rate: Option<f64>) {
+    let theme = crate::themes::Theme::load(&config.theme);
+    let (fps_visible, _) = crate::pacing(config, refresh_rate, &theme);
+    println!(
+        "\nReveals per threshold, frames every {:.1} ms",
+        fps_visible.as_secs_f64() * 1000.0
+    );
+    let mut thresholds = THRESHOLDS.to_vec();
+    if !thresholds.contains(&config.threshold) {
+        thresholds.push(config.threshold);
+        thresholds.sort_by(f64::total_cmp);
+    }
+
+    for threshold in thresholds {
+        let reveals = count_reveals(
+            velocities,
+            Config {
+                threshold,
+                ..config.clone()
+            },
+            fps_visible.as_secs_f64(),
+        );
+        let current = if threshold == config.threshold {
+            " (current)"
+        } else {
+            ""
+        };
+        println!("  {:6.0} {:5}{}", threshold, reveals, current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two flicks, 1 kHz reports with every fourth one empty as coalesced mice send them
+    fn burst() -> Vec<(f64, f64)> {
+        let flick = |start: f64| {
+            (0..300).map(move |i| {
+                let velocity = if i % 4 == 3 { 0.0 } else { 3000.0 };
+                (start + i as f64 / 1000.0, velocity)
+            })
+        };
+        flick(0.0).chain(flick(2.0)).collect()
+    }
+
+    #[test]
+    fn each_flick_is_one_reveal() {
+        assert_eq!(count_reveals(&burst(), Config::new(), 1.0 / 60.0), 2);
+    }
+
+    #[test]
+    fn flicks_below_the_threshold_are_not_revealed() {
+        let config = Config {
+            threshold: 4000.0,
+            ..Config::new()
+        };
+        assert_eq!(count_reveals(&burst(), config, 1.0 / 60.0), 0);
+    }
+}
@@ -1,58 +1,481 @@
+use crate::models::{ColorMode, Config, WidthCurve};
+use crate::themes::{Color, Shape, Theme};
+use crate::timeline::{Frame, Playback};
+
 pub struct Animation {
+    max_size: u32,
     max_border: u32,
-    frames: Vec<xcb::x::Arc>,
+    theme: Theme,
+    color_mode: ColorMode,
+    color: Option<Color>,
+    hue_cycle: std::time::Duration,
+    response_gamma: f64,
+    width_curve: WidthCurve,
+    min_width: u32,
+    max_width: u32,
+    start: std::time::Instant,
+    // Arcs of every ring per frame, tagged with the ring index for its alpha falloff
+    frames: Vec<Vec<(u32, xcb::x::Arc)>>,
+    intro: Option<Playback>,
+    outro: Option<Playback>,
+    revealed: bool,
+    click: Option<std::time::Instant>,
+    dismiss: u32,
 }
 
+const CLICK_RING: std::time::Duration = std::time::Duration::from_millis(350);
+// Speed at which all visuals are saturated
+const SATURATION: f64 = 1000.0;
+
 impl Animation {
-    pub fn new(max_size: u32) -> Animation {
-        let max_border = max_size / 2 - 16;
-        let frames = (0..100)
-            .map(|s| {
-                circle(
-                    max_size,
-                    (((max_size - max_border) as f64) * ((s as f64) / 100.0)) as u32,
-                )
-            })
-            .collect::<Vec<xcb::x::Arc>>();
+    pub fn new(config: Config, max_size: u32, theme: Theme) -> Animation {
+        let max_border = border(max_size);
+        let frames = frames(&theme, max_size);
+        let min_width = config.min_width.max(theme.min_width).max(1);
+
+        Animation {
+            max_size,
+            max_border,
+            theme,
+            color_mode: config.color_mode,
+            color: config.color,
+            hue_cycle: std::time::Duration::from_secs_f64(config.hue_cycle_seconds.max(0.1)),
+            response_gamma: config.response_gamma.max(0.01),
+            width_curve: config.width_curve,
+            min_width,
+            max_width: config.max_width,
+            start: std::time::Instant::now(),
+            frames,
+            intro: None,
+            outro: None,
+            revealed: false,
+            click: None,
+            dismiss: 0,
+        }
+    }
+
+    // Frames are centered in a window of max_size, a resized window needs new ones
+    fn resize(&mut self, size: u32) {
+        if size == self.max_size {
+            return;
+        }
+        self.max_size = size;
+        self.max_border = border(size);
+        self.frames = frames(&self.theme, size);
+    }
+
+    pub fn click(&mut self) {
+        self.click = Some(std::time::Instant::now());
+    }
+
+    // Size of the dismiss hotspot in the top right corner, 0 while it isn't clickable
+    pub fn set_dismiss(&mut self, size: u32) {
+        self.dismiss = size;
+    }
+
+    pub fn start_intro(&mut self) {
+        self.revealed = true;
+        self.outro = None;
+        self.intro = Playback::new(&self.theme.intro);
+    }
+
+    // Returns whether an outro started, only once per reveal
+    pub fn start_outro(&mut self) -> bool {
+        if !self.revealed {
+            return false;
+        }
+        self.revealed = false;
+        self.intro = None;
+        self.outro = Playback::new(&self.theme.outro);
+        self.outro.is_some()
+    }
 
-        Animation { max_border, frames }
+    pub fn outro_playing(&self) -> bool {
+        self.outro.as_ref().is_some_and(|outro| !outro.finished())
     }
+
+    fn keyframe(&self) -> Option<Frame> {
+        [self.intro.as_ref(), self.outro.as_ref()]
+            .into_iter()
+            .flatten()
+            .find(|playback| !playback.finished())
+            .map(Playback::sample)
+    }
+
+    // Power curve around the saturation speed, a gamma above 1.0 keeps
+    // low speeds subtle and ramps up sharply at high speeds.
+    fn response(&self, speed: f64) -> f64 {
+        SATURATION * (speed.max(0.0) / SATURATION).powf(self.response_gamma)
+    }
+
+    // Both curves reach the same width at the saturation speed of response
+    fn width(&self, speed: f64) -> u32 {
+        let max_width = match self.max_width {
+            0 => self.max_border,
+            max_width => max_width.min(self.max_border),
+        };
+        let width = match self.width_curve {
+            WidthCurve::Linear => speed / 30.0,
+            WidthCurve::Sqrt => (speed.max(0.0) * 1000.0).sqrt() / 30.0,
+            WidthCurve::Constant => max_width as f64,
+        };
+        (width as u32).clamp(self.min_width.min(max_width), max_width)
+    }
+
+    fn color(&self) -> Color {
+        match self.color_mode {
+            // Cycling hues are motion as well
+            ColorMode::Rainbow if !self.theme.steady => {
+                let cycle = self.start.elapsed().as_secs_f64() / self.hue_cycle.as_secs_f64();
+                Color::from_hue(cycle.fract() * 360.0)
+            }
+            ColorMode::Theme | ColorMode::Rainbow => self.color.unwrap_or(self.theme.color),
+        }
+    }
+
+    // Clears the window of the given size and draws the frame for the speed
     pub fn play(
-        &self,
+        &mut self,
         conn: &xcb::Connection,
         win: xcb::x::Window,
         gfx_ctx: xcb::x::Gcontext,
+        size: u32,
         speed: f64,
     ) {
-        let alpha = ((speed / 5.0).max(0.0).min(200.0) as u32) << 24;
-        let red = ((speed / 0.8).max(0.0).min(255.0) as u32) << 16;
-        let color = xcb::x::Gc::Foreground(red | alpha);
-        let border = xcb::x::Gc::LineWidth((speed / 30.0).max(1.0).min(self.max_border as _) as _);
-        let frame_idx = ((speed / 10.0).max(0.0) as usize).min(self.frames.len() - 1);
         conn.send_request(
             &(xcb::x::ClearArea {
                 exposures: true,
                 window: win,
                 x: 0,
                 y: 0,
-                width: 500,
-                height: 500,
+                width: size as _,
+                height: size as _,
             }),
         );
+        self.draw(conn, win, gfx_ctx, size, speed);
+    }
+
+    // Draws on top of the current window contents
+    pub fn draw(
+        &mut self,
+        conn: &xcb::Connection,
+        win: xcb::x::Window,
+        gfx_ctx: xcb::x::Gcontext,
+        size: u32,
+        speed: f64,
+    ) {
+        self.resize(size);
+
+        self.draw_click(conn, win, gfx_ctx);
+        self.draw_dismiss(conn, win, gfx_ctx);
+        if let Some(frame) = self.keyframe() {
+            self.draw_keyframe(conn, win, gfx_ctx, frame);
+            return;
+        }
+
+        let speed = match self.theme.steady {
+            true => SATURATION,
+            false => self.response(speed),
+        };
+        let alpha = (speed / 5.0).max(0.0).min(self.theme.max_alpha as _) as u8;
+        let intensity = (speed / 0.8).max(0.0).min(255.0) / 255.0;
+        let width = self.width(speed);
+        let frame_idx = ((speed / 10.0).max(0.0) as usize).min(self.frames.len() - 1);
+        let frame = self.frames.get(frame_idx).unwrap();
+        self.draw_rings(conn, win, gfx_ctx, frame, intensity, alpha, width);
+    }
+
+    fn draw_keyframe(
+        &self,
+        conn: &xcb::Connection,
+        win: xcb::x::Window,
+        gfx_ctx: xcb::x::Gcontext,
+        frame: Frame,
+    ) {
+        let size = ((self.max_size - self.max_border) as f64 * frame.radius.clamp(0.0, 1.0)) as u32;
+        let rings = (0..self.theme.rings.max(1))
+            .map(|ring| (ring, ring_size(&self.theme, size, ring)))
+            .filter(|&(_, ring_size)| ring_size > 0)
+            .map(|(ring, ring_size)| (ring, circle(self.max_size, ring_size)))
+            .collect::<Vec<(u32, xcb::x::Arc)>>();
+        let alpha = (self.theme.max_alpha as f64 * frame.alpha.clamp(0.0, 1.0)).round() as u8;
+        let width = frame.width.max(1.0).min(self.max_border as _) as u32;
+        self.draw_rings(conn, win, gfx_ctx, &rings, 1.0, alpha, width);
+    }
+
+    // Grows from the cursor to the full size and fades while doing so
+    fn draw_click(&self, conn: &xcb::Connection, win: xcb::x::Window, gfx_ctx: xcb::x::Gcontext) {
+        let progress = match self.click {
+            Some(click) if click.elapsed() < CLICK_RING && !self.theme.steady => {
+                click.elapsed().as_secs_f64() / CLICK_RING.as_secs_f64()
+            }
+            _ => return,
+        };
+        let size = (self.max_size.saturating_sub(2) as f64 * (0.2 + 0.8 * progress)) as u32;
+        let alpha = (self.theme.max_alpha as f64 * (1.0 - progress)).round() as u8;
+        let color = xcb::x::Gc::Foreground(self.color().pixel(1.0, alpha));
         conn.send_request(
             &(xcb::x::ChangeGc {
                 gc: gfx_ctx,
-                value_list: &[color, border],
+                value_list: &[
+                    color,
+                    xcb::x::Gc::LineWidth(2),
+                    xcb::x::Gc::LineStyle(xcb::x::LineStyle::Solid),
+                ],
             }),
         );
         conn.send_request(
             &(xcb::x::PolyArc {
                 drawable: xcb::x::Drawable::Window(win),
                 gc: gfx_ctx,
-                arcs: &[*self.frames.get(frame_idx).unwrap()],
+                arcs: &[circle(self.max_size, size)],
+            }),
+        );
+    }
+
+    // A cross marks the hotspot, inset so the line stays inside of it
+    fn draw_dismiss(&self, conn: &xcb::Connection, win: xcb::x::Window, gfx_ctx: xcb::x::Gcontext) {
+        let size = self.dismiss.min(self.max_size);
+        if size < 6 {
+            return;
+        }
+        let (left, right) = (
+            (self.max_size - size + size / 4) as i16,
+            (self.max_size - size / 4) as i16,
+        );
+        let (top, bottom) = ((size / 4) as i16, (size - size / 4) as i16);
+        conn.send_request(
+            &(xcb::x::ChangeGc {
+                gc: gfx_ctx,
+                value_list: &[
+                    xcb::x::Gc::Foreground(self.color().pixel(1.0, self.theme.max_alpha)),
+                    xcb::x::Gc::LineWidth(2),
+                    xcb::x::Gc::LineStyle(xcb::x::LineStyle::Solid),
+                ],
+            }),
+        );
+        conn.send_request(
+            &(xcb::x::PolySegment {
+                drawable: xcb::x::Drawable::Window(win),
+                gc: gfx_ctx,
+                segments: &[
+                    xcb::x::Segment {
+                        x1: left,
+                        y1: top,
+                        x2: right,
+                        y2: bottom,
+                    },
+                    xcb::x::Segment {
+                        x1: left,
+                        y1: bottom,
+                        x2: right,
+                        y2: top,
+                    },
+                ],
             }),
         );
     }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_rings(
+        &self,
+        conn: &xcb::Connection,
+        win: xcb::x::Window,
+        gfx_ctx: xcb::x::Gcontext,
+        rings: &[(u32, xcb::x::Arc)],
+        intensity: f64,
+        alpha: u8,
+        width: u32,
+    ) {
+        self.draw_glow(conn, win, gfx_ctx, rings, intensity, alpha, width);
+
+        let mut values = self.pens(intensity, alpha);
+        values.push(xcb::x::Gc::LineWidth(width));
+        values.push(xcb::x::Gc::LineStyle(self.line_style()));
+        if self.theme.dash_length > 0 {
+            values.push(xcb::x::Gc::DashList(self.theme.dash_length.min(255)));
+        }
+        conn.send_request(
+            &(xcb::x::ChangeGc {
+                gc: gfx_ctx,
+                value_list: &values,
+            }),
+        );
+
+        if self.theme.ring_falloff == 1.0 {
+            let arcs = rings
+                .iter()
+                .map(|(_, arc)| *arc)
+                .collect::<Vec<xcb::x::Arc>>();
+            self.draw_arcs(conn, win, gfx_ctx, &arcs);
+            return;
+        }
+
+        // Every ring fades relative to the one outside of it
+        for (ring, arc) in rings {
+            let ring_alpha = (alpha as f64) * self.theme.ring_falloff.powi(*ring as i32);
+            conn.send_request(
+                &(xcb::x::ChangeGc {
+                    gc: gfx_ctx,
+                    value_list: &self.pens(intensity, ring_alpha.round() as u8),
+                }),
+            );
+            self.draw_arcs(conn, win, gfx_ctx, &[*arc]);
+        }
+    }
+
+    // Double dashes draw the gaps in the background color
+    fn pens(&self, intensity: f64, alpha: u8) -> Vec<xcb::x::Gc> {
+        let mut pens = vec![xcb::x::Gc::Foreground(self.color().pixel(intensity, alpha))];
+        if let (Some(dash_color), xcb::x::LineStyle::DoubleDash) =
+            (self.theme.dash_color, self.line_style())
+        {
+            pens.push(xcb::x::Gc::Background(dash_color.pixel(intensity, alpha)));
+        }
+        pens
+    }
+
+    fn line_style(&self) -> xcb::x::LineStyle {
+        match (self.theme.dash_length, self.theme.dash_color) {
+            (0, _) => xcb::x::LineStyle::Solid,
+            (_, Some(_)) => xcb::x::LineStyle::DoubleDash,
+            (_, None) => xcb::x::LineStyle::OnOffDash,
+        }
+    }
+
+    // Core drawing replaces pixels instead of blending them, so the widest and faintest
+    // copy goes first and every closer one paints over its inner part
+    #[allow(clippy::too_many_arguments)]
+    fn draw_glow(
+        &self,
+        conn: &xcb::Connection,
+        win: xcb::x::Window,
+        gfx_ctx: xcb::x::Gcontext,
+        rings: &[(u32, xcb::x::Arc)],
+        intensity: f64,
+        alpha: u8,
+        width: u32,
+    ) {
+        for glow in (1..=self.theme.glow_rings).rev() {
+            let spread = glow.saturating_mul(self.theme.glow_spread);
+            let glow_alpha = alpha as f64 * self.theme.glow_alpha.clamp(0.0, 1.0).powi(glow as i32);
+            conn.send_request(
+                &(xcb::x::ChangeGc {
+                    gc: gfx_ctx,
+                    value_list: &[
+                        xcb::x::Gc::Foreground(
+                            self.color().pixel(intensity, glow_alpha.round() as u8),
+                        ),
+                        // Line widths are 16 bit on the wire
+                        xcb::x::Gc::LineWidth(
+                            width
+                                .saturating_add(spread.saturating_mul(2))
+                                .min(u16::MAX as u32),
+                        ),
+                        xcb::x::Gc::LineStyle(xcb::x::LineStyle::Solid),
+                    ],
+                }),
+            );
+            let arcs = rings
+                .iter()
+                .map(|(_, arc)| match self.theme.shape {
+                    // A wider line already spreads to both sides of the ring
+                    Shape::Ring => *arc,
+                    Shape::Disc => grow(arc, spread),
+                })
+                .collect::<Vec<xcb::x::Arc>>();
+            self.draw_arcs(conn, win, gfx_ctx, &arcs);
+        }
+    }
+
+    // The frame cache holds full circles, partial arcs get their angles when drawn
+    fn segments(&self, arcs: &[xcb::x::Arc]) -> Vec<xcb::x::Arc> {
+        let degrees = self.theme.arc_degrees.clamp(0.0, 360.0);
+        if degrees >= 360.0 {
+            return arcs.to_vec();
+        }
+        let rotation = if self.theme.spin_seconds > 0.0 {
+            let turns = self.start.elapsed().as_secs_f64() / self.theme.spin_seconds;
+            // Clockwise, X angles run counterclockwise
+            360.0 - turns.fract() * 360.0
+        } else {
+            0.0
+        };
+        arcs.iter()
+            .map(|arc| xcb::x::Arc {
+                angle1: (rotation * 64.0) as i16,
+                angle2: (degrees * 64.0) as i16,
+                ..*arc
+            })
+            .collect()
+    }
+
+    fn draw_arcs(
+        &self,
+        conn: &xcb::Connection,
+        win: xcb::x::Window,
+        gfx_ctx: xcb::x::Gcontext,
+        arcs: &[xcb::x::Arc],
+    ) {
+        let arcs = &self.segments(arcs);
+        match self.theme.shape {
+            Shape::Ring => conn.send_request(
+                &(xcb::x::PolyArc {
+                    drawable: xcb::x::Drawable::Window(win),
+                    gc: gfx_ctx,
+                    arcs,
+                }),
+            ),
+            Shape::Disc => conn.send_request(
+                &(xcb::x::PolyFillArc {
+                    drawable: xcb::x::Drawable::Window(win),
+                    gc: gfx_ctx,
+                    arcs,
+                }),
+            ),
+        };
+    }
+}
+
+// Theme files can ask for any spread, the arc saturates at the limits of its fields
+fn grow(arc: &xcb::x::Arc, by: u32) -> xcb::x::Arc {
+    let by = by.min(u16::MAX as u32) as i32;
+    let position = |at: i16| (at as i32 - by).max(i16::MIN as i32) as i16;
+    let size = |size: u16| (size as i32 + by * 2).min(u16::MAX as i32) as u16;
+    xcb::x::Arc {
+        x: position(arc.x),
+        y: position(arc.y),
+        width: size(arc.width),
+        height: size(arc.height),
+        ..*arc
+    }
+}
+
+fn border(max_size: u32) -> u32 {
+    (max_size / 2).saturating_sub(16).max(1)
+}
+
+// Arcs of every ring for 100 steps of the speed, tagged with the ring index
+fn frames(theme: &Theme, max_size: u32) -> Vec<Vec<(u32, xcb::x::Arc)>> {
+    let max_border = border(max_size);
+    (0..100)
+        .map(|s| {
+            let size = ((max_size.saturating_sub(max_border) as f64) * ((s as f64) / 100.0)) as u32;
+            (0..theme.rings.max(1))
+                .map(|ring| (ring, ring_size(theme, size, ring)))
+                .filter(|&(_, ring_size)| ring_size > 0)
+                .map(|(ring, ring_size)| (ring, circle(max_size, ring_size)))
+                .collect::<Vec<(u32, xcb::x::Arc)>>()
+        })
+        .collect()
+}
+
+fn ring_size(theme: &Theme, size: u32, ring: u32) -> u32 {
+    match theme.ring_scales.get(ring as usize) {
+        Some(scale) => ((size as f64) * scale.max(0.0)) as u32,
+        None if !theme.ring_scales.is_empty() => 0,
+        None => size.saturating_sub(ring * theme.ring_spacing * 2),
+    }
 }
 
 fn circle(max_size: u32, size: u32) -> xcb::x::Arc {
@@ -69,3 +492,36 @@ fn circle(max_size: u32, size: u32) -> xcb::x::Arc {
         angle2: 360 << 6,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arc(x: i16, size: u16) -> xcb::x::Arc {
+        xcb::x::Arc {
+            x,
+            y: x,
+            width: size,
+            height: size,
+            angle1: 0,
+            angle2: 360 * 64,
+        }
+    }
+
+    #[test]
+    fn grow_spreads_to_both_sides() {
+        let grown = grow(&arc(10, 20), 3);
+        assert_eq!(
+            (grown.x, grown.y, grown.width, grown.height),
+            (7, 7, 26, 26)
+        );
+    }
+
+    #[test]
+    fn grow_saturates_for_large_spreads() {
+        for by in [40_000, u32::MAX] {
+            let grown = grow(&arc(-30_000, 60_000), by);
+            assert_eq!((grown.x, grown.width), (i16::MIN, u16::MAX));
+        }
+    }
+}
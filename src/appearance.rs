@@ -0,0 +1,176 @@
+use crate::models::{ColorScheme, ReducedMotion};
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Preferences {
+    scheme: Option<ColorScheme>,
+    reduce_motion: Option<bool>,
+}
+
+// Preferences of the desktop, looked up once in the background. gdbus and gsettings
+// take a while, until they answered auto resolves as if the desktop had no preference.
+pub struct Appearance {
+    lookup: Option<std::thread::JoinHandle<Preferences>>,
+    preferences: Preferences,
+    ready: bool,
+}
+
+impl Appearance {
+    pub fn lookup() -> Appearance {
+        let lookup = std::thread::spawn(|| Preferences {
+            scheme: portal().or_else(gsettings).or_else(gtk_settings),
+            reduce_motion: portal_reduced_motion()
+                .or_else(gsettings_animations)
+                .or_else(gtk_animations),
+        });
+        Appearance {
+            lookup: Some(lookup),
+            preferences: Preferences::default(),
+            ready: false,
+        }
+    }
+
+    // True once the lookup finished, auto settings may resolve differently from then on
+    pub fn take_ready(&mut self) -> bool {
+        if self
+            .lookup
+            .as_ref()
+            .is_some_and(|lookup| lookup.is_finished())
+        {
+            self.wait();
+        }
+        std::mem::take(&mut self.ready)
+    }
+
+    // Blocks until the lookup finished, e.g. before the seccomp filter stops helpers
+    pub fn wait(&mut self) {
+        if let Some(lookup) = self.lookup.take() {
+            self.preferences = lookup.join().unwrap_or_default();
+            self.ready = true;
+        }
+    }
+
+    // Resolves auto to the preference of the desktop, light when none is found
+    pub fn color_scheme(&self, scheme: ColorScheme) -> ColorScheme {
+        match scheme {
+            ColorScheme::Auto => self.preferences.scheme.unwrap_or(ColorScheme::Light),
+            scheme => scheme,
+        }
+    }
+
+    // Off when the desktop states no preference
+    pub fn reduce_motion(&self, preference: ReducedMotion) -> bool {
+        match preference {
+            ReducedMotion::Auto => self.preferences.reduce_motion.unwrap_or(false),
+            preference => preference == ReducedMotion::On,
+        }
+    }
+}
+
+// The settings portal answers "(<<uint32 1>>,)", 1 prefers dark, 2 light and 0 has no preference
+fn portal() -> Option<ColorScheme> {
+    match portal_setting("color-scheme")? {
+        '1' => Some(ColorScheme::Dark),
+        '2' => Some(ColorScheme::Light),
+        _ => None,
+    }
+}
+
+// 1 asks to reduce motion, 0 has no preference
+fn portal_reduced_motion() -> Option<bool> {
+    match portal_setting("reduced-motion")? {
+        '1' => Some(true),
+        '0' => Some(false),
+        _ => None,
+    }
+}
+
+fn portal_setting(key: &str) -> Option<char> {
+    let output = std::process::Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest",
+            "org.freedesktop.portal.Desktop",
+            "--object-path",
+            "/org/freedesktop/portal/desktop",
+            "--method",
+            "org.freedesktop.portal.Settings.Read",
+            "org.freedesktop.appearance",
+            key,
+        ])
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    let reply = String::from_utf8_lossy(&output.stdout);
+    reply.split("uint32 ").nth(1)?.chars().next()
+}
+
+fn gsettings_read(key: &str) -> Option<String> {
+    std::process::Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.interface", key])
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .to_lowercase()
+        })
+}
+
+fn gsettings() -> Option<ColorScheme> {
+    match gsettings_read("color-scheme").as_deref() {
+        Some("'prefer-dark'") => Some(ColorScheme::Dark),
+        Some("'prefer-light'") => Some(ColorScheme::Light),
+        _ => gsettings_read("gtk-theme").map(from_theme_name),
+    }
+}
+
+// GNOME turns animations off in its accessibility settings
+fn gsettings_animations() -> Option<bool> {
+    match gsettings_read("enable-animations").as_deref() {
+        Some("false") => Some(true),
+        Some("true") => Some(false),
+        _ => None,
+    }
+}
+
+fn gtk_settings_ini() -> Option<String> {
+    let path = crate::config::config_dir()
+        .parent()?
+        .join("gtk-3.0")
+        .join("settings.ini");
+    std::fs::read_to_string(path).ok()
+}
+
+fn gtk_animations() -> Option<bool> {
+    gtk_settings_ini()?.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "gtk-enable-animations").then(|| matches!(value.trim(), "0" | "false"))
+    })
+}
+
+fn gtk_settings() -> Option<ColorScheme> {
+    let content = gtk_settings_ini()?;
+    content.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        match key.trim() {
+            "gtk-application-prefer-dark-theme" => Some(match value.trim() {
+                "1" | "true" => ColorScheme::Dark,
+                _ => ColorScheme::Light,
+            }),
+            "gtk-theme-name" => Some(from_theme_name(value.trim().to_lowercase())),
+            _ => None,
+        }
+    })
+}
+
+// Adwaita-dark, Breeze-Dark and most others mark their dark variant in the name
+fn from_theme_name(name: String) -> ColorScheme {
+    if name.contains("dark") {
+        ColorScheme::Dark
+    } else {
+        ColorScheme::Light
+    }
+}
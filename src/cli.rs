@@ -0,0 +1,156 @@
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "mouse-reveal",
+    version,
+    about = "Reveals the mouse cursor when shaking it"
+)]
+pub struct Args {
+    /// Emit a JSON status line on every state change, to `stdout` or a FIFO path
+    #[arg(long, value_name = "stdout|PATH")]
+    pub status_output: Option<String>,
+
+    /// Desktop environment to apply workarounds for, detected by default
+    #[arg(long, value_enum)]
+    pub de: Option<crate::models::Desktop>,
+
+    /// Suppress periodic diagnostics and traces, even when debug_dumps is set
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print periodic diagnostics, twice to also trace every input event
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Detect and log reveals without ever mapping a window, e.g. while presenting
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Capture input events for capture_seconds at startup, to capture_output or stderr
+    #[arg(long)]
+    pub capture: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+    pub fn overrides(&self) -> Overrides {
+        Overrides {
+            status_output: self.status_output.clone(),
+            desktop: self.de,
+            dry_run: self.dry_run,
+            capture: self.capture,
+        }
+    }
+}
+
+// Settings given on the command line, they win over the config file and its reloads
+#[derive(Debug, Clone)]
+pub struct Overrides {
+    status_output: Option<String>,
+    desktop: Option<crate::models::Desktop>,
+    dry_run: bool,
+    capture: bool,
+}
+
+impl Overrides {
+    pub fn apply(&self, config: &mut crate::models::Config) {
+        if self.status_output.is_some() {
+            config.status_output = self.status_output.clone();
+        }
+        if let Some(desktop) = self.desktop {
+            config.desktop = desktop;
+        }
+        config.capture |= self.capture;
+        // The verbosity is decided once at startup
+        config.debug_dumps = crate::logging::enabled(crate::logging::Verbosity::Debug);
+    }
+
+    // The file setting stays as it is, reloads can't turn off a dry run from the command line
+    pub fn dry_run(&self, config: &crate::models::Config) -> bool {
+        self.dry_run || config.dry_run
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Inspect the configuration
+    Config {
+        /// Print the default configuration with all settings documented
+        #[arg(long)]
+        print_default: bool,
+    },
+    /// Print velocity statistics of a capture written with `capture_output`
+    Analyze {
+        capture: std::path::PathBuf,
+        /// Refresh rate the frames are paced to, the current display's when not given
+        #[arg(long)]
+        refresh_rate: Option<f64>,
+    },
+    /// Record the input events of the monitored device to a capture
+    Record {
+        /// Seconds to record, an interrupt stops earlier
+        #[arg(long, default_value_t = 60.0)]
+        duration: f64,
+        /// Capture to write, binary for .bin and JSON otherwise
+        #[arg(short, long)]
+        output: std::path::PathBuf,
+    },
+    /// Convert a capture between the JSON and binary formats
+    Convert {
+        capture: std::path::PathBuf,
+        #[arg(long, value_enum)]
+        to: crate::logging::CaptureFormat,
+        /// Defaults to the capture with the extension of the format
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Tune the detection parameters with a live velocity graph
+    Tune,
+    /// Print the state of the running daemon
+    Status {
+        /// Print reveal counts, visible time and trigger reasons per day as JSON instead
+        #[arg(long)]
+        stats: bool,
+    },
+    /// Pause revealing the cursor
+    Pause,
+    /// Resume revealing the cursor
+    Resume,
+    /// Toggle between paused and enabled
+    Toggle,
+    /// Reveal the cursor once, as if it was shaken
+    Reveal,
+    /// Toggle the persistent highlight around the cursor
+    Highlight,
+    /// Print frame timing statistics of the running daemon as JSON
+    Timing,
+    /// Shake the pointer through XTest and check that an overlay reveals and hides
+    SelfTest,
+    /// Feed random and degenerate motion through the detection and check its invariants
+    #[command(hide = true)]
+    FuzzDetector {
+        #[arg(long, default_value_t = 10000)]
+        iterations: u64,
+        /// Seed of the first run, failures print the seed reproducing them
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+    /// Switch the running daemon to a profile, or back to the plain settings without a name
+    Profile { name: Option<String> },
+    /// Toggle the presentation profile: a large persistent highlight with click rings
+    Presentation,
+    /// Change a setting of the running daemon, without saving it
+    Set { key: String, value: String },
+    /// Open the settings window
+    #[cfg(feature = "gui")]
+    Settings,
+}
+
+impl Args {
+    pub fn load() -> Args {
+        Args::parse()
+    }
+}
@@ -0,0 +1,115 @@
+use crate::models::{Monitor, Position32, ScreenLayout};
+use crate::themes::Theme;
+use crate::{ConnExt, OverlayWindow};
+
+pub struct Compass {
+    win: OverlayWindow,
+    theme: Theme,
+    origin: Option<Position32>,
+}
+
+impl Compass {
+    pub fn new(win: OverlayWindow, theme: Theme) -> Compass {
+        Compass {
+            win,
+            theme,
+            origin: None,
+        }
+    }
+
+    // Anchors the compass to the monitor holding the focused window,
+    // falling back to the primary monitor.
+    pub fn reveal(&mut self, layout: &ScreenLayout) {
+        let focus = self.win.get_conn().get_focus_position(self.win.get_root());
+
+        self.origin = focus
+            .and_then(|pos| layout.monitor_at(pos))
+            .or_else(|| layout.primary())
+            .map(Monitor::center);
+
+        if let Some(origin) = self.origin {
+            self.win.set_center_position(origin);
+        }
+    }
+
+    pub fn point_at(&mut self, target: Position32) {
+        let origin = match self.origin {
+            Some(origin) => origin,
+            None => return,
+        };
+
+        let dx = (target.x - origin.x) as f64;
+        let dy = (target.y - origin.y) as f64;
+        let distance = dx.hypot(dy);
+
+        // The cursor is right next to the compass, an arrow would only be in the way
+        if distance < self.win.size as f64 {
+            self.hide();
+            return;
+        }
+
+        self.win.show();
+        self.draw(dx / distance, dy / distance);
+    }
+
+    pub fn resize(&mut self, size: u32, theme: Theme) {
+        self.theme = theme;
+        self.win.resize(size);
+    }
+
+    pub fn hide(&mut self) {
+        if self.win.visible {
+            self.win.hide();
+        }
+    }
+
+    fn draw(&self, ux: f64, uy: f64) {
+        let radius = (self.win.size / 2) as f64;
+        // Arrow outline along the direction (u) and its perpendicular (p)
+        let outline = [
+            (0.9, 0.0),
+            (0.3, 0.35),
+            (0.3, 0.12),
+            (-0.8, 0.12),
+            (-0.8, -0.12),
+            (0.3, -0.12),
+            (0.3, -0.35),
+        ];
+        let points = outline
+            .iter()
+            .map(|(u, p)| xcb::x::Point {
+                x: (radius + (ux * u - uy * p) * radius) as i16,
+                y: (radius + (uy * u + ux * p) * radius) as i16,
+            })
+            .collect::<Vec<xcb::x::Point>>();
+
+        let conn = self.win.get_conn();
+        conn.send_request(
+            &(xcb::x::ClearArea {
+                exposures: false,
+                window: self.win.get_win(),
+                x: 0,
+                y: 0,
+                width: self.win.size as _,
+                height: self.win.size as _,
+            }),
+        );
+        conn.send_request(
+            &(xcb::x::ChangeGc {
+                gc: self.win.get_gfx(),
+                value_list: &[xcb::x::Gc::Foreground(
+                    self.theme.color.pixel(1.0, self.theme.max_alpha),
+                )],
+            }),
+        );
+        conn.send_request(
+            &(xcb::x::FillPoly {
+                drawable: xcb::x::Drawable::Window(self.win.get_win()),
+                gc: self.win.get_gfx(),
+                shape: xcb::x::PolyShape::Nonconvex,
+                coordinate_mode: xcb::x::CoordMode::Origin,
+                points: &points,
+            }),
+        );
+    }
+}
@@ -0,0 +1,790 @@
+use crate::models::{Config, DeviceClass, WindowClass};
+
+pub const CONFIG_VERSION: i64 = 1;
+
+// Fields renamed since older releases: (version introducing the rename, old name, new name)
+const RENAMES: &[(i64, &str, &str)] = &[];
+
+// Documentation of every field, in the order they are printed in the default config
+const FIELDS: &[(&str, &str)] = &[
+    (
+        "device_name",
+        "Name (or part of it) of the evdev input device to monitor",
+    ),
+    (
+        "exclude_devices",
+        "Names (or parts of them) of input devices that are never monitored",
+    ),
+    (
+        "exclude_touchscreens",
+        "Skip devices reporting screen positions, e.g. touchscreens and pen displays",
+    ),
+    (
+        "pointer_fallback",
+        "Poll the X pointer position when the input device is not accessible",
+    ),
+    (
+        "pointer_only",
+        "Always poll the X pointer position instead of reading an input device",
+    ),
+    (
+        "pointer_poll_hz",
+        "Sampling rate of the pointer position fallback",
+    ),
+    (
+        "spike_cutoff",
+        "Velocities above this are dropped as glitches, 0 keeps everything",
+    ),
+    (
+        "median_filter",
+        "Smooth single sample outliers with a median over the last three velocities",
+    ),
+    (
+        "smoothing_ms",
+        "Time constant for smoothing the velocities at the report rate of the device, 0 disables it",
+    ),
+    ("window_size", "Size of the overlay window in pixels"),
+    (
+        "dismiss_hotspot",
+        "Size of a clickable square in the top right corner of the ring that dismisses a locked or highlighted overlay, 0 keeps the overlay click-through",
+    ),
+    (
+        "fallback_opacity",
+        "Opacity a compositor applies to the overlay when the X screen offers no 32-bit visual",
+    ),
+    (
+        "threshold",
+        "Smoothed velocity above which the cursor is revealed",
+    ),
+    (
+        "threshold_unit",
+        "Unit of the threshold: \"device\" units per second or \"screen\" for percent of the screen diagonal per second",
+    ),
+    (
+        "output_sensitivity",
+        "Velocity multipliers while the cursor is on these outputs, e.g. { HDMI-1 = 1.5 } for a large monitor",
+    ),
+    ("decay", "Decay of the smoothed velocity per frame"),
+    (
+        "accel",
+        "Velocity at which a sample gets the full input weight",
+    ),
+    (
+        "accel_decay",
+        "Minimum weight of a velocity sample in the smoothed velocity",
+    ),
+    (
+        "accel_inc",
+        "Maximum weight of a velocity sample in the smoothed velocity",
+    ),
+    (
+        "attack_ms",
+        "Time constant of the animation intensity rising towards the smoothed velocity, 0 is instant",
+    ),
+    (
+        "release_ms",
+        "Time constant of the animation intensity fading as the shake slows down or after a triggered reveal",
+    ),
+    (
+        "exclude_apps",
+        "WM_CLASS names of applications in which the cursor is never revealed",
+    ),
+    (
+        "exclude_fullscreen",
+        "Never reveal the cursor while the focused window is fullscreen, e.g. games and videos",
+    ),
+    (
+        "apps",
+        "Settings overridden while an application is focused, as [apps.<WM_CLASS>] tables",
+    ),
+    (
+        "schedule",
+        "Settings overridden from a time of day until the next entry, as [schedule.\"HH:MM\"] tables",
+    ),
+    (
+        "devices",
+        "Settings overridden for a kind of input device, as [devices.<touchpad|trackpoint|tablet|mouse>] tables",
+    ),
+    (
+        "profile",
+        "Named sets of settings, as [profile.<name>] tables, switched with `mouse-reveal profile <name>`. The built-in presentation profile can be extended the same way",
+    ),
+    (
+        "active_profile",
+        "Profile applied on top of all other settings, see profile",
+    ),
+    (
+        "hide_during_grabs",
+        "Hide the overlay while another client grabs the pointer, e.g. drag and drop or games",
+    ),
+    (
+        "refresh_pacing",
+        "Pace polling and animation frames to the refresh rate of the primary output",
+    ),
+    (
+        "hide_during_screencast",
+        "Hide the overlay while the screen is shared or recorded, detected through PipeWire",
+    ),
+    (
+        "typing_pause_ms",
+        "Milliseconds after a keystroke in which motion does not reveal the cursor, 0 leaves the keyboards unwatched",
+    ),
+    (
+        "keyboard_name",
+        "Part of the name of the keyboards watched for typing_pause_ms, any keyboard by default",
+    ),
+    (
+        "velocity_window_ms",
+        "Average the velocity over this many milliseconds instead of using the latest sample",
+    ),
+    (
+        "motion_expiry_ms",
+        "Milliseconds without input events after which the motion counts as stopped",
+    ),
+    (
+        "pen_reveal",
+        "Reveal the cursor when a tablet pen comes back into proximity",
+    ),
+    (
+        "pen_reveal_seconds",
+        "Time a pen has to be out of proximity before it counts as reappearing",
+    ),
+    (
+        "haptic_feedback_ms",
+        "Rumble the device for this many milliseconds on reveals if it supports force feedback, 0 disables it",
+    ),
+    (
+        "hide_cursor_seconds",
+        "Hide the cursor after this many seconds without motion and reveal it on the next motion, 0 keeps it visible",
+    ),
+    (
+        "teleport_reveal",
+        "Reveal the cursor when it jumps without any motion, e.g. between monitors",
+    ),
+    (
+        "teleport_distance",
+        "Minimum jump in pixels that counts as a teleport",
+    ),
+    (
+        "edge_reveal",
+        "Reveal the cursor when it is pushed into these screen edges, e.g. [\"top-left\", \"right\"]",
+    ),
+    (
+        "hot_corners",
+        "Actions when the cursor rests in a corner, e.g. { top-left = \"reveal\", bottom-right = \"highlight\" }",
+    ),
+    ("hot_corner_ms", "Time the cursor has to rest in a hot corner"),
+    (
+        "edge_reveal_velocity",
+        "Velocity the device keeps moving at while the cursor is stuck at an edge",
+    ),
+    (
+        "gesture_reveal",
+        "Touchpad gestures whose motion may reveal the cursor: \"scroll\", \"pinch\" and \"edge-swipe\"",
+    ),
+    (
+        "palm_pressure",
+        "Fraction of the maximum contact pressure above which a touchpad contact is a palm and ignored, 0 disables it",
+    ),
+    (
+        "palm_size",
+        "Width of a touchpad contact, as a fraction of the touchpad width, above which it is a palm and ignored, 0 disables it",
+    ),
+    (
+        "desktop_reveal",
+        "Briefly reveal the cursor after switching workspaces",
+    ),
+    (
+        "output_focus_reveal",
+        "Briefly reveal the cursor when the focused window ends up on another monitor",
+    ),
+    (
+        "prediction_ms",
+        "Milliseconds the ring is moved ahead of the cursor along its motion, 0 disables",
+    ),
+    (
+        "interpolate_pointer",
+        "Glide the ring between pointer samples instead of jumping, smoother but one sample behind",
+    ),
+    (
+        "theme",
+        "Name of a built-in theme (classic, ripple, spotlight, target, spinner, high-contrast) or a file in themes/",
+    ),
+    (
+        "color",
+        "Ring color as #rrggbb, overriding the color of the theme",
+    ),
+    ("color_mode", "Ring color: \"theme\" or \"rainbow\""),
+    (
+        "color_scheme",
+        "Picks the light or dark variant of the theme color: \"auto\" follows the desktop, \"light\" or \"dark\"",
+    ),
+    (
+        "reduced_motion",
+        "Shows a steady ring without growing, fading or spinning: \"auto\" follows the desktop, \"on\" or \"off\"",
+    ),
+    (
+        "hue_cycle_seconds",
+        "Duration of a full hue cycle in rainbow mode",
+    ),
+    (
+        "response_gamma",
+        "Power curve applied to the velocity before picking visuals, 1.0 is linear",
+    ),
+    (
+        "width_curve",
+        "Border width over the speed: \"linear\", \"sqrt\" grows fast at low speeds, \"constant\" always uses max_width",
+    ),
+    ("min_width", "Thinnest border of the ring in pixels"),
+    (
+        "max_width",
+        "Thickest border of the ring in pixels, 0 allows up to half the window",
+    ),
+    (
+        "highlight",
+        "Keep the ring visible around the cursor at all times, toggled with `highlight`",
+    ),
+    (
+        "highlight_intensity",
+        "Velocity shown by the persistent highlight, lower is more subtle",
+    ),
+    (
+        "click_rings",
+        "Draw an expanding ring on every click while the overlay is visible",
+    ),
+    (
+        "shake_lock",
+        "Keep the ring visible after two shakes in a row, until the next click or shake",
+    ),
+    (
+        "shake_lock_seconds",
+        "Maximum pause between the two shakes that lock the ring visible",
+    ),
+    (
+        "compass",
+        "Draw an arrow from the center of the focused monitor toward the cursor",
+    ),
+    ("compass_size", "Size of the compass window in pixels"),
+    (
+        "edge_flash",
+        "Flash the edges of the monitor holding the cursor, with one overlay per monitor",
+    ),
+    ("edge_flash_width", "Width of the edge flash in pixels"),
+    (
+        "magnifier",
+        "Show a magnified view of the screen under the cursor inside the ring",
+    ),
+    ("magnifier_zoom", "Zoom factor of the magnifier lens"),
+    (
+        "mit_shm",
+        "Upload the magnifier image through shared memory when the X server supports it",
+    ),
+    (
+        "learn_threshold",
+        "Learn the threshold from observed velocities: \"off\", \"suggest\" or \"apply\"",
+    ),
+    (
+        "learn_hours",
+        "Hours of observed velocities after which a threshold is learned",
+    ),
+    (
+        "learn_percentile",
+        "Percentile of observed velocities used as the learned threshold",
+    ),
+    (
+        "dpi_scaling",
+        "Scale window_size by the DPI of the primary monitor",
+    ),
+    (
+        "desktop",
+        "Desktop environment workarounds: \"auto\", \"gnome\", \"kde\" or \"other\"",
+    ),
+    (
+        "status_output",
+        "Emit JSON status lines for status bars to \"stdout\" or a FIFO path",
+    ),
+    (
+        "dry_run",
+        "Run the detection without ever showing a window, logging every reveal instead",
+    ),
+    (
+        "mqtt_broker",
+        "MQTT broker as host:port to publish reveal events to, needs the mqtt feature",
+    ),
+    (
+        "mqtt_topic",
+        "Topic prefix for MQTT: <topic>/event, <topic>/control and <topic>/reply",
+    ),
+    (
+        "http_port",
+        "Serve /reveal, /pause, /resume, /status and /stats on this localhost port, needs the http feature",
+    ),
+    (
+        "shell_bridge",
+        "Leave drawing to the GNOME Shell extension over D-Bus instead of the X overlay, needs the gnome-shell feature",
+    ),
+    (
+        "seccomp",
+        "Restrict the syscalls once started: \"off\", \"log\" to the audit log or \"enforce\" by failing them. No programs can be started under the filter, e.g. pw-dump for hide_during_screencast",
+    ),
+    (
+        "debug_dumps",
+        "Periodically print velocity and timing diagnostics to stderr, like -v",
+    ),
+    (
+        "debug_dump_seconds",
+        "Seconds between the periodic diagnostics, 0 turns them off while keeping the traces of -vv",
+    ),
+    (
+        "debug_dump_output",
+        "File the periodic diagnostics are appended to instead of stderr",
+    ),
+    (
+        "capture",
+        "Capture input events at startup and print them, like --capture",
+    ),
+    (
+        "capture_seconds",
+        "Seconds of input events captured at startup",
+    ),
+    (
+        "capture_output",
+        "File the startup capture is written to for the analyze subcommand, JSON or binary for .bin. Setting it turns on the capture",
+    ),
+    (
+        "reveal_screenshots",
+        "Directory to save the area around the cursor to on every reveal, with an index.tsv of the reveals",
+    ),
+    (
+        "reveal_screenshot_size",
+        "Size of the reveal screenshots in pixels",
+    ),
+    (
+        "capture_keys",
+        "Key events in captures: \"keep\", \"redact\" the key codes or \"drop\" keyboards entirely",
+    ),
+];
+
+// Examples for settings that are disabled by default
+const EXAMPLES: &[(&str, &str)] = &[
+    ("status_output", "\"stdout\""),
+    ("color", "\"#ff0000\""),
+    ("mqtt_broker", "\"localhost:1883\""),
+    ("http_port", "8737"),
+    ("active_profile", "\"presentation\""),
+    ("capture_output", "\"/tmp/mouse-reveal-capture.json\""),
+    ("reveal_screenshots", "\"/tmp/mouse-reveal-reveals\""),
+    ("debug_dump_output", "\"/tmp/mouse-reveal-dumps.log\""),
+];
+
+pub const PRESENTATION_PROFILE: &str = "presentation";
+
+pub fn config_dir() -> std::path::PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config"))
+        })
+        .unwrap_or_default()
+        .join("mouse-reveal")
+}
+
+pub fn config_path() -> std::path::PathBuf {
+    config_dir().join("config.toml")
+}
+
+pub fn load() -> Config {
+    let path = config_path();
+
+    match std::fs::read_to_string(&path) {
+        Ok(content) => parse(&content).unwrap_or_else(|e| {
+            eprintln!("Invalid config {}: {}", path.display(), e);
+            Config::new()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Config::new(),
+        Err(e) => {
+            eprintln!("Unable to read config {}: {}", path.display(), e);
+            Config::new()
+        }
+    }
+}
+
+// Reads the edited config file, None when it can't be used and the running config stays
+pub fn reload() -> Option<Config> {
+    let path = config_path();
+
+    match std::fs::read_to_string(&path) {
+        Ok(content) => parse(&content)
+            .map_err(|e| {
+                eprintln!(
+                    "Invalid config {}, keeping the previous one: {}",
+                    path.display(),
+                    e
+                )
+            })
+            .ok(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Some(Config::new()),
+        Err(e) => {
+            eprintln!("Unable to read config {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// Modification time of the config file, to notice edits while running
+pub fn modified() -> Option<std::time::SystemTime> {
+    std::fs::metadata(config_path()).ok()?.modified().ok()
+}
+
+pub fn parse(content: &str) -> Result<Config, toml::de::Error> {
+    parse_with(content, RENAMES)
+}
+
+fn parse_with(content: &str, renames: &[(i64, &str, &str)]) -> Result<Config, toml::de::Error> {
+    let mut table: toml::Table = toml::from_str(content)?;
+    migrate(&mut table, renames);
+    let explicit = table.keys().cloned().collect();
+    let config: Config = toml::Value::Table(table).try_into()?;
+    Ok(Config { explicit, ..config })
+}
+
+// Updates the given settings in the config file, leaving the others and comments untouched
+pub fn save_values(values: &[(&str, toml::Value)]) -> std::io::Result<()> {
+    let path = config_path();
+    let previous = match std::fs::read_to_string(&path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    let mut document = previous
+        .as_deref()
+        .unwrap_or_default()
+        .parse::<toml_edit::Document>()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    if !document.contains_key("version") {
+        document["version"] = toml_edit::value(CONFIG_VERSION);
+    }
+    for (key, value) in values {
+        let mut value = value
+            .to_string()
+            .parse::<toml_edit::Value>()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        // Keeps a trailing comment of the replaced value
+        if let Some(old) = document.get(key).and_then(toml_edit::Item::as_value) {
+            *value.decor_mut() = old.decor().clone();
+        }
+        document[*key] = toml_edit::Item::Value(value);
+    }
+
+    std::fs::create_dir_all(config_dir())?;
+    // A config symlinked from a dotfiles repository stays a symlink, the target is replaced
+    let path = match previous {
+        Some(_) => std::fs::canonicalize(&path)?,
+        None => path,
+    };
+    // Only the config before the latest save is kept
+    if previous.is_some() {
+        std::fs::copy(&path, path.with_extension("toml.bak"))?;
+    }
+
+    // Renaming is atomic, a crash leaves either the old or the new config behind
+    let temporary = path.with_extension("toml.tmp");
+    let mut file = std::fs::File::create(&temporary)?;
+    if previous.is_some() {
+        file.set_permissions(std::fs::metadata(&path)?.permissions())?;
+    }
+    std::io::Write::write_all(&mut file, document.to_string().as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&temporary, &path)?;
+    // The rename itself only lasts through a crash once the directory is written
+    match path.parent() {
+        Some(dir) => std::fs::File::open(dir)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+// Returns a copy of the config with a single setting replaced
+pub fn with_value(config: &Config, key: &str, value: toml::Value) -> Result<Config, String> {
+    if !FIELDS.iter().any(|(name, _)| *name == key) {
+        return Err(format!("unknown setting `{}`", key));
+    }
+
+    let mut table = match toml::Value::try_from(config) {
+        Ok(toml::Value::Table(table)) => table,
+        _ => return Err(String::from("unable to serialize config")),
+    };
+    table.insert(key.to_string(), value);
+    let mut updated: Config = toml::Value::Table(table)
+        .try_into()
+        .map_err(|e| e.to_string())?;
+    updated.explicit = config.explicit.clone();
+    updated.explicit.insert(key.to_string());
+    Ok(updated)
+}
+
+// Returns the config with the profiles for the device, time of day, focused application
+// and the active named profile applied
+pub fn effective(
+    base: &Config,
+    device: Option<DeviceClass>,
+    minute_of_day: u32,
+    class: Option<&WindowClass>,
+) -> Config {
+    let mut config = base.clone();
+    if let Some(device) = device {
+        config = apply_profile(
+            config,
+            &builtin_device_profile(device, base),
+            "device profile",
+        );
+        if let Some(profile) = base.devices.get(&device) {
+            config = apply_profile(config, profile, "device profile");
+        }
+    }
+    if let Some(profile) = scheduled(base, minute_of_day) {
+        config = apply_profile(config, profile, "schedule");
+    }
+    if let Some((_, profile)) =
+        class.and_then(|class| base.apps.iter().find(|(name, _)| class.matches(name)))
+    {
+        config = apply_profile(config, profile, "app profile");
+    }
+    if let Some(name) = base.active_profile.as_deref() {
+        match named_profile(base, name) {
+            Some(profile) => config = apply_profile(config, &profile, "profile"),
+            None => eprintln!("Config: there is no profile `{}`", name),
+        }
+    }
+    config
+}
+
+// A [profile.<name>] table of the config, extending the built-in profile of that name
+pub fn named_profile(base: &Config, name: &str) -> Option<toml::Table> {
+    let mut profile = builtin_named_profile(name);
+    if let Some(custom) = base.profile.get(name) {
+        profile
+            .get_or_insert_with(toml::Table::new)
+            .extend(custom.clone());
+    }
+    profile
+}
+
+// Large and always visible for screencasts and teaching
+fn builtin_named_profile(name: &str) -> Option<toml::Table> {
+    if name != PRESENTATION_PROFILE {
+        return None;
+    }
+    let mut profile = toml::Table::new();
+    profile.insert(String::from("highlight"), toml::Value::Boolean(true));
+    profile.insert(
+        String::from("highlight_intensity"),
+        toml::Value::Float(600.0),
+    );
+    profile.insert(String::from("click_rings"), toml::Value::Boolean(true));
+    profile.insert(String::from("window_size"), toml::Value::Integer(320));
+    Some(profile)
+}
+
+// TrackPoints report small relative deltas, the defaults are tuned for touchpads.
+// Settings of the user are kept, a [devices.trackpoint] table overrides them for trackpoints only.
+fn builtin_device_profile(device: DeviceClass, base: &Config) -> toml::Table {
+    let mut profile = toml::Table::new();
+    if device == DeviceClass::Trackpoint {
+        profile.insert(String::from("threshold"), toml::Value::Float(400.0));
+        profile.insert(String::from("accel"), toml::Value::Float(400.0));
+    }
+    profile.retain(|key, _| !base.explicit.contains(key));
+    profile
+}
+
+// The latest entry starting before the given time, wrapping around to the previous day
+fn scheduled(config: &Config, minute_of_day: u32) -> Option<&toml::Table> {
+    let entries = config
+        .schedule
+        .iter()
+        .filter_map(|(time, profile)| Some((parse_time(time)?, profile)));
+    entries
+        .clone()
+        .filter(|(start, _)| *start <= minute_of_day)
+        .max_by_key(|(start, _)| *start)
+        .or_else(|| entries.max_by_key(|(start, _)| *start))
+        .map(|(_, profile)| profile)
+}
+
+// Minutes since midnight of a "HH:MM" time
+fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+fn apply_profile(mut config: Config, profile: &toml::Table, source: &str) -> Config {
+    for (key, value) in profile {
+        match with_value(&config, key, value.clone()) {
+            Ok(updated) => config = updated,
+            Err(e) => eprintln!("Config: ignoring `{}` in {}: {}", key, source, e),
+        }
+    }
+    config
+}
+
+fn migrate(table: &mut toml::Table, renames: &[(i64, &str, &str)]) {
+    // Files without a version predate versioning
+    let version = match table.remove("version") {
+        Some(toml::Value::Integer(version)) => version,
+        Some(other) => {
+            eprintln!("Config: ignoring invalid version {}", other);
+            CONFIG_VERSION
+        }
+        None => 0,
+    };
+
+    if version > CONFIG_VERSION {
+        eprintln!(
+            "Config: version {} is newer than supported version {}, some settings may be ignored",
+            version, CONFIG_VERSION
+        );
+    }
+
+    for (since, old, new) in renames.iter().filter(|(since, _, _)| version < *since) {
+        if let Some(value) = table.remove(*old) {
+            eprintln!(
+                "Config: `{}` is deprecated since version {}, use `{}` instead",
+                old, since, new
+            );
+            table.entry(new.to_string()).or_insert(value);
+        }
+    }
+
+    for key in table.keys() {
+        if !FIELDS.iter().any(|(name, _)| name == key) {
+            eprintln!("Config: ignoring unknown setting `{}`", key);
+        }
+    }
+
+    if let Some(toml::Value::Table(schedule)) = table.get("schedule") {
+        for time in schedule.keys().filter(|time| parse_time(time).is_none()) {
+            eprintln!("Config: ignoring schedule entry `{}`, expected HH:MM", time);
+        }
+    }
+}
+
+// Values that differ from the defaults, in the order of the documented fields
+pub fn changed_values(config: &Config) -> Vec<(&'static str, toml::Value)> {
+    let table = |config: Config| match toml::Value::try_from(config) {
+        Ok(toml::Value::Table(table)) => table,
+        _ => toml::Table::new(),
+    };
+    let (values, defaults) = (table(config.clone()), table(Config::new()));
+
+    FIELDS
+        .iter()
+        .filter_map(|(name, _)| {
+            let value = values.get(*name)?;
+            (defaults.get(*name) != Some(value)).then(|| (*name, value.clone()))
+        })
+        .collect()
+}
+
+pub fn default_text() -> String {
+    let defaults = match toml::Value::try_from(Config::new()) {
+        Ok(toml::Value::Table(table)) => table,
+        _ => toml::Table::new(),
+    };
+
+    let mut text = format!(
+        "# Mouse reveal configuration, place at {}\n\n# Format version of this file\nversion = {}\n",
+        config_path().display(),
+        CONFIG_VERSION
+    );
+    for (name, doc) in FIELDS {
+        text.push_str(&format!("\n# {}\n", doc));
+        match defaults.get(*name) {
+            Some(value) => text.push_str(&format!("{} = {}\n", name, value)),
+            None => {
+                let example = EXAMPLES
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map_or("", |(_, example)| example);
+                text.push_str(&format!("# {} = {}\n", name, example));
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RENAMED: &[(i64, &str, &str)] = &[(1, "reveal_threshold", "threshold")];
+
+    #[test]
+    fn renamed_fields_of_older_versions_are_migrated() {
+        let config = parse_with("reveal_threshold = 2100.0\n", RENAMED).unwrap();
+        assert_eq!(config.threshold, 2100.0);
+        assert!(config.explicit.contains("threshold"));
+        assert!(!config.explicit.contains("reveal_threshold"));
+    }
+
+    #[test]
+    fn new_names_win_over_old_ones() {
+        let config =
+            parse_with("reveal_threshold = 2100.0\nthreshold = 1800.0\n", RENAMED).unwrap();
+        assert_eq!(config.threshold, 1800.0);
+    }
+
+    #[test]
+    fn current_versions_are_not_migrated() {
+        let content = format!("version = {}\nreveal_threshold = 2100.0\n", CONFIG_VERSION);
+        let config = parse_with(&content, RENAMED).unwrap();
+        assert_eq!(config.threshold, Config::new().threshold);
+    }
+
+    fn schedule() -> Config {
+        parse(
+            "[schedule.\"07:30\"]\nthreshold = 1500.0\n[schedule.\"21:00\"]\nthreshold = 3000.0\n",
+        )
+        .unwrap()
+    }
+
+    fn scheduled_threshold(config: &Config, time: &str) -> Option<toml::Value> {
+        scheduled(config, parse_time(time).unwrap())?
+            .get("threshold")
+            .cloned()
+    }
+
+    #[test]
+    fn schedule_uses_the_latest_entry_started() {
+        let config = schedule();
+        assert_eq!(scheduled_threshold(&config, "07:30"), Some(1500.0.into()));
+        assert_eq!(scheduled_threshold(&config, "20:59"), Some(1500.0.into()));
+        assert_eq!(scheduled_threshold(&config, "23:00"), Some(3000.0.into()));
+    }
+
+    #[test]
+    fn schedule_wraps_around_to_the_previous_day() {
+        let config = schedule();
+        assert_eq!(scheduled_threshold(&config, "02:00"), Some(3000.0.into()));
+        assert_eq!(scheduled_threshold(&config, "00:00"), Some(3000.0.into()));
+        assert_eq!(scheduled(&Config::new(), 120), None);
+    }
+
+    #[test]
+    fn times_are_hours_and_minutes() {
+        assert_eq!(parse_time("00:00"), Some(0));
+        assert_eq!(parse_time("21:00"), Some(21 * 60));
+        assert_eq!(parse_time("23:59"), Some(23 * 60 + 59));
+        for time in ["24:00", "12:60", "-1:00", "12", "12:30:00", "noon", ""] {
+            assert_eq!(parse_time(time), None, "{}", time);
+        }
+    }
+
+    #[test]
+    fn invalid_schedule_times_are_skipped() {
+        let config = parse("[schedule.\"25:00\"]\nthreshold = 3000.0\n").unwrap();
+        assert_eq!(scheduled(&config, 120), None);
+    }
+}
@@ -0,0 +1,57 @@
+// Hides the cursor while the pointer rests, like unclutter. XFixes keeps the cursor
+// hidden only as long as this client is connected, a crash cannot leave it hidden.
+// Requests are only queued, the UI loop flushes them with its frame.
+pub struct CursorHider {
+    idle: std::time::Duration,
+    last_motion: std::time::Instant,
+    hidden: bool,
+}
+
+impl CursorHider {
+    pub fn new(conn: &xcb::Connection, seconds: f64) -> Option<CursorHider> {
+        if seconds <= 0.0 {
+            return None;
+        }
+
+        // HideCursor needs version 4, the server ignores clients that never asked
+        let version = conn.wait_for_reply(conn.send_request(&xcb::xfixes::QueryVersion {
+            client_major_version: 4,
+            client_minor_version: 0,
+        }));
+        match version {
+            Ok(version) if version.major_version() >= 4 => {}
+            _ => {
+                eprintln!("The X server has no XFixes 4, the cursor is not hidden");
+                return None;
+            }
+        }
+
+        Some(CursorHider {
+            idle: std::time::Duration::from_secs_f64(seconds),
+            last_motion: std::time::Instant::now(),
+            hidden: false,
+        })
+    }
+
+    // Returns true when the cursor was hidden and the motion brought it back
+    pub fn update(&mut self, conn: &xcb::Connection, root: xcb::x::Window, moving: bool) -> bool {
+        if moving {
+            self.last_motion = std::time::Instant::now();
+            if self.hidden {
+                self.show(conn, root);
+                return true;
+            }
+        } else if !self.hidden && self.last_motion.elapsed() > self.idle {
+            conn.send_request(&xcb::xfixes::HideCursor { window: root });
+            self.hidden = true;
+        }
+        false
+    }
+
+    pub fn show(&mut self, conn: &xcb::Connection, root: xcb::x::Window) {
+        if std::mem::take(&mut self.hidden) {
+            conn.send_request(&xcb::xfixes::ShowCursor { window: root });
+        }
+        self.last_motion = std::time::Instant::now();
+    }
+}
@@ -0,0 +1,76 @@
+use crate::models::Desktop;
+
+// Picks the desktop environment from the session environment unless overridden
+pub fn resolve(choice: Desktop) -> Desktop {
+    if choice != Desktop::Auto {
+        return choice;
+    }
+
+    let current = std::env::var("XDG_CURRENT_DESKTOP")
+        .or_else(|_| std::env::var("DESKTOP_SESSION"))
+        .unwrap_or_default()
+        .to_lowercase();
+    // XDG_CURRENT_DESKTOP is a colon separated list, e.g. "ubuntu:GNOME"
+    if current.split(':').any(|name| name == "gnome") {
+        Desktop::Gnome
+    } else if current.split(':').any(|name| name == "kde")
+        || std::env::var_os("KDE_FULL_SESSION").is_some()
+    {
+        Desktop::Kde
+    } else {
+        Desktop::Other
+    }
+}
+
+// KWin stacks newly mapped override-redirect windows below keep-above windows
+pub fn raise_on_show(desktop: Desktop) -> bool {
+    desktop == Desktop::Kde
+}
+
+// Flatpak mounts its metadata at the root of every sandbox
+pub fn sandboxed() -> bool {
+    std::path::Path::new("/.flatpak-info").exists()
+}
+
+// Xwayland advertises its own extension, older versions only name their outputs XWAYLAND*
+pub fn is_xwayland(conn: &xcb::Connection) -> bool {
+    let extension = conn
+        .wait_for_reply(conn.send_request(&xcb::x::QueryExtension { name: b"XWAYLAND" }))
+        .is_ok_and(|reply| reply.present());
+    extension || xwayland_outputs(conn)
+}
+
+fn xwayland_outputs(conn: &xcb::Connection) -> bool {
+    let Some(screen) = conn.get_setup().roots().next() else {
+        return false;
+    };
+    let Ok(resources) =
+        conn.wait_for_reply(conn.send_request(&xcb::randr::GetScreenResourcesCurrent {
+            window: screen.root(),
+        }))
+    else {
+        return false;
+    };
+    resources.outputs().iter().any(|output| {
+        conn.wait_for_reply(conn.send_request(&xcb::randr::GetOutputInfo {
+            output: *output,
+            config_timestamp: resources.config_timestamp(),
+        }))
+        .is_ok_and(|info| info.name().starts_with(b"XWAYLAND"))
+    })
+}
+
+pub fn report(desktop: Desktop, xwayland: bool) {
+    eprintln!("Desktop environment: {:?}", desktop);
+    if !xwayland {
+        return;
+    }
+
+    eprintln!("X server is Xwayland, running with reduced capabilities:");
+    eprintln!("  - the overlay is only stacked above X11 windows, not native Wayland windows");
+    eprintln!("  - the pointer position only updates while the cursor is over X11 windows");
+    eprintln!("  - evdev velocities keep working when /dev/input is accessible");
+    if desktop == Desktop::Gnome {
+        eprintln!("  - Mutter does not allow X11 clients to draw above the shell");
+    }
+}
@@ -0,0 +1,255 @@
+use crate::models::{Config, ThresholdUnit};
+
+// Longer gaps between updates are pauses of the UI loop, not frames
+const MAX_FRAME_INTERVAL: f64 = 0.1;
+
+pub struct Detector {
+    config: Config,
+    avg_weighted: f64,
+    avg_ui: f64,
+    last_update: std::time::Instant,
+}
+
+impl Detector {
+    pub fn new(config: Config) -> Detector {
+        Detector {
+            config,
+            avg_weighted: 0.0,
+            avg_ui: 0.0,
+            last_update: std::time::Instant::now(),
+        }
+    }
+
+    // Feeds the latest velocity and returns whether the cursor should be revealed
+    pub fn update(&mut self, velocity: f64) -> bool {
+        self.update_at(velocity, std::time::Instant::now())
+    }
+
+    // Same as update for a frame at the given time, replays pass their simulated time
+    pub fn update_at(&mut self, velocity: f64, now: std::time::Instant) -> bool {
+        // A single invalid velocity would otherwise stick in the averages forever
+        let velocity = if velocity.is_finite() { velocity } else { 0.0 };
+        self.avg_weighted = update_avg(&self.config, self.avg_weighted, velocity);
+        if !self.avg_weighted.is_finite() {
+            self.reset();
+        }
+
+        let elapsed = now
+            .saturating_duration_since(std::mem::replace(&mut self.last_update, now))
+            .as_secs_f64()
+            .min(MAX_FRAME_INTERVAL);
+        if self.avg_ui > 50.0 || self.avg_weighted > self.config.threshold {
+            // Follows the smoothed velocity over time, independent of the frame rate
+            let time_constant = if self.avg_weighted > self.avg_ui {
+                self.config.attack_ms
+            } else {
+                self.config.release_ms
+            } / 1000.0;
+            let alpha = if time_constant > 0.0 {
+                1.0 - (-elapsed / time_constant).exp()
+            } else {
+                1.0
+            };
+            self.avg_ui += alpha * (self.avg_weighted - self.avg_ui);
+            true
+        } else {
+            self.avg_ui = 0.0;
+            false
+        }
+    }
+
+    // Applies new settings while keeping the current averages
+    pub fn reconfigure(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    // Forces a reveal that fades out from the given intensity
+    pub fn trigger(&mut self, intensity: f64) {
+        self.avg_ui = self.avg_ui.max(intensity);
+    }
+
+    pub fn reset(&mut self) {
+        self.avg_weighted = 0.0;
+        self.avg_ui = 0.0;
+    }
+
+    // Smoothed velocity compared against the threshold
+    pub fn weighted(&self) -> f64 {
+        self.avg_weighted
+    }
+
+    // Smoothed velocity driving the animation
+    pub fn intensity(&self) -> f64 {
+        self.avg_ui
+    }
+}
+
+// Settings with the threshold converted to the units of the velocities
+pub fn device_threshold(config: &Config, diagonal: Option<f64>) -> Config {
+    match (config.threshold_unit, diagonal) {
+        (ThresholdUnit::Screen, Some(diagonal)) => Config {
+            threshold: config.threshold / 100.0 * diagonal,
+            ..config.clone()
+        },
+        _ => config.clone(),
+    }
+}
+
+// Drops impossible spikes and smooths single sample outliers of the raw velocities
+pub struct SpikeFilter {
+    cutoff: f64,
+    median: bool,
+    window: [f64; 3],
+    len: usize,
+}
+
+impl SpikeFilter {
+    pub fn new(config: &Config) -> SpikeFilter {
+        SpikeFilter {
+            cutoff: config.spike_cutoff,
+            median: config.median_filter,
+            window: [0.0; 3],
+            len: 0,
+        }
+    }
+
+    pub fn filter(&mut self, velocity: f64) -> Option<f64> {
+        if self.cutoff > 0.0 && velocity > self.cutoff {
+            return None;
+        }
+        if !self.median {
+            return Some(velocity);
+        }
+
+        self.window.rotate_left(1);
+        self.window[2] = velocity;
+        self.len = (self.len + 1).min(3);
+        if self.len < 3 {
+            return Some(velocity);
+        }
+
+        let mut sorted = self.window;
+        sorted.sort_by(f64::total_cmp);
+        Some(sorted[1])
+    }
+}
+
+// Gaps longer than this are pauses in the motion, not the report interval
+const MAX_REPORT_INTERVAL: f64 = 0.1;
+
+// Exponential smoothing over a fixed time, a 1000 Hz mouse is smoothed as much as a
+// 90 Hz touchpad. The coefficient follows the estimated report interval of the device.
+pub struct RateSmoother {
+    time_constant: f64,
+    interval: Option<f64>,
+    last: Option<std::time::Instant>,
+    smoothed: Option<f64>,
+}
+
+impl RateSmoother {
+    pub fn new(config: &Config) -> Option<RateSmoother> {
+        (config.smoothing_ms > 0.0).then(|| RateSmoother {
+            time_constant: config.smoothing_ms / 1000.0,
+            interval: None,
+            last: None,
+            smoothed: None,
+        })
+    }
+
+    pub fn smooth(&mut self, velocity: f64, time: std::time::Instant) -> f64 {
+        let gap = self
+            .last
+            .replace(time)
+            .map(|last| time.saturating_duration_since(last).as_secs_f64());
+        match gap {
+            Some(gap) if gap <= MAX_REPORT_INTERVAL => {
+                // Averaged, single late or coalesced reports barely move the estimate
+                self.interval = Some(self.interval.map_or(gap, |i| i * 0.9 + gap * 0.1));
+            }
+            _ => self.smoothed = None,
+        }
+
+        let alpha = match self.interval {
+            Some(interval) => 1.0 - (-interval / self.time_constant).exp(),
+            None => 1.0,
+        };
+        let smoothed = self.smoothed.map_or(velocity, |smoothed| {
+            smoothed + alpha * (velocity - smoothed)
+        });
+        self.smoothed = Some(smoothed);
+        smoothed
+    }
+
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.smoothed = None;
+    }
+}
+
+fn update_avg(config: &Config, avg: f64, velocity: f64) -> f64 {
+    let weight_input = (velocity / config.accel)
+        .max(config.accel_decay)
+        .min(config.accel_inc);
+    let weight_state = 1.0 - weight_input;
+
+    avg * weight_state * config.decay + velocity * weight_input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PointerInputEvent;
+    use std::time::{Duration, Instant};
+
+    fn event(x: i32, time: Instant) -> PointerInputEvent {
+        PointerInputEvent { x, y: x, time }
+    }
+
+    #[test]
+    fn velocity_is_finite_for_equal_and_reversed_times() {
+        let now = Instant::now();
+        let later = now + Duration::from_millis(5);
+        assert!(event(100, now).velocity(&event(0, now)).is_finite());
+        assert!(event(100, now).velocity(&event(0, later)).is_finite());
+        assert_eq!(event(0, now).velocity(&event(0, now)), 0.0);
+    }
+
+    #[test]
+    fn detector_recovers_from_non_finite_velocities() {
+        let mut detector = Detector::new(Config::new());
+        for velocity in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            detector.update(velocity);
+            assert!(detector.weighted().is_finite());
+            assert!(detector.intensity().is_finite());
+        }
+        assert!(!detector.update(0.0));
+    }
+
+    #[test]
+    fn release_follows_the_given_time() {
+        let mut detector = Detector::new(Config::new());
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        for ms in 0..300 {
+            assert_eq!(detector.update_at(3000.0, at(ms)), ms > 0);
+        }
+        // The fade out lasts for the given time, however often it is called
+        for ms in 300..400 {
+            assert!(detector.update_at(0.0, at(ms)));
+        }
+    }
+
+    #[test]
+    fn smoother_stays_finite_without_time_passing() {
+        let mut smoother = RateSmoother::new(&Config {
+            smoothing_ms: 20.0,
+            ..Config::new()
+        })
+        .unwrap();
+        let now = Instant::now();
+        let earlier = now - Duration::from_millis(5);
+        for (velocity, time) in [(10.0, now), (20.0, now), (30.0, earlier), (40.0, now)] {
+            assert!(smoother.smooth(velocity, time).is_finite());
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::models::{Monitor, Position32, ScreenLayout};
+use crate::themes::Theme;
+use crate::{ConnExt, OverlayWindow};
+
+struct MonitorWindow {
+    monitor: Monitor,
+    win: xcb::x::Window,
+    gfx: xcb::x::Gcontext,
+    visible: bool,
+}
+
+// One overlay per monitor, flashing the edges of the monitor holding the cursor
+pub struct EdgeFlash {
+    conn: std::sync::Arc<xcb::Connection>,
+    screen_num: usize,
+    theme: Theme,
+    width: u16,
+    windows: Vec<MonitorWindow>,
+}
+
+impl EdgeFlash {
+    pub fn new(
+        conn: std::sync::Arc<xcb::Connection>,
+        screen_num: usize,
+        layout: &ScreenLayout,
+        theme: Theme,
+        width: u32,
+    ) -> EdgeFlash {
+        let mut flash = EdgeFlash {
+            conn,
+            screen_num,
+            theme,
+            width: width.max(1) as u16,
+            windows: Vec::new(),
+        };
+        flash.rebuild(layout);
+        flash
+    }
+
+    // Recreates the windows after monitors were (un)plugged or resized
+    pub fn rebuild(&mut self, layout: &ScreenLayout) {
+        for window in self.windows.drain(..) {
+            self.conn.send_request(&(xcb::x::FreeGc { gc: window.gfx }));
+            self.conn
+                .send_request(&(xcb::x::DestroyWindow { window: window.win }));
+        }
+
+        for monitor in &layout.monitors {
+            let (win, _) = OverlayWindow::create_window(&self.conn, self.screen_num, 1);
+            self.conn.send_request(
+                &(xcb::x::ConfigureWindow {
+                    window: win,
+                    value_list: &[
+                        xcb::x::ConfigWindow::X(monitor.x),
+                        xcb::x::ConfigWindow::Y(monitor.y),
+                        xcb::x::ConfigWindow::Width(monitor.width as u32),
+                        xcb::x::ConfigWindow::Height(monitor.height as u32),
+                    ],
+                }),
+            );
+
+            // Only the edges are part of the window, the compositor never blends the rest
+            self.conn.send_request(
+                &(xcb::shape::Rectangles {
+                    operation: xcb::shape::So::Set,
+                    destination_kind: xcb::shape::Sk::Bounding,
+                    destination_window: win,
+                    x_offset: 0,
+                    y_offset: 0,
+                    ordering: xcb::x::ClipOrdering::Unsorted,
+                    rectangles: &edges(monitor, self.width),
+                }),
+            );
+
+            let gfx = self.conn.create_gcontext(win);
+            self.windows.push(MonitorWindow {
+                monitor: monitor.clone(),
+                win,
+                gfx,
+                visible: false,
+            });
+        }
+    }
+
+    pub fn show(&mut self, pointer: Position32, strength: f64) {
+        for window in self.windows.iter_mut() {
+            if !window.monitor.contains(pointer) {
+                hide(&self.conn, window);
+                continue;
+            }
+
+            if !window.visible {
+                window.visible = true;
+                self.conn
+                    .send_request(&(xcb::x::MapWindow { window: window.win }));
+            }
+
+            self.conn.send_request(
+                &(xcb::x::ChangeGc {
+                    gc: window.gfx,
+                    value_list: &[xcb::x::Gc::Foreground(
+                        self.theme.color.pixel(strength, self.theme.max_alpha),
+                    )],
+                }),
+            );
+            self.conn.send_request(
+                &(xcb::x::PolyFillRectangle {
+                    drawable: xcb::x::Drawable::Window(window.win),
+                    gc: window.gfx,
+                    rectangles: &edges(&window.monitor, self.width),
+                }),
+            );
+        }
+    }
+
+    pub fn hide(&mut self) {
+        for window in self.windows.iter_mut() {
+            hide(&self.conn, window);
+        }
+    }
+}
+
+fn hide(conn: &xcb::Connection, window: &mut MonitorWindow) {
+    if window.visible {
+        window.visible = false;
+        conn.send_request(&(xcb::x::UnmapWindow { window: window.win }));
+    }
+}
+
+// Window relative rectangles along the four edges of a monitor
+fn edges(monitor: &Monitor, width: u16) -> [xcb::x::Rectangle; 4] {
+    let w = monitor.width as u16;
+    let h = monitor.height as u16;
+    let width = width.min(w / 2).min(h / 2);
+    [
+        xcb::x::Rectangle {
+            x: 0,
+            y: 0,
+            width: w,
+            height: width,
+        },
+        xcb::x::Rectangle {
+            x: 0,
+            y: (h - width) as i16,
+            width: w,
+            height: width,
+        },
+        xcb::x::Rectangle {
+            x: 0,
+            y: 0,
+            width,
+            height: h,
+        },
+        xcb::x::Rectangle {
+            x: (w - width) as i16,
+            y: 0,
+            width,
+            height: h,
+        },
+    ]
+}
@@ -0,0 +1,149 @@
+use crate::detection::{Detector, RateSmoother, SpikeFilter};
+use crate::models::{Config, PointerInputEvent};
+use std::time::{Duration, Instant};
+
+// Xorshift, reproducible from the seed without pulling in a random number crate
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n.max(1)
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+const COORDINATES: &[i32] = &[0, 1, -1, i32::MAX, i32::MIN, 65535, -65536];
+
+// Motion the detector must survive, mixing plausible sequences with degenerate ones
+fn sequence(rng: &mut Rng, start: Instant) -> Vec<PointerInputEvent> {
+    let mut time = start;
+    let (mut x, mut y) = (0i32, 0i32);
+    (0..rng.below(500) + 2)
+        .map(|_| {
+            time += match rng.below(6) {
+                // Coalesced reports share a timestamp
+                0 => Duration::ZERO,
+                1 => Duration::from_nanos(1),
+                2 => Duration::from_micros(rng.below(2000)),
+                3 => Duration::from_secs(rng.below(5)),
+                _ => Duration::from_millis(rng.below(20) + 1),
+            };
+            (x, y) = match rng.below(8) {
+                0 => (
+                    COORDINATES[rng.below(COORDINATES.len() as u64) as usize],
+                    COORDINATES[rng.below(COORDINATES.len() as u64) as usize],
+                ),
+                1 => (x, y),
+                _ => (
+                    x.wrapping_add(rng.below(400) as i32 - 200),
+                    y.wrapping_add(rng.below(400) as i32 - 200),
+                ),
+            };
+            PointerInputEvent { x, y, time }
+        })
+        .collect()
+}
+
+// Settings within the ranges the config documents
+fn config(rng: &mut Rng) -> Config {
+    let defaults = Config::new();
+    Config {
+        decay: rng.unit(),
+        accel: [defaults.accel, 1e-9, 1e12][rng.below(3) as usize],
+        accel_decay: rng.unit(),
+        accel_inc: rng.unit(),
+        threshold: rng.unit() * 10000.0,
+        spike_cutoff: [0.0, defaults.spike_cutoff][rng.below(2) as usize],
+        median_filter: rng.below(2) == 0,
+        smoothing_ms: [0.0, 0.001, 8.0, 1000.0][rng.below(4) as usize],
+        attack_ms: [0.0, defaults.attack_ms, 1e9][rng.below(3) as usize],
+        release_ms: [0.0, defaults.release_ms, 1e9][rng.below(3) as usize],
+        ..defaults
+    }
+}
+
+// Returns the violated invariant of one run, if any
+fn run(rng: &mut Rng) -> Option<String> {
+    let config = config(rng);
+    let mut spike_filter = SpikeFilter::new(&config);
+    let mut smoother = RateSmoother::new(&config);
+    let mut detector = Detector::new(config);
+    let mut peak: f64 = 0.0;
+
+    let events = sequence(rng, Instant::now());
+    for (step, pair) in events.windows(2).enumerate() {
+        let velocity = pair[1].velocity(&pair[0]);
+        if !velocity.is_finite() || velocity < 0.0 {
+            return Some(format!(
+                "step {}: velocity {} between {:?} and {:?}",
+                step, velocity, pair[0], pair[1]
+            ));
+        }
+
+        let velocity = match spike_filter.filter(velocity) {
+            Some(velocity) => velocity,
+            None => continue,
+        };
+        let velocity = match smoother.as_mut() {
+            Some(smoother) => smoother.smooth(velocity, pair[1].time),
+            None => velocity,
+        };
+        if !velocity.is_finite() {
+            return Some(format!("step {}: filtered velocity {}", step, velocity));
+        }
+        peak = peak.max(velocity);
+
+        if rng.below(50) == 0 {
+            detector.trigger(rng.unit() * 2000.0);
+        }
+        detector.update(velocity);
+
+        // Smoothing only ever averages, it never exceeds the inputs or the triggers
+        let weighted = detector.weighted();
+        if !weighted.is_finite() || weighted > peak * (1.0 + 1e-9) {
+            return Some(format!(
+                "step {}: smoothed velocity {} above the peak {}",
+                step, weighted, peak
+            ));
+        }
+        if !detector.intensity().is_finite() || detector.intensity() > peak.max(2000.0) {
+            return Some(format!(
+                "step {}: intensity {} out of bounds",
+                step,
+                detector.intensity()
+            ));
+        }
+    }
+    None
+}
+
+// Returns whether all runs kept the invariants
+pub fn fuzz(iterations: u64, seed: u64) -> bool {
+    let mut failures = 0;
+    for iteration in 0..iterations {
+        // Every run is reproducible on its own from its seed
+        let run_seed = seed.wrapping_add(iteration).max(1);
+        let mut rng = Rng(run_seed);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| run(&mut rng)));
+        let failure = match result {
+            Ok(failure) => failure,
+            Err(_) => Some(String::from("panicked")),
+        };
+        if let Some(failure) = failure {
+            failures += 1;
+            println!("FAIL seed {}: {}", run_seed, failure);
+        }
+    }
+    println!("{} of {} runs failed", failures, iterations);
+    failures == 0
+}
@@ -0,0 +1,116 @@
+use crate::models::Config;
+use crate::themes::Color;
+
+struct SettingsApp {
+    config: Config,
+    color: [u8; 3],
+    message: String,
+}
+
+impl SettingsApp {
+    fn new(config: Config) -> SettingsApp {
+        let color = config.color.unwrap_or_else(|| {
+            // The window opens once, waiting for the desktop preference is fine here
+            let mut appearance = crate::appearance::Appearance::lookup();
+            appearance.wait();
+            crate::themes::Theme::load(&config.theme)
+                .for_scheme(appearance.color_scheme(config.color_scheme))
+                .color
+        });
+        SettingsApp {
+            config,
+            color: [color.red, color.green, color.blue],
+            message: String::new(),
+        }
+    }
+
+    // Changes are previewed live by the running daemon
+    fn preview(&mut self, key: &str, value: toml::Value) {
+        let command = format!("set {} {}", key, value);
+        self.message = match crate::ipc::send(&command) {
+            Ok(reply) if reply.starts_with("error") => reply,
+            Ok(_) => String::from("Previewing in the running daemon"),
+            Err(e) => format!("Daemon not reachable, no live preview: {}", e),
+        };
+    }
+
+    fn save(&mut self) {
+        let values = [
+            ("threshold", toml::Value::Float(self.config.threshold)),
+            ("decay", toml::Value::Float(self.config.decay)),
+            ("accel", toml::Value::Float(self.config.accel)),
+            (
+                "window_size",
+                toml::Value::Integer(self.config.window_size as _),
+            ),
+            ("color", toml::Value::String(self.current_color().into())),
+        ];
+        self.message = match crate::config::save_values(&values) {
+            Ok(()) => format!("Saved to {}", crate::config::config_path().display()),
+            Err(e) => format!("Unable to save config: {}", e),
+        };
+    }
+
+    fn current_color(&self) -> Color {
+        Color::new(self.color[0], self.color[1], self.color[2])
+    }
+}
+
+impl eframe::App for SettingsApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Mouse Reveal");
+
+            if ui
+                .add(
+                    egui::Slider::new(&mut self.config.threshold, 100.0..=6000.0).text("Threshold"),
+                )
+                .changed()
+            {
+                self.preview("threshold", toml::Value::Float(self.config.threshold));
+            }
+            if ui
+                .add(egui::Slider::new(&mut self.config.decay, 0.8..=0.999).text("Decay"))
+                .changed()
+            {
+                self.preview("decay", toml::Value::Float(self.config.decay));
+            }
+            if ui
+                .add(egui::Slider::new(&mut self.config.accel, 100.0..=5000.0).text("Acceleration"))
+                .changed()
+            {
+                self.preview("accel", toml::Value::Float(self.config.accel));
+            }
+            if ui
+                .add(egui::Slider::new(&mut self.config.window_size, 50..=500).text("Size"))
+                .changed()
+            {
+                self.preview(
+                    "window_size",
+                    toml::Value::Integer(self.config.window_size as _),
+                );
+            }
+            ui.horizontal(|ui| {
+                ui.label("Color");
+                if ui.color_edit_button_srgb(&mut self.color).changed() {
+                    let color: String = self.current_color().into();
+                    self.preview("color", toml::Value::String(color));
+                }
+            });
+
+            ui.separator();
+            if ui.button("Save").clicked() {
+                self.save();
+            }
+            ui.label(&self.message);
+        });
+    }
+}
+
+pub fn run(config: Config) -> Result<(), eframe::Error> {
+    eframe::run_native(
+        "Mouse Reveal Settings",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Box::new(SettingsApp::new(config))),
+    )
+}
@@ -0,0 +1,65 @@
+// Rumble of the monitored device played on reveals. The effect holds its own handle
+// of the device, so the motion thread uploads it and the event thread plays it.
+#[derive(Clone, Default)]
+pub struct Haptics {
+    effect: std::sync::Arc<std::sync::Mutex<Option<evdev::FFEffect>>>,
+}
+
+impl Haptics {
+    pub fn attach(&self, device: &mut evdev::Device, length_ms: u32) {
+        let rumble = device
+            .supported_ff()
+            .is_some_and(|ff| ff.contains(evdev::FFEffectType::FF_RUMBLE));
+        if !rumble {
+            eprintln!("Device has no force feedback, haptic feedback is disabled");
+            return;
+        }
+
+        let effect = device.upload_ff_effect(evdev::FFEffectData {
+            direction: 0,
+            trigger: evdev::FFTrigger::default(),
+            replay: evdev::FFReplay {
+                length: length_ms.min(u16::MAX as u32) as u16,
+                delay: 0,
+            },
+            kind: evdev::FFEffectKind::Rumble {
+                strong_magnitude: 0,
+                weak_magnitude: u16::MAX,
+            },
+        });
+        match effect {
+            Ok(effect) => *self.effect.lock().unwrap() = Some(effect),
+            // Uploading needs write access to the device, reading events does not
+            Err(e) => eprintln!("Unable to upload the haptic effect: {}", e),
+        }
+    }
+
+    pub fn detach(&self) {
+        *self.effect.lock().unwrap() = None;
+    }
+
+    fn play(&self) {
+        let mut effect = self.effect.lock().unwrap();
+        if let Some(Err(e)) = effect.as_mut().map(|effect| effect.play(1)) {
+            eprintln!("Unable to play the haptic effect: {}", e);
+            *effect = None;
+        }
+    }
+}
+
+// Plays the effect on every reveal and passes the events on
+pub fn forward(
+    haptics: Haptics,
+    events: std::sync::mpsc::Receiver<crate::status::OverlayEvent>,
+) -> std::sync::mpsc::Receiver<crate::status::OverlayEvent> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for event in events {
+            if event == crate::status::OverlayEvent::Reveal {
+                haptics.play();
+            }
+            let _ = tx.send(event);
+        }
+    });
+    rx
+}
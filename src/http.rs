@@ -0,0 +1,42 @@
+use crate::ipc::Request;
+
+// Maps a few paths onto IPC commands, e.g. `curl -X POST localhost:8737/reveal`
+pub fn start_http_thread(port: u16, ipc: std::sync::mpsc::Sender<Request>) {
+    // Never reachable from other machines, there is no authentication
+    let server = match tiny_http::Server::http(("127.0.0.1", port)) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Unable to listen on 127.0.0.1:{}: {}", port, e);
+            return;
+        }
+    };
+
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let command = match (request.method(), request.url()) {
+                (tiny_http::Method::Get, "/status") => Some("status"),
+                (tiny_http::Method::Get, "/stats") => Some("stats"),
+                (tiny_http::Method::Post, "/reveal") => Some("reveal"),
+                (tiny_http::Method::Post, "/pause") => Some("pause"),
+                (tiny_http::Method::Post, "/resume") => Some("resume"),
+                _ => None,
+            };
+
+            let response = match command {
+                Some(command) => {
+                    let reply = crate::ipc::request(&ipc, command);
+                    let status = if reply.starts_with("error:") {
+                        500
+                    } else {
+                        200
+                    };
+                    tiny_http::Response::from_string(reply + "\n").with_status_code(status)
+                }
+                None => tiny_http::Response::from_string("not found\n").with_status_code(404),
+            };
+            if let Err(e) = request.respond(response) {
+                eprintln!("HTTP client error: {}", e);
+            }
+        }
+    });
+}
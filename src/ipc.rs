@@ -0,0 +1,149 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Pause,
+    Resume,
+    Toggle,
+    Status,
+    Reveal,
+    Highlight,
+    Timing,
+    Stats,
+    // Switches to a named profile, or back to the plain settings without a name
+    Profile { name: Option<String> },
+    Presentation,
+    Set { key: String, value: toml::Value },
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("pause") => Ok(Command::Pause),
+            Some("resume") => Ok(Command::Resume),
+            Some("toggle") => Ok(Command::Toggle),
+            Some("status") => Ok(Command::Status),
+            Some("reveal") => Ok(Command::Reveal),
+            Some("highlight") => Ok(Command::Highlight),
+            Some("timing") => Ok(Command::Timing),
+            Some("stats") => Ok(Command::Stats),
+            Some("presentation") => Ok(Command::Presentation),
+            Some("profile") => Ok(Command::Profile {
+                name: words.next().map(String::from),
+            }),
+            Some("set") => {
+                let key = words.next().ok_or("usage: set KEY VALUE")?;
+                let value = words.collect::<Vec<&str>>().join(" ");
+                if value.is_empty() {
+                    return Err(String::from("usage: set KEY VALUE"));
+                }
+                Ok(Command::Set {
+                    key: key.to_string(),
+                    value: parse_value(&value),
+                })
+            }
+            Some(other) => Err(format!("unknown command: {}", other)),
+            None => Err(String::from("empty command")),
+        }
+    }
+}
+
+// Values are TOML literals, anything else is taken as a plain string
+pub fn parse_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+pub struct Request {
+    pub command: Command,
+    pub reply: std::sync::mpsc::Sender<String>,
+}
+
+pub fn socket_path() -> std::path::PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => std::path::Path::new(&dir).join("mouse-reveal.sock"),
+        None => std::env::temp_dir().join(format!(
+            "mouse-reveal-{}.sock",
+            std::env::var("USER").unwrap_or_default()
+        )),
+    }
+}
+
+// Returns the socket this instance owns, to remove it on exit
+pub fn start_ipc_thread(sender: std::sync::mpsc::Sender<Request>) -> Option<std::path::PathBuf> {
+    let path = socket_path();
+    if UnixStream::connect(&path).is_ok() {
+        eprintln!("Another instance owns {}, IPC disabled", path.display());
+        return None;
+    }
+    // Left over from a previous run
+    let _ = std::fs::remove_file(&path);
+
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Unable to listen on {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                // A client keeping its connection open doesn't hold up the others
+                Ok(stream) => {
+                    let sender = sender.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = handle_client(stream, &sender) {
+                            eprintln!("IPC client error: {}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("IPC connection failed: {}", e),
+            }
+        }
+    });
+    Some(path)
+}
+
+fn handle_client(
+    stream: UnixStream,
+    sender: &std::sync::mpsc::Sender<Request>,
+) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        writeln!(writer, "{}", request(sender, &line))?;
+    }
+    Ok(())
+}
+
+// Hands a command line to the UI loop and waits for its reply
+pub fn request(sender: &std::sync::mpsc::Sender<Request>, line: &str) -> String {
+    match Command::parse(line) {
+        Ok(command) => {
+            let (reply, response) = std::sync::mpsc::channel();
+            if sender.send(Request { command, reply }).is_err() {
+                return String::from("error: daemon stopped");
+            }
+            response
+                .recv()
+                .unwrap_or_else(|_| String::from("error: no reply"))
+        }
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+pub fn send(command: &str) -> std::io::Result<String> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    writeln!(stream, "{}", command)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
@@ -0,0 +1,88 @@
+use crate::logging::LogEvent;
+use crate::models::{Config, LearnMode};
+
+const BUCKET: f64 = 10.0;
+const BUCKETS: usize = 2000;
+
+// Keeps a histogram of the velocities seen during a learning window
+// and derives a threshold from it at the end of each window.
+pub struct Learner {
+    mode: LearnMode,
+    percentile: f64,
+    window: std::time::Duration,
+    started: std::time::Instant,
+    threshold: f64,
+    histogram: Vec<u64>,
+    samples: u64,
+}
+
+impl Learner {
+    pub fn new(config: &Config) -> Option<Learner> {
+        if config.learn_threshold == LearnMode::Off {
+            return None;
+        }
+
+        Some(Learner {
+            mode: config.learn_threshold,
+            percentile: config.learn_percentile.clamp(0.0, 100.0),
+            window: std::time::Duration::from_secs_f64(config.learn_hours.max(0.01) * 3600.0),
+            started: std::time::Instant::now(),
+            threshold: config.threshold,
+            histogram: vec![0; BUCKETS],
+            samples: 0,
+        })
+    }
+
+    pub fn push(&mut self, event: &LogEvent) {
+        if let LogEvent::Velocity { velocity, .. } = event {
+            // Idle samples would drag the percentile down
+            if *velocity <= 0.0 || !velocity.is_finite() {
+                return;
+            }
+            let idx = ((velocity / BUCKET) as usize).min(BUCKETS - 1);
+            self.histogram[idx] += 1;
+            self.samples += 1;
+        }
+    }
+
+    fn suggestion(&self) -> Option<f64> {
+        if self.samples == 0 {
+            return None;
+        }
+
+        let target = ((self.samples as f64) * self.percentile / 100.0).ceil() as u64;
+        let mut seen = 0;
+        self.histogram
+            .iter()
+            .position(|count| {
+                seen += count;
+                seen >= target
+            })
+            .map(|idx| (idx + 1) as f64 * BUCKET)
+    }
+
+    // Sends the learned threshold once a window completes and auto-apply is enabled
+    pub fn tick(&mut self, learned: &std::sync::mpsc::Sender<f64>) {
+        if self.started.elapsed() < self.window {
+            return;
+        }
+
+        match self.suggestion() {
+            Some(suggestion) => {
+                eprintln!(
+                    "Learned threshold: {:.0} (p{} of {} samples, current {:.0})",
+                    suggestion, self.percentile, self.samples, self.threshold
+                );
+                if self.mode == LearnMode::Apply {
+                    self.threshold = suggestion;
+                    let _ = learned.send(suggestion);
+                }
+            }
+            None => eprintln!("No motion seen while learning the threshold"),
+        }
+
+        self.started = std::time::Instant::now();
+        self.histogram.iter_mut().for_each(|count| *count = 0);
+        self.samples = 0;
+    }
+}
@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
 pub enum LogEvent {
     Velocity {
         velocity: f64,
@@ -30,32 +33,465 @@ impl Capture {
     pub fn events(&self) -> &Vec<LogEvent> {
         &self.events
     }
+
+    pub fn records(&self, start: std::time::Instant) -> Vec<CaptureRecord> {
+        self.events
+            .iter()
+            .map(|event| CaptureRecord::new(event, start))
+            .collect()
+    }
+}
+
+// Serializable form of a LogEvent, times are seconds since the capture start
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CaptureRecord {
+    Velocity {
+        time: f64,
+        velocity: f64,
+    },
+    Evdev {
+        time: f64,
+        event_type: u16,
+        code: u16,
+        value: i32,
+    },
+    PointerInput {
+        time: f64,
+        x: i32,
+        y: i32,
+    },
+}
+
+impl CaptureRecord {
+    pub fn new(event: &LogEvent, start: std::time::Instant) -> CaptureRecord {
+        let since = |time: &std::time::Instant| time.saturating_duration_since(start).as_secs_f64();
+        match event {
+            LogEvent::Velocity { velocity, time } => CaptureRecord::Velocity {
+                time: since(time),
+                velocity: *velocity,
+            },
+            LogEvent::Evdev { time, evdev_event } => CaptureRecord::Evdev {
+                time: since(time),
+                event_type: evdev_event.event_type().0,
+                code: evdev_event.code(),
+                value: evdev_event.value(),
+            },
+            LogEvent::PointerInput { time, x, y } => CaptureRecord::PointerInput {
+                time: since(time),
+                x: *x,
+                y: *y,
+            },
+        }
+    }
+}
+
+const BINARY_MAGIC: &[u8; 8] = b"MRCAP\0\0\x01";
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum CaptureFormat {
+    Json,
+    Bin,
+}
+
+impl CaptureFormat {
+    // Captures ending in .bin use the binary format, anything else is JSON
+    pub fn of(path: &std::path::Path) -> CaptureFormat {
+        if path.extension().is_some_and(|extension| extension == "bin") {
+            CaptureFormat::Bin
+        } else {
+            CaptureFormat::Json
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            CaptureFormat::Json => "json",
+            CaptureFormat::Bin => "bin",
+        }
+    }
+}
+
+pub fn write_records(path: &std::path::Path, records: &[CaptureRecord]) -> std::io::Result<()> {
+    write_records_as(path, records, CaptureFormat::of(path))
+}
+
+pub fn write_records_as(
+    path: &std::path::Path,
+    records: &[CaptureRecord],
+    format: CaptureFormat,
+) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    if format == CaptureFormat::Bin {
+        let mut writer = BinaryWriter::new(file)?;
+        for record in records {
+            writer.write(record)?;
+        }
+        return writer.finish();
+    }
+    serde_json::to_writer(&mut file, records)?;
+    std::io::Write::flush(&mut file)
+}
+
+pub fn read_records(path: &std::path::Path) -> std::io::Result<Vec<CaptureRecord>> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let binary = std::io::BufRead::fill_buf(&mut file)?.starts_with(BINARY_MAGIC);
+    if binary {
+        return read_binary(file);
+    }
+    Ok(serde_json::from_reader(file)?)
+}
+
+// Length prefixed records: u16 length of the rest, u8 kind, u64 nanoseconds since
+// the capture start and the payload of the kind, all little endian.
+pub struct BinaryWriter<W: std::io::Write> {
+    writer: W,
+}
+
+impl<W: std::io::Write> BinaryWriter<W> {
+    pub fn new(mut writer: W) -> std::io::Result<BinaryWriter<W>> {
+        writer.write_all(BINARY_MAGIC)?;
+        Ok(BinaryWriter { writer })
+    }
+
+    pub fn write(&mut self, record: &CaptureRecord) -> std::io::Result<()> {
+        let nanos = |time: f64| ((time.max(0.0) * 1e9).round() as u64).to_le_bytes();
+        let mut buffer = [0u8; 32];
+        let mut len = 0;
+        let mut put = |bytes: &[u8]| {
+            buffer[len..len + bytes.len()].copy_from_slice(bytes);
+            len += bytes.len();
+        };
+        match *record {
+            CaptureRecord::Velocity { time, velocity } => {
+                put(&[0]);
+                put(&nanos(time));
+                put(&velocity.to_le_bytes());
+            }
+            CaptureRecord::Evdev {
+                time,
+                event_type,
+                code,
+                value,
+            } => {
+                put(&[1]);
+                put(&nanos(time));
+                put(&event_type.to_le_bytes());
+                put(&code.to_le_bytes());
+                put(&value.to_le_bytes());
+            }
+            CaptureRecord::PointerInput { time, x, y } => {
+                put(&[2]);
+                put(&nanos(time));
+                put(&x.to_le_bytes());
+                put(&y.to_le_bytes());
+            }
+        }
+        self.writer.write_all(&(len as u16).to_le_bytes())?;
+        self.writer.write_all(&buffer[..len])
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn read_binary(mut reader: impl std::io::Read) -> std::io::Result<Vec<CaptureRecord>> {
+    let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+
+    let mut records = Vec::new();
+    loop {
+        let mut len = [0u8; 2];
+        let read = reader.read(&mut len[..1])?;
+        if read == 0 {
+            break;
+        }
+        let mut record = Vec::new();
+        let complete = reader.read_exact(&mut len[1..]).and_then(|()| {
+            record.resize(u16::from_le_bytes(len) as usize, 0);
+            reader.read_exact(&mut record)
+        });
+        match complete {
+            Ok(()) => {}
+            // What an interrupted capture leaves behind, everything before it is intact
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                eprintln!(
+                    "Capture ends in a truncated record, read {} records",
+                    records.len()
+                );
+                break;
+            }
+            Err(e) => return Err(e),
+        }
+        if record.len() < 9 {
+            return Err(invalid("truncated record"));
+        }
+
+        let time = u64::from_le_bytes(record[1..9].try_into().unwrap()) as f64 / 1e9;
+        let payload = &record[9..];
+        let field = |offset: usize, size: usize| {
+            payload
+                .get(offset..offset + size)
+                .ok_or_else(|| invalid("truncated record"))
+        };
+        // Unknown kinds come from newer versions and are skipped
+        match record[0] {
+            0 => records.push(CaptureRecord::Velocity {
+                time,
+                velocity: f64::from_le_bytes(field(0, 8)?.try_into().unwrap()),
+            }),
+            1 => records.push(CaptureRecord::Evdev {
+                time,
+                event_type: u16::from_le_bytes(field(0, 2)?.try_into().unwrap()),
+                code: u16::from_le_bytes(field(2, 2)?.try_into().unwrap()),
+                value: i32::from_le_bytes(field(4, 4)?.try_into().unwrap()),
+            }),
+            2 => records.push(CaptureRecord::PointerInput {
+                time,
+                x: i32::from_le_bytes(field(0, 4)?.try_into().unwrap()),
+                y: i32::from_le_bytes(field(4, 4)?.try_into().unwrap()),
+            }),
+            _ => {}
+        }
+    }
+    Ok(records)
+}
+
+// Events queued for the capture thread, beyond that the input threads drop them
+pub const CAPTURE_QUEUE: usize = 4096;
+
+#[derive(Default)]
+pub struct CaptureStats {
+    pub sent: std::sync::atomic::AtomicU64,
+    pub dropped: std::sync::atomic::AtomicU64,
+}
+
+impl CaptureStats {
+    pub fn report_dropped(&self) {
+        let dropped = self.dropped.load(std::sync::atomic::Ordering::Relaxed);
+        if dropped > 0 {
+            let total = dropped + self.sent.load(std::sync::atomic::Ordering::Relaxed);
+            eprintln!(
+                "Dropped {} of {} events ({:.1}%), the capture fell behind",
+                dropped,
+                total,
+                dropped as f64 * 100.0 / total as f64
+            );
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct CaptureEmitter {
     start: std::time::Instant,
     expires: std::time::Duration,
-    emitter: std::sync::mpsc::Sender<LogEvent>,
+    // None when nothing captures or learns from the events
+    emitter: Option<std::sync::mpsc::SyncSender<LogEvent>>,
+    // Set once the capture expired or its thread stopped, shared by all clones
+    closed: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    stats: std::sync::Arc<CaptureStats>,
 }
 
 impl CaptureEmitter {
     pub fn new(
         start: std::time::Instant,
         expires: std::time::Duration,
-        emitter: std::sync::mpsc::Sender<LogEvent>,
+        emitter: std::sync::mpsc::SyncSender<LogEvent>,
     ) -> CaptureEmitter {
         CaptureEmitter {
             start,
             expires,
-            emitter,
+            emitter: Some(emitter),
+            closed: std::sync::Arc::default(),
+            stats: std::sync::Arc::default(),
         }
     }
 
-    pub fn emit(&self, event: LogEvent) {
+    pub fn disabled() -> CaptureEmitter {
+        CaptureEmitter {
+            start: std::time::Instant::now(),
+            expires: std::time::Duration::ZERO,
+            emitter: None,
+            closed: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true)),
+            stats: std::sync::Arc::default(),
+        }
+    }
+
+    pub fn stats(&self) -> std::sync::Arc<CaptureStats> {
+        std::sync::Arc::clone(&self.stats)
+    }
+
+    // Input threads check this before building events, after the capture ended it
+    // costs a single load per event
+    pub fn active(&self) -> bool {
+        if self.closed.load(std::sync::atomic::Ordering::Relaxed) {
+            return false;
+        }
         if self.start.elapsed() > self.expires {
+            self.close();
+            return false;
+        }
+        true
+    }
+
+    fn close(&self) {
+        self.closed
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    // Never blocks, a slow capture thread must not stall the input threads
+    pub fn emit(&self, event: LogEvent) {
+        let Some(emitter) = self.emitter.as_ref().filter(|_| self.active()) else {
             return;
+        };
+        let counter = match emitter.try_send(event) {
+            Ok(()) => &self.stats.sent,
+            Err(std::sync::mpsc::TrySendError::Full(_)) => &self.stats.dropped,
+            // The capture thread stops once capturing and learning finished
+            Err(std::sync::mpsc::TrySendError::Disconnected(_)) => return self.close(),
+        };
+        counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+// Diagnostics printed to stderr, errors are always printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    // Periodic dumps of the detection and frame timing
+    Debug,
+    // Every captured input event and unexpected X event
+    Trace,
+}
+
+static VERBOSITY: std::sync::atomic::AtomicU8 =
+    std::sync::atomic::AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, std::sync::atomic::Ordering::Relaxed);
+}
+
+pub fn enabled(verbosity: Verbosity) -> bool {
+    VERBOSITY.load(std::sync::atomic::Ordering::Relaxed) >= verbosity as u8
+}
+
+// Destination of the periodic dumps, stderr unless a file is configured
+static DUMP_OUTPUT: std::sync::Mutex<Option<std::fs::File>> = std::sync::Mutex::new(None);
+
+pub fn set_dump_output(path: Option<&str>) {
+    let file = path.and_then(|path| {
+        match std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+        {
+            Ok(file) => Some(file),
+            Err(e) => {
+                eprintln!("Unable to open debug dump output {}: {}", path, e);
+                None
+            }
+        }
+    });
+    *DUMP_OUTPUT.lock().unwrap() = file;
+}
+
+// Written to a file the dumps are timestamped, stderr usually ends up in the journal
+pub fn dump(args: std::fmt::Arguments) {
+    let mut output = DUMP_OUTPUT.lock().unwrap();
+    match output.as_mut() {
+        Some(file) => {
+            let time = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f");
+            if let Err(e) = writeln!(file, "{} {}", time, args) {
+                eprintln!("Unable to write debug dump, writing to stderr: {}", e);
+                *output = None;
+            }
+        }
+        None => eprintln!("{}", args),
+    }
+}
+
+// Paces the periodic dumps of one thread, no interval turns them off
+pub struct DumpTimer {
+    interval: std::time::Duration,
+    last: std::time::Instant,
+}
+
+impl DumpTimer {
+    pub fn new(seconds: f64) -> DumpTimer {
+        DumpTimer {
+            interval: std::time::Duration::from_secs_f64(seconds.max(0.0)),
+            last: std::time::Instant::now(),
+        }
+    }
+
+    pub fn set_interval(&mut self, seconds: f64) {
+        self.interval = std::time::Duration::from_secs_f64(seconds.max(0.0));
+    }
+
+    pub fn due(&mut self) -> bool {
+        if !enabled(Verbosity::Debug) || self.interval.is_zero() {
+            return false;
+        }
+        if self.last.elapsed() < self.interval {
+            return false;
+        }
+        self.last = std::time::Instant::now();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<CaptureRecord> {
+        vec![
+            CaptureRecord::Velocity {
+                time: 0.5,
+                velocity: 1234.5,
+            },
+            CaptureRecord::Evdev {
+                time: 1.25,
+                event_type: 2,
+                code: 1,
+                value: -7,
+            },
+            CaptureRecord::PointerInput {
+                time: 2.0,
+                x: -20,
+                y: 1080,
+            },
+        ]
+    }
+
+    fn binary(records: &[CaptureRecord]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut writer = BinaryWriter::new(&mut bytes).unwrap();
+        for record in records {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn binary_round_trips_every_kind() {
+        let read = read_binary(binary(&records()).as_slice()).unwrap();
+        assert_eq!(read, records());
+    }
+
+    #[test]
+    fn truncated_tail_ends_the_capture() {
+        let bytes = binary(&records());
+        let last = binary(&records()[2..]).len() - BINARY_MAGIC.len();
+        // Cut inside the length prefix and inside the record of the last one
+        for cut in [last - 1, 1] {
+            let read = read_binary(&bytes[..bytes.len() - cut]).unwrap();
+            assert_eq!(read, records()[..2]);
         }
-        self.emitter.send(event).unwrap();
     }
 }
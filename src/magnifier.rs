@@ -0,0 +1,157 @@
+use crate::models::{Position32, ScreenLayout};
+use crate::shm::ShmImage;
+
+// Grabbing the screen while the overlay is mapped would magnify the lens
+// itself, so a snapshot around the cursor is taken right before a reveal
+// and the lens samples from it while the cursor moves.
+struct Snapshot {
+    origin: Position32,
+    width: i32,
+    height: i32,
+    data: Vec<u8>,
+}
+
+pub struct Magnifier {
+    zoom: f64,
+    size: u32,
+    snapshot: Option<Snapshot>,
+    use_shm: bool,
+    shm: Option<ShmImage>,
+}
+
+impl Magnifier {
+    pub fn new(zoom: f64, size: u32, use_shm: bool) -> Magnifier {
+        Magnifier {
+            zoom: zoom.max(1.0),
+            size,
+            snapshot: None,
+            use_shm,
+            shm: None,
+        }
+    }
+
+    pub fn capture(
+        &mut self,
+        conn: &xcb::Connection,
+        root: xcb::x::Window,
+        layout: &ScreenLayout,
+        center: Position32,
+    ) {
+        self.snapshot = None;
+
+        // Cover the area the cursor will likely shake around in
+        let extent = (self.size as i32) * 2;
+        let Position32 { x, y } =
+            layout.clamp(Position32::new(center.x - extent, center.y - extent));
+        let width = (extent * 2).min(layout.width - x);
+        let height = (extent * 2).min(layout.height - y);
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        let image = conn.wait_for_reply(conn.send_request(&xcb::x::GetImage {
+            format: xcb::x::ImageFormat::ZPixmap,
+            drawable: xcb::x::Drawable::Window(root),
+            x: x as _,
+            y: y as _,
+            width: width as _,
+            height: height as _,
+            plane_mask: u32::MAX,
+        }));
+
+        match image {
+            // Only 32 bits per pixel layouts can be copied to the overlay as-is
+            Ok(image) if image.data().len() == (width * height * 4) as usize => {
+                self.snapshot = Some(Snapshot {
+                    origin: Position32::new(x, y),
+                    width,
+                    height,
+                    data: image.data().to_vec(),
+                });
+            }
+            Ok(image) => eprintln!("Unsupported root depth for magnifier: {}", image.depth()),
+            Err(e) => eprintln!("Unable to capture screen for magnifier: {}", e),
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        conn: &std::sync::Arc<xcb::Connection>,
+        win: xcb::x::Window,
+        gfx_ctx: xcb::x::Gcontext,
+        pointer: Position32,
+        radius: u32,
+        depth: u8,
+    ) {
+        if self.snapshot.is_none() {
+            return;
+        }
+
+        let size = self.size as i32;
+        let len = (size * size * 4) as usize;
+        if self.use_shm && self.shm.is_none() {
+            self.shm = ShmImage::new(std::sync::Arc::clone(conn), len);
+            if self.shm.is_none() {
+                eprintln!("MIT-SHM not available, uploading the magnifier with PutImage");
+                self.use_shm = false;
+            }
+        }
+
+        if let Some(mut shm) = self.shm.take() {
+            let pixels = shm.data_mut();
+            pixels.fill(0);
+            self.render(pixels, pointer, radius);
+            shm.put(win, gfx_ctx, size as _, size as _, depth);
+            self.shm = Some(shm);
+            return;
+        }
+
+        let mut pixels = vec![0u8; len];
+        self.render(&mut pixels, pointer, radius);
+        conn.send_request(
+            &(xcb::x::PutImage {
+                format: xcb::x::ImageFormat::ZPixmap,
+                drawable: xcb::x::Drawable::Window(win),
+                gc: gfx_ctx,
+                width: size as _,
+                height: size as _,
+                dst_x: 0,
+                dst_y: 0,
+                left_pad: 0,
+                depth,
+                data: &pixels,
+            }),
+        );
+    }
+
+    fn render(&self, pixels: &mut [u8], pointer: Position32, radius: u32) {
+        let snapshot = match &self.snapshot {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+
+        let size = self.size as i32;
+        let half = size / 2;
+        let radius_sq = (radius * radius) as i32;
+
+        for py in 0..size {
+            for px in 0..size {
+                let (dx, dy) = (px - half, py - half);
+                if dx * dx + dy * dy > radius_sq {
+                    continue;
+                }
+
+                let sx = pointer.x - snapshot.origin.x + ((dx as f64) / self.zoom) as i32;
+                let sy = pointer.y - snapshot.origin.y + ((dy as f64) / self.zoom) as i32;
+                if sx < 0 || sy < 0 || sx >= snapshot.width || sy >= snapshot.height {
+                    continue;
+                }
+
+                let src = ((sy * snapshot.width + sx) * 4) as usize;
+                let dst = ((py * size + px) * 4) as usize;
+                pixels[dst..dst + 3].copy_from_slice(&snapshot.data[src..src + 3]);
+                pixels[dst + 3] = 0xff;
+            }
+        }
+    }
+}
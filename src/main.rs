@@ -2,22 +2,84 @@ use animations::Animation;
 use fn_chain_rs::prelude::*;
 use models::Config;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::RecvTimeoutError;
-use std::sync::RwLock;
 use std::thread;
 use std::time::Duration;
+use xcb::Xid;
 
+mod analyze;
 mod animations;
+mod appearance;
+mod cli;
+mod compass;
+mod config;
+mod cursor_hider;
+mod desktop;
+mod detection;
+mod edge_flash;
+mod fuzz;
+#[cfg(feature = "gui")]
+mod gui;
+mod haptics;
+#[cfg(feature = "http")]
+mod http;
+mod ipc;
+mod learning;
 mod logging;
+mod magnifier;
 mod models;
+mod motion;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod notify;
+mod overlay_probe;
+mod pointer_source;
+mod record;
+mod report;
+mod screencast;
+mod screenshots;
+mod seccomp;
+mod self_test;
+#[cfg(feature = "gnome-shell")]
+mod shell_bridge;
+mod shm;
+mod shutdown;
+mod state;
+mod status;
+mod themes;
+mod timeline;
+mod timing;
+mod touch;
+mod tune;
+mod typing;
+mod usage;
+mod wm;
 
 struct OverlayWindow {
-    conn: xcb::Connection,
+    conn: std::sync::Arc<xcb::Connection>,
     win: xcb::x::Window,
+    root: xcb::x::Window,
     gfx: xcb::x::Gcontext,
+    depth: u8,
     size: u32,
     visible: bool,
     position: models::Position32,
+    raise_on_show: bool,
+    // Keeps the visibility state but never maps the window
+    dry_run: bool,
+    layout: models::ScreenLayout,
+    wm: wm::Wm,
+    desktop_changes: wm::Subscription,
+    // Output of the focused window, checked again once it moved or focus changed
+    output_changes: wm::Subscription,
+    focus_output: Option<String>,
+    hotspot: u32,
+    dismissed: bool,
+    // Another client grabbed the pointer, told by the crossing events of the grab
+    pointer_grabbed: bool,
+    // A mouse button was held at the last pointer query
+    button_pressed: bool,
 }
 
 impl Debug for OverlayWindow {
@@ -26,37 +88,74 @@ impl Debug for OverlayWindow {
             .field("size", &self.size)
             .field("visible", &self.visible)
             .field("position", &self.position)
+            .field("monitors", &self.layout.monitors.len())
             .finish()
     }
 }
 
 impl OverlayWindow {
-    pub fn new(config: models::Config, conn: xcb::Connection, screen_num: usize) -> OverlayWindow {
-        let win = OverlayWindow::create_window(&conn, screen_num, config.window_size as _);
+    pub fn new(
+        config: models::Config,
+        conn: std::sync::Arc<xcb::Connection>,
+        screen_num: usize,
+    ) -> OverlayWindow {
+        OverlayWindow::with_size(conn, screen_num, config.window_size as _)
+    }
+
+    pub fn with_size(
+        conn: std::sync::Arc<xcb::Connection>,
+        screen_num: usize,
+        size: u32,
+    ) -> OverlayWindow {
+        let (win, depth) = OverlayWindow::create_window(&conn, screen_num, size);
         let gfx = conn.create_gcontext(win);
+        let root = conn.get_setup().roots().nth(screen_num).unwrap().root();
+        let layout = conn.get_layout(root);
+        let mut wm = wm::Wm::new(&conn, root, screen_num);
+        let desktop_changes = wm.subscribe();
+        let output_changes = wm.subscribe();
 
-        OverlayWindow {
+        let mut window = OverlayWindow {
             conn,
             win,
+            root,
             gfx,
-            size: config.window_size as _,
+            depth,
+            size,
             position: models::Position32::new(0, 0),
+            raise_on_show: false,
+            dry_run: false,
             visible: false,
-        }
+            layout,
+            wm,
+            desktop_changes,
+            output_changes,
+            focus_output: None,
+            hotspot: 0,
+            dismissed: false,
+            pointer_grabbed: false,
+            button_pressed: false,
+        };
+        window.focus_output = window.active_output();
+        window
     }
 
-    fn create_window(conn: &xcb::Connection, screen_num: usize, size: u32) -> xcb::x::Window {
-        let window_state = conn.get_atom(b"ATOM_WM_STATE");
-        let window_on_top = conn.get_atom(b"ATOM_WM_STATE_STAYS_ON_TOP");
+    fn create_window(conn: &xcb::Connection, screen_num: usize, size: u32) -> (xcb::x::Window, u8) {
+        // Only a hint for compositors, window managers ignore override-redirect
+        // windows, so the stacking order is maintained in handle_event.
 
         let screen = conn.get_setup().roots().nth(screen_num).unwrap();
-        let alpha = screen.alpha_visual().unwrap();
-        let colormap = conn.create_colormap(screen, &alpha);
+        let (depth, visual) = match screen.alpha_visual() {
+            Some(visual) => (32, visual),
+            None => (screen.root_depth(), screen.root_visual_type()),
+        };
+        themes::PixelFormat::from_visual(&visual, depth == 32).install();
+        let colormap = conn.create_colormap(screen, &visual);
 
         let win: xcb::x::Window = conn.generate_id();
         conn.send_request(
             &(xcb::x::CreateWindow {
-                depth: 32,
+                depth,
                 wid: win,
                 parent: screen.root(),
                 x: 0,
@@ -65,12 +164,14 @@ impl OverlayWindow {
                 height: size as u16,
                 border_width: 0,
                 class: xcb::x::WindowClass::InputOutput,
-                visual: alpha.visual_id(),
+                visual: visual.visual_id(),
                 value_list: &[
                     xcb::x::Cw::BackPixel(0x00),
                     xcb::x::Cw::BorderPixel(0x00),
                     xcb::x::Cw::OverrideRedirect(true),
-                    xcb::x::Cw::EventMask(xcb::x::EventMask::EXPOSURE),
+                    xcb::x::Cw::EventMask(
+                        xcb::x::EventMask::EXPOSURE | xcb::x::EventMask::BUTTON_PRESS,
+                    ),
                     xcb::x::Cw::Colormap(colormap),
                 ],
             }),
@@ -78,6 +179,28 @@ impl OverlayWindow {
 
         conn.send_request(&(xcb::x::FreeColormap { cmap: colormap }));
 
+        // Get notified when other windows are mapped or restacked above the overlay,
+        // when the active window changes and when grabs move the pointer to the root
+        conn.send_request(
+            &(xcb::x::ChangeWindowAttributes {
+                window: screen.root(),
+                value_list: &[xcb::x::Cw::EventMask(
+                    xcb::x::EventMask::SUBSTRUCTURE_NOTIFY
+                        | xcb::x::EventMask::PROPERTY_CHANGE
+                        | xcb::x::EventMask::ENTER_WINDOW
+                        | xcb::x::EventMask::LEAVE_WINDOW,
+                )],
+            }),
+        );
+
+        // Get notified when monitors are (un)plugged or the resolution changes
+        conn.send_request(
+            &(xcb::randr::SelectInput {
+                window: screen.root(),
+                enable: xcb::randr::NotifyMask::SCREEN_CHANGE,
+            }),
+        );
+
         conn.send_request(
             &(xcb::x::ChangeProperty {
                 mode: xcb::x::PropMode::Replace,
@@ -88,15 +211,20 @@ impl OverlayWindow {
             }),
         );
 
-        conn.send_request(
-            &(xcb::x::ChangeProperty {
-                mode: xcb::x::PropMode::Append,
-                window: win,
-                property: window_state,
-                r#type: xcb::x::ATOM_ATOM,
-                data: &[window_on_top],
-            }),
-        );
+        if let (Ok(window_state), Ok(window_on_top)) = (
+            conn.get_atom(b"_NET_WM_STATE"),
+            conn.get_atom(b"_NET_WM_STATE_ABOVE"),
+        ) {
+            conn.send_request(
+                &(xcb::x::ChangeProperty {
+                    mode: xcb::x::PropMode::Append,
+                    window: win,
+                    property: window_state,
+                    r#type: xcb::x::ATOM_ATOM,
+                    data: &[window_on_top],
+                }),
+            );
+        }
 
         // Prevent interaction from the mouse with the window,
         // OverrideRedirect did not work, so applying a clip mask instead does the trick.
@@ -117,7 +245,58 @@ impl OverlayWindow {
             }),
         );
 
-        win
+        if depth != 32 {
+            OverlayWindow::shape_disc(conn, win, size);
+        }
+
+        // Unredirecting would drop the overlay drawn above fullscreen windows
+        if let Ok(bypass_compositor) = conn.get_atom(b"_NET_WM_BYPASS_COMPOSITOR") {
+            conn.send_request(
+                &(xcb::x::ChangeProperty {
+                    mode: xcb::x::PropMode::Replace,
+                    window: win,
+                    property: bypass_compositor,
+                    r#type: xcb::x::ATOM_CARDINAL,
+                    data: &[2u32],
+                }),
+            );
+        }
+        // The alpha channel already blends the ring, opacity rules of picom and the like
+        // would fade it twice. Opaque windows get their opacity from set_opacity.
+        if depth == 32 {
+            OverlayWindow::set_opacity_hint(conn, win, 1.0);
+        }
+
+        (win, depth)
+    }
+
+    // Without an alpha channel the window is opaque, cut it down to a disc instead of a square
+    fn shape_disc(conn: &xcb::Connection, win: xcb::x::Window, size: u32) {
+        let radius = size as f64 / 2.0;
+        let rectangles = (0..size)
+            .map(|y| {
+                let dy = y as f64 + 0.5 - radius;
+                let half = (radius * radius - dy * dy).max(0.0).sqrt();
+                xcb::x::Rectangle {
+                    x: (radius - half).round() as i16,
+                    y: y as i16,
+                    width: (half * 2.0).round() as u16,
+                    height: 1,
+                }
+            })
+            .collect::<Vec<xcb::x::Rectangle>>();
+
+        conn.send_request(
+            &(xcb::shape::Rectangles {
+                operation: xcb::shape::So::Set,
+                destination_kind: xcb::shape::Sk::Bounding,
+                destination_window: win,
+                x_offset: 0,
+                y_offset: 0,
+                ordering: xcb::x::ClipOrdering::YSorted,
+                rectangles: &rectangles,
+            }),
+        );
     }
 
     pub fn set_center_position(&mut self, pos: models::Position32) {
@@ -148,9 +327,16 @@ impl OverlayWindow {
     }
 
     fn show(&mut self) {
+        let was_visible = self.visible;
         self.visible = true;
+        if self.dry_run {
+            return;
+        }
         self.conn
             .send_request(&(xcb::x::MapWindow { window: self.win }));
+        if self.raise_on_show && !was_visible {
+            self.raise();
+        }
     }
 
     fn hide(&mut self) {
@@ -159,43 +345,296 @@ impl OverlayWindow {
             .send_request(&(xcb::x::UnmapWindow { window: self.win }));
     }
 
+    // A failed query skips the frame rather than moving the overlay to a stale position
+    fn pointer(&mut self) -> Option<models::Position32> {
+        let (position, pressed) = self
+            .conn
+            .get_pointer(self.win)
+            .inspect_err(|e| eprintln!("Unable to query the pointer: {}", e))
+            .ok()?;
+        self.button_pressed = pressed;
+        Some(position)
+    }
+
     fn get_gfx(&self) -> xcb::x::Gcontext {
         self.gfx
     }
 
-    fn handle_event(&self) {
-        match self.conn.poll_for_queued_event() {
-            Ok(Some(xcb::Event::X(xcb::x::Event::Expose(_)))) => {}
-            Ok(Some(x)) => println!("event: {:?}", x),
-            Err(e) => println!("error: {}", e),
-            Ok(None) => {
-                // No event
+    fn get_depth(&self) -> u8 {
+        self.depth
+    }
+
+    // Returns whether the screen layout changed
+    fn handle_event(&mut self) -> bool {
+        let mut restack = false;
+        let mut layout_changed = false;
+        loop {
+            match self.conn.poll_for_event() {
+                Ok(Some(xcb::Event::X(xcb::x::Event::Expose(_)))) => {}
+                Ok(Some(xcb::Event::X(xcb::x::Event::ButtonPress(e)))) => {
+                    self.dismissed |= e.event() == self.win;
+                }
+                Ok(Some(xcb::Event::RandR(xcb::randr::Event::ScreenChangeNotify(_)))) => {
+                    layout_changed = true;
+                }
+                Ok(Some(xcb::Event::X(xcb::x::Event::PropertyNotify(e)))) => {
+                    self.wm.property_changed(&self.conn, &e);
+                }
+                Ok(Some(xcb::Event::XFixes(xcb::xfixes::Event::SelectionNotify(e)))) => {
+                    self.wm.selection_changed(&e);
+                }
+                Ok(Some(xcb::Event::X(xcb::x::Event::EnterNotify(e)))) => {
+                    self.pointer_crossed(e.mode());
+                }
+                Ok(Some(xcb::Event::X(xcb::x::Event::LeaveNotify(e)))) => {
+                    self.pointer_crossed(e.mode());
+                }
+                // New windows are mapped on top of the stack
+                Ok(Some(xcb::Event::X(xcb::x::Event::MapNotify(e)))) => {
+                    restack |= e.window() != self.win;
+                }
+                // Another window was restacked directly above the overlay
+                // Override-redirect windows are popups and our own overlays, compass and
+                // edge flashes, never the focused window
+                Ok(Some(xcb::Event::X(xcb::x::Event::ConfigureNotify(e)))) => {
+                    restack |= e.window() != self.win && e.above_sibling() == self.win;
+                    if !e.override_redirect() {
+                        self.wm.window_moved();
+                    }
+                }
+                Ok(Some(xcb::Event::X(
+                    xcb::x::Event::UnmapNotify(_)
+                    | xcb::x::Event::DestroyNotify(_)
+                    | xcb::x::Event::CreateNotify(_)
+                    | xcb::x::Event::ReparentNotify(_)
+                    | xcb::x::Event::GravityNotify(_)
+                    | xcb::x::Event::CirculateNotify(_),
+                ))) => {}
+                Ok(Some(x)) => {
+                    if logging::enabled(logging::Verbosity::Trace) {
+                        eprintln!("event: {:?}", x);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    break;
+                }
+                Ok(None) => break,
+            }
+        }
+
+        if restack && self.visible {
+            self.raise();
+        }
+
+        if layout_changed {
+            let layout = self.conn.get_layout(self.root);
+            layout_changed = layout != self.layout;
+            if layout_changed {
+                eprintln!("Screen layout changed: {:?}", layout);
+                self.layout = layout;
             }
         }
+
+        layout_changed
+    }
+
+    // Grabs send the pointer as if it moved to the grab window and back, X has no query
+    // for them. Grabs within the window the pointer is in don't cross and go unnoticed.
+    fn pointer_crossed(&mut self, mode: xcb::x::NotifyMode) {
+        match mode {
+            xcb::x::NotifyMode::Grab => self.pointer_grabbed = true,
+            xcb::x::NotifyMode::Ungrab => self.pointer_grabbed = false,
+            _ => {}
+        }
+    }
+
+    fn resize(&mut self, size: u32) {
+        if self.size == size {
+            return;
+        }
+
+        self.conn.send_request(
+            &(xcb::x::ConfigureWindow {
+                window: self.win,
+                value_list: &[
+                    xcb::x::ConfigWindow::Width(size),
+                    xcb::x::ConfigWindow::Height(size),
+                ],
+            }),
+        );
+
+        // Keep the same center point
+        let center = models::Position32::new(
+            self.position.x + (self.size as i32) / 2,
+            self.position.y + (self.size as i32) / 2,
+        );
+        self.size = size;
+        self.set_center_position(center);
+        if self.depth != 32 || self.hotspot > 0 {
+            self.set_hotspot(self.hotspot);
+        }
+    }
+
+    fn raise(&self) {
+        self.conn.send_request(
+            &(xcb::x::ConfigureWindow {
+                window: self.win,
+                value_list: &[xcb::x::ConfigWindow::StackMode(xcb::x::StackMode::Above)],
+            }),
+        );
     }
 
     fn get_conn(&self) -> &xcb::Connection {
         &self.conn
     }
+
+    fn get_root(&self) -> xcb::x::Window {
+        self.root
+    }
+
+    fn get_active_class(&self) -> Option<&models::WindowClass> {
+        self.wm.active_class()
+    }
+
+    // Returns whether the workspace was switched since the last call
+    fn take_desktop_switch(&mut self) -> bool {
+        self.wm.take(&self.desktop_changes).desktop
+    }
+
+    fn active_output(&self) -> Option<String> {
+        self.wm
+            .active_window()
+            .and_then(|active| self.conn.get_window_center(active, self.root))
+            .and_then(|center| self.layout.monitor_at(center))
+            .map(|monitor| monitor.name.clone())
+    }
+
+    // Returns whether the focused window is on another output than at the last call,
+    // moves while dragging are only looked at once per frame
+    fn take_output_switch(&mut self) -> bool {
+        let changes = self.wm.take(&self.output_changes);
+        if !changes.active_window && !changes.geometry {
+            return false;
+        }
+        // Unknown outputs, e.g. without a focused window, don't count as a switch
+        let Some(output) = self.active_output() else {
+            return false;
+        };
+        let switched = self
+            .focus_output
+            .as_ref()
+            .is_some_and(|last| *last != output);
+        self.focus_output = Some(output);
+        switched
+    }
+
+    // Returns whether the dismiss hotspot was clicked since the last call
+    fn take_dismissed(&mut self) -> bool {
+        std::mem::take(&mut self.dismissed)
+    }
+
+    // Only opaque windows are blended as a whole, see create_window
+    fn set_opacity(&self, opacity: f64) {
+        if self.depth != 32 {
+            OverlayWindow::set_opacity_hint(&self.conn, self.win, opacity);
+        }
+    }
+
+    fn set_opacity_hint(conn: &xcb::Connection, win: xcb::x::Window, opacity: f64) {
+        if let Ok(window_opacity) = conn.get_atom(b"_NET_WM_WINDOW_OPACITY") {
+            conn.send_request(
+                &(xcb::x::ChangeProperty {
+                    mode: xcb::x::PropMode::Replace,
+                    window: win,
+                    property: window_opacity,
+                    r#type: xcb::x::ATOM_CARDINAL,
+                    data: &[(opacity.clamp(0.0, 1.0) * u32::MAX as f64) as u32],
+                }),
+            );
+        }
+    }
+
+    // Makes a square in the top right corner clickable, the rest stays click-through
+    fn set_hotspot(&mut self, size: u32) {
+        self.hotspot = size.min(self.size);
+        let rectangle = xcb::x::Rectangle {
+            x: (self.size - self.hotspot) as i16,
+            y: 0,
+            width: self.hotspot as u16,
+            height: self.hotspot as u16,
+        };
+        // The corner is outside of the disc an opaque window is cut down to
+        if self.depth != 32 {
+            OverlayWindow::shape_disc(&self.conn, self.win, self.size);
+            if self.hotspot > 0 {
+                self.conn.send_request(
+                    &(xcb::shape::Rectangles {
+                        operation: xcb::shape::So::Union,
+                        destination_kind: xcb::shape::Sk::Bounding,
+                        destination_window: self.win,
+                        x_offset: 0,
+                        y_offset: 0,
+                        ordering: xcb::x::ClipOrdering::Unsorted,
+                        rectangles: &[rectangle],
+                    }),
+                );
+            }
+        }
+        self.conn.send_request(
+            &(xcb::shape::Rectangles {
+                operation: xcb::shape::So::Set,
+                destination_kind: xcb::shape::Sk::Input,
+                destination_window: self.win,
+                x_offset: 0,
+                y_offset: 0,
+                ordering: xcb::x::ClipOrdering::Unsorted,
+                rectangles: &[rectangle],
+            }),
+        );
+    }
 }
 
 trait ScreenUtil {
     fn alpha_visual(&self) -> Option<xcb::x::Visualtype>;
+    fn root_visual_type(&self) -> xcb::x::Visualtype;
 }
 
 impl ScreenUtil for xcb::x::Screen {
     fn alpha_visual(&self) -> std::option::Option<xcb::x::Visualtype> {
-        let depths = self.allowed_depths();
-        let mut alpha_depths = depths.filter(|d| d.depth() == 32u8).peekable();
-        if alpha_depths.peek().is_none() {
-            panic!("Alpha channel not found!");
+        // fetch a visual supporting alpha channels
+        let visual = self
+            .allowed_depths()
+            .find(|d| d.depth() == 32u8)
+            .and_then(|d| d.visuals().get(1_usize).copied());
+        if visual.is_none() {
+            eprintln!(
+                "The X screen offers no 32-bit visual, falling back to an opaque {}-bit window",
+                self.root_depth()
+            );
         }
+        visual
+    }
 
-        // fetch a visual supporting alpha channels
-        alpha_depths.next().unwrap().visuals().get(1_usize).copied()
+    fn root_visual_type(&self) -> xcb::x::Visualtype {
+        match self
+            .allowed_depths()
+            .flat_map(|d| d.visuals().iter())
+            .find(|v| v.visual_id() == self.root_visual())
+        {
+            Some(visual) => *visual,
+            None => notify::fatal(
+                "Visual not found",
+                "The X screen does not describe its root visual, the overlay cannot be drawn.",
+            ),
+        }
     }
 }
 
+// Atoms are server wide and never change, so every name is interned once per process
+static ATOMS: std::sync::Mutex<std::collections::BTreeMap<Vec<u8>, xcb::x::Atom>> =
+    std::sync::Mutex::new(std::collections::BTreeMap::new());
+
 trait ConnExt {
     fn create_colormap(
         &self,
@@ -203,8 +642,30 @@ trait ConnExt {
         visual: &xcb::x::Visualtype,
     ) -> xcb::x::Colormap;
     fn create_gcontext(&self, win: xcb::x::Window) -> xcb::x::Gcontext;
-    fn get_pointer(&self, win: xcb::x::Window) -> models::Position32;
-    fn get_atom(&self, name: &[u8]) -> xcb::x::Atom;
+    fn get_pointer(&self, win: xcb::x::Window) -> xcb::Result<(models::Position32, bool)>;
+    fn get_atom(&self, name: &[u8]) -> xcb::Result<xcb::x::Atom>;
+    fn get_atom_name(&self, atom: xcb::x::Atom) -> xcb::Result<String>;
+    fn get_monitors(&self, root: xcb::x::Window) -> Vec<models::Monitor>;
+    fn get_layout(&self, root: xcb::x::Window) -> models::ScreenLayout;
+    fn get_rotations(
+        &self,
+        root: xcb::x::Window,
+    ) -> std::collections::BTreeMap<String, models::Rotation>;
+    fn get_rotation(&self, output: xcb::randr::Output) -> models::Rotation;
+    fn get_refresh_rate(&self, root: xcb::x::Window) -> Option<f64>;
+    fn get_active_window(
+        &self,
+        root: xcb::x::Window,
+        net_active_window: xcb::x::Atom,
+    ) -> Option<xcb::x::Window>;
+    fn get_window_class(&self, win: xcb::x::Window) -> Option<models::WindowClass>;
+    fn get_window_center(
+        &self,
+        win: xcb::x::Window,
+        root: xcb::x::Window,
+    ) -> Option<models::Position32>;
+    fn get_focus_position(&self, root: xcb::x::Window) -> Option<models::Position32>;
+    fn display_off(&self) -> bool;
 }
 
 impl ConnExt for xcb::Connection {
@@ -238,293 +699,1918 @@ impl ConnExt for xcb::Connection {
         gfx_ctx
     }
 
-    fn get_pointer(&self, win: xcb::x::Window) -> models::Position32 {
+    // Along with whether one of the main mouse buttons is held
+    fn get_pointer(&self, win: xcb::x::Window) -> xcb::Result<(models::Position32, bool)> {
         self.chain_with(&(xcb::x::QueryPointer { window: win }))
             .then(Self::send_request)
-            .then(Self::wait_for_reply)
-            .then_value(Result::unwrap)
-            .map_value(|r| models::Position32::new(r.root_x() as i32, r.root_y() as i32))
+            .map(Self::wait_for_reply)
+            .map(|r| {
+                let pressed = r.mask().intersects(
+                    xcb::x::KeyButMask::BUTTON1
+                        | xcb::x::KeyButMask::BUTTON2
+                        | xcb::x::KeyButMask::BUTTON3,
+                );
+                (
+                    models::Position32::new(r.root_x() as i32, r.root_y() as i32),
+                    pressed,
+                )
+            })
     }
 
-    fn get_atom(&self, name: &[u8]) -> xcb::x::Atom {
+    fn get_atom(&self, name: &[u8]) -> xcb::Result<xcb::x::Atom> {
+        if let Some(atom) = ATOMS.lock().unwrap().get(name) {
+            return Ok(*atom);
+        }
+
         let atom = xcb::x::InternAtom {
             only_if_exists: false,
             name,
         };
-
-        self.chain_with(&atom)
+        let atom = self
+            .chain_with(&atom)
             .then(Self::send_request)
-            .map(Self::wait_for_reply)
-            .unwrap()
-            .atom()
+            .map(Self::wait_for_reply)?
+            .atom();
+        ATOMS.lock().unwrap().insert(name.to_vec(), atom);
+        Ok(atom)
     }
-}
-
-fn main() -> ! {
-    let (tx, rx) = std::sync::mpsc::channel();
 
-    let config = models::Config::new();
-    let last_velocity_event = std::sync::Arc::new(RwLock::new(models::VelocityEvent::new(0.0)));
-
-    start_capture_thread(config.clone(), rx);
-
-    start_motion_thread(
-        config.clone(),
-        logging::CaptureEmitter::new(
-            std::time::Instant::now(),
-            std::time::Duration::from_secs_f64(config.capture_seconds),
-            tx.clone(),
-        ),
-        std::sync::Arc::clone(&last_velocity_event),
-    );
+    fn get_atom_name(&self, atom: xcb::x::Atom) -> xcb::Result<String> {
+        self.wait_for_reply(self.send_request(&xcb::x::GetAtomName { atom }))
+            .map(|reply| reply.name().to_utf8().into_owned())
+    }
 
-    start_ui_loop(
-        config.clone(),
-        logging::CaptureEmitter::new(
-            std::time::Instant::now(),
-            std::time::Duration::from_secs_f64(config.capture_seconds),
-            tx,
-        ),
-        last_velocity_event,
-    );
-}
+    fn get_monitors(&self, root: xcb::x::Window) -> Vec<models::Monitor> {
+        let request = xcb::randr::GetMonitors {
+            window: root,
+            get_active: true,
+        };
 
-fn start_capture_thread(config: Config, receiver: std::sync::mpsc::Receiver<logging::LogEvent>) {
-    std::thread::spawn(move || {
-        let start = std::time::Instant::now();
-        let mut capture = logging::Capture::new();
-        loop {
-            if start.elapsed() > Duration::from_secs(config.capture_seconds as _) {
-                println!("Capture finished!");
-                break;
+        match self.wait_for_reply(self.send_request(&request)) {
+            Ok(reply) => reply
+                .monitors()
+                .map(|m| models::Monitor {
+                    name: self.get_atom_name(m.name()).unwrap_or_default(),
+                    x: m.x() as _,
+                    y: m.y() as _,
+                    width: m.width() as _,
+                    height: m.height() as _,
+                    width_mm: m.width_in_millimeters(),
+                    height_mm: m.height_in_millimeters(),
+                    primary: m.primary(),
+                })
+                .collect(),
+            Err(e) => {
+                eprintln!("Unable to query monitors: {}", e);
+                Vec::new()
             }
+        }
+    }
 
-            match receiver.recv_timeout(Duration::from_millis(100)) {
-                Ok(event) => {
-                    capture.push(event);
-                }
-                Err(RecvTimeoutError::Timeout) => {}
-                Err(RecvTimeoutError::Disconnected) => {
-                    println!("Receiver disconnected");
-                    break;
-                }
-            }
+    // Two round trips per monitor, only queried for the startup report
+    fn get_rotations(
+        &self,
+        root: xcb::x::Window,
+    ) -> std::collections::BTreeMap<String, models::Rotation> {
+        let request = xcb::randr::GetMonitors {
+            window: root,
+            get_active: true,
+        };
+        match self.wait_for_reply(self.send_request(&request)) {
+            Ok(reply) => reply
+                .monitors()
+                .filter_map(|m| {
+                    let output = m.outputs().first()?;
+                    Some((
+                        self.get_atom_name(m.name()).unwrap_or_default(),
+                        self.get_rotation(*output),
+                    ))
+                })
+                .collect(),
+            Err(_) => std::collections::BTreeMap::new(),
         }
+    }
 
-        capture.events().iter().for_each(|x| match x {
-            logging::LogEvent::PointerInput { x, y, time } => {
-                println!(
-                    "{:1.6}s PointerInputEvent, x: {}, y: {}",
-                    (*time - start).as_secs_f64(),
-                    x,
-                    y
-                );
-            }
-            logging::LogEvent::Velocity { velocity, time } => {
-                println!(
-                    "{:1.6}s VelocityEvent, velocity: {:1.1}",
-                    (*time - start).as_secs_f64(),
-                    velocity
-                );
-            }
-            logging::LogEvent::Evdev { time, evdev_event } => {
-                println!(
-                    "{:1.6}s EvdevEvent, evdev_event: {:?}, value: {}",
-                    (*time - start).as_secs_f64(),
-                    evdev_event.kind(),
-                    evdev_event.value()
-                );
-            }
+    fn get_rotation(&self, output: xcb::randr::Output) -> models::Rotation {
+        let crtc = self
+            .wait_for_reply(self.send_request(&xcb::randr::GetOutputInfo {
+                output,
+                config_timestamp: xcb::x::CURRENT_TIME,
+            }))
+            .map(|info| info.crtc());
+
+        let rotation = crtc.and_then(|crtc| {
+            self.wait_for_reply(self.send_request(&xcb::randr::GetCrtcInfo {
+                crtc,
+                config_timestamp: xcb::x::CURRENT_TIME,
+            }))
+            .map(|info| info.rotation())
         });
-    });
-}
-
-fn start_ui_loop(
-    config: models::Config,
-    _capture: logging::CaptureEmitter,
-    last_velocity_event: std::sync::Arc<RwLock<models::VelocityEvent>>,
-) -> ! {
-    let (conn, screen_num) = xcb::Connection::connect(None).unwrap();
-    let mut win = OverlayWindow::new(config.clone(), conn, screen_num as _);
-    let animation = Animation::new(win.size);
-
-    let mut avg_weighted = 0.0;
-    let mut avg_ui = 0.0;
 
-    let mut last_render = std::time::Instant::now();
-    let mut last_debug = std::time::Instant::now();
-
-    let fps_hidden = Duration::from_millis(1000 / 20);
-    let fps_visible = Duration::from_millis(1000 / 120);
-    let fps_animation = Duration::from_millis(1000 / 30);
-
-    loop {
-        win.handle_event();
+        match rotation {
+            Ok(r) if r.contains(xcb::randr::Rotation::ROTATE_90) => models::Rotation::Left,
+            Ok(r) if r.contains(xcb::randr::Rotation::ROTATE_180) => models::Rotation::Inverted,
+            Ok(r) if r.contains(xcb::randr::Rotation::ROTATE_270) => models::Rotation::Right,
+            Ok(_) => models::Rotation::Normal,
+            Err(e) => {
+                eprintln!("Unable to query output rotation: {}", e);
+                models::Rotation::Normal
+            }
+        }
+    }
 
-        let velocity_event = *last_velocity_event.read().unwrap();
-        let velocity = if velocity_event.expired() {
-            0.0
+    fn get_refresh_rate(&self, root: xcb::x::Window) -> Option<f64> {
+        let resources = self
+            .wait_for_reply(
+                self.send_request(&xcb::randr::GetScreenResourcesCurrent { window: root }),
+            )
+            .ok()?;
+        let primary = self
+            .wait_for_reply(self.send_request(&xcb::randr::GetOutputPrimary { window: root }))
+            .ok()?
+            .output();
+
+        // Without a primary output the first active CRTC paces the animation
+        let crtc = if primary.is_none() {
+            *resources.crtcs().first()?
         } else {
-            velocity_event.velocity()
+            self.wait_for_reply(self.send_request(&xcb::randr::GetOutputInfo {
+                output: primary,
+                config_timestamp: resources.config_timestamp(),
+            }))
+            .ok()?
+            .crtc()
         };
+        let mode = self
+            .wait_for_reply(self.send_request(&xcb::randr::GetCrtcInfo {
+                crtc,
+                config_timestamp: resources.config_timestamp(),
+            }))
+            .ok()?
+            .mode();
+
+        let info = resources
+            .modes()
+            .iter()
+            .find(|info| info.id == mode.resource_id())?;
+        let mut lines = info.vtotal as f64;
+        if info.mode_flags.contains(xcb::randr::ModeFlag::DOUBLE_SCAN) {
+            lines *= 2.0;
+        }
+        if info.mode_flags.contains(xcb::randr::ModeFlag::INTERLACE) {
+            lines /= 2.0;
+        }
+        let rate = info.dot_clock as f64 / (info.htotal as f64 * lines);
+        rate.is_finite().then_some(rate).filter(|rate| *rate > 0.0)
+    }
 
-        avg_weighted = update_avg(config.clone(), avg_weighted, velocity);
-
-        if avg_ui > 50.0 || avg_weighted > config.threshold {
-            avg_ui = avg_ui * 0.95 + avg_weighted * 0.05;
+    fn get_active_window(
+        &self,
+        root: xcb::x::Window,
+        net_active_window: xcb::x::Atom,
+    ) -> Option<xcb::x::Window> {
+        let active = self
+            .wait_for_reply(self.send_request(&xcb::x::GetProperty {
+                delete: false,
+                window: root,
+                property: net_active_window,
+                r#type: xcb::x::ATOM_WINDOW,
+                long_offset: 0,
+                long_length: 1,
+            }))
+            .ok()?
+            .value::<xcb::x::Window>()
+            .first()
+            .copied()?;
+        (!active.is_none()).then_some(active)
+    }
 
-            if last_render.elapsed() > fps_animation {
-                last_render = std::time::Instant::now();
-                animation.play(win.get_conn(), win.get_win(), win.get_gfx(), avg_ui);
-            }
+    fn get_window_class(&self, win: xcb::x::Window) -> Option<models::WindowClass> {
+        let class = self
+            .wait_for_reply(self.send_request(&xcb::x::GetProperty {
+                delete: false,
+                window: win,
+                property: xcb::x::ATOM_WM_CLASS,
+                r#type: xcb::x::ATOM_STRING,
+                long_offset: 0,
+                long_length: 256,
+            }))
+            .ok()?;
+
+        // Two null terminated strings: instance and class
+        let mut parts = class
+            .value::<u8>()
+            .split(|b| *b == 0)
+            .map(|part| String::from_utf8_lossy(part).into_owned());
+        Some(models::WindowClass {
+            instance: parts.next().unwrap_or_default(),
+            class: parts.next().unwrap_or_default(),
+        })
+    }
 
-            win.show();
-            win.set_center_position(win.get_conn().get_pointer(win.get_win()));
-            win.conn.flush().unwrap();
+    // Client windows are positioned relative to the frame the window manager put them in
+    fn get_window_center(
+        &self,
+        win: xcb::x::Window,
+        root: xcb::x::Window,
+    ) -> Option<models::Position32> {
+        let geometry = self
+            .wait_for_reply(self.send_request(&xcb::x::GetGeometry {
+                drawable: xcb::x::Drawable::Window(win),
+            }))
+            .ok()?;
+        let reply = self
+            .wait_for_reply(self.send_request(&xcb::x::TranslateCoordinates {
+                src_window: win,
+                dst_window: root,
+                src_x: (geometry.width() / 2) as _,
+                src_y: (geometry.height() / 2) as _,
+            }))
+            .ok()?;
+        Some(models::Position32::new(
+            reply.dst_x() as _,
+            reply.dst_y() as _,
+        ))
+    }
 
-            thread::sleep(fps_visible);
-        } else {
-            avg_ui = 0.0;
+    fn get_layout(&self, root: xcb::x::Window) -> models::ScreenLayout {
+        let geometry = self.wait_for_reply(self.send_request(&xcb::x::GetGeometry {
+            drawable: xcb::x::Drawable::Window(root),
+        }));
 
-            if win.visible {
-                win.hide();
-                win.conn.flush().unwrap();
+        let (width, height) = match geometry {
+            Ok(geometry) => (geometry.width() as i32, geometry.height() as i32),
+            Err(e) => {
+                eprintln!("Unable to query root geometry: {}", e);
+                (0, 0)
             }
+        };
 
-            thread::sleep(fps_hidden);
+        models::ScreenLayout {
+            width,
+            height,
+            monitors: self.get_monitors(root),
         }
+    }
 
-        if last_debug.elapsed() > Duration::from_secs(1) {
-            last_debug = std::time::Instant::now();
-            println!("{:?}", win);
+    fn display_off(&self) -> bool {
+        // Servers without DPMS never power the display off
+        match self.wait_for_reply(self.send_request(&xcb::dpms::Info {})) {
+            Ok(info) => info.state() && info.power_level() != xcb::dpms::DpmsMode::On,
+            Err(_) => false,
         }
     }
-}
 
-fn update_avg(config: models::Config, avg: f64, velocity: f64) -> f64 {
-    let weight_input = (velocity / config.accel)
-        .max(config.accel_decay)
-        .min(config.accel_inc);
-    let weight_state = 1.0 - weight_input;
+    fn get_focus_position(&self, root: xcb::x::Window) -> Option<models::Position32> {
+        let focus = self
+            .wait_for_reply(self.send_request(&xcb::x::GetInputFocus {}))
+            .ok()?
+            .focus();
+
+        // None and PointerRoot are not real windows
+        if focus.resource_id() <= 1 {
+            return None;
+        }
 
-    avg * weight_state * config.decay + velocity * weight_input
+        let reply = self
+            .wait_for_reply(self.send_request(&xcb::x::TranslateCoordinates {
+                src_window: focus,
+                dst_window: root,
+                src_x: 0,
+                src_y: 0,
+            }))
+            .ok()?;
+
+        Some(models::Position32::new(
+            reply.dst_x() as _,
+            reply.dst_y() as _,
+        ))
+    }
 }
 
-fn start_motion_thread(
-    config: models::Config,
-    capture: logging::CaptureEmitter,
-    last_speed: std::sync::Arc<RwLock<models::VelocityEvent>>,
-) {
-    std::thread::spawn(move || loop {
-        thread::sleep(Duration::from_secs(1));
+fn main() {
+    let args = cli::Args::load();
+    if let Some(command) = args.command {
+        run_command(command);
+    }
 
-        MotionMonitor::new(
-            config.device_name.clone(),
-            capture.clone(),
-            std::sync::Arc::clone(&last_speed),
-        )
-        .start_until_error();
+    let (status_tx, status_rx) = std::sync::mpsc::channel();
+    let (learned_tx, learned_rx) = std::sync::mpsc::channel();
+    let (ipc_tx, ipc_rx) = std::sync::mpsc::channel();
+    let (events_tx, events_rx) = std::sync::mpsc::channel();
+
+    let overrides = args.overrides();
+    let mut config = config::load();
+    logging::set_verbosity(match (args.quiet, args.verbose) {
+        (true, _) => logging::Verbosity::Quiet,
+        (false, 0) if config.debug_dumps => logging::Verbosity::Debug,
+        (false, 0) => logging::Verbosity::Normal,
+        (false, 1) => logging::Verbosity::Debug,
+        (false, _) => logging::Verbosity::Trace,
     });
-}
+    overrides.apply(&mut config);
+    logging::set_dump_output(config.debug_dump_output.as_deref());
+    let shutdown = shutdown::Shutdown::install();
+    // Resumes where the last run left off, the UI loop saves the state as it changes
+    let state = state::load();
+    if let Some(state) = state.as_ref() {
+        config.active_profile = state.active_profile.clone();
+        // Edits of the config file meanwhile replace the threshold, like a reload does
+        let edited = state.config_modified != state::stamp(config::modified());
+        if let Some(threshold) = state.learned_threshold.filter(|_| !edited) {
+            let _ = learned_tx.send(threshold);
+        }
+    }
+    let history = std::sync::Arc::new(models::VelocityHistory::new());
+    let paused = std::sync::Arc::new(AtomicBool::new(state.is_some_and(|state| state.paused)));
+    // Without a capture or learning nothing listens for the input events
+    let (capture, capture_thread) = if config.capturing()
+        || config.learn_threshold != models::LearnMode::Off
+    {
+        let (tx, rx) = std::sync::mpsc::sync_channel(logging::CAPTURE_QUEUE);
+        let capture =
+            logging::CaptureEmitter::new(std::time::Instant::now(), capture_expiry(&config), tx);
+        let thread = start_capture_thread(
+            config.clone(),
+            rx,
+            capture.stats(),
+            learned_tx,
+            shutdown.clone(),
+        );
+        (capture, Some(thread))
+    } else {
+        (logging::CaptureEmitter::disabled(), None)
+    };
+    start_pause_thread(std::sync::Arc::clone(&paused));
+    let haptics = haptics::Haptics::default();
+    start_event_threads(&mut config, events_rx, ipc_tx.clone(), haptics.clone());
+    start_http_thread(&config, ipc_tx.clone());
+    let ipc_socket = ipc::start_ipc_thread(ipc_tx);
+
+    if let Some(output) = config.status_output.as_deref() {
+        status::start_status_thread(status::StatusOutput::parse(output), status_rx);
+    }
 
-struct MotionMonitor {
-    device_name: String,
-    capture: logging::CaptureEmitter,
-    last_speed: std::sync::Arc<RwLock<models::VelocityEvent>>,
-    last: models::PointerInputEvent,
-    working: models::PointerInputEvent,
-    ignore_block: bool,
-}
+    let motion_thread = start_motion_thread(
+        config.clone(),
+        capture.clone(),
+        std::sync::Arc::clone(&history),
+        haptics,
+        shutdown.clone(),
+    );
 
-impl Debug for MotionMonitor {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("MotionMonitor")
-            .field("device_name", &self.device_name)
-            .field("last_speed", &self.last_speed)
-            .field("last", &self.last)
-            .field("working", &self.working)
-            .field("ignore_block", &self.ignore_block)
-            .finish()
+    start_ui_loop(
+        config.clone(),
+        capture,
+        history,
+        paused,
+        status_tx,
+        events_tx,
+        learned_rx,
+        ipc_rx,
+        overrides,
+        shutdown,
+    );
+
+    // The capture takes the last events of the motion thread before it finishes
+    eprintln!("Shutting down");
+    let threads = std::iter::once(("motion", motion_thread))
+        .chain(capture_thread.map(|thread| ("capture", thread)));
+    for (name, thread) in threads {
+        if thread.join().is_err() {
+            eprintln!("The {} thread panicked", name);
+        }
+    }
+    if let Some(path) = ipc_socket {
+        let _ = std::fs::remove_file(path);
     }
 }
 
-impl MotionMonitor {
-    pub fn new(
-        device_name: String,
-        capture: logging::CaptureEmitter,
-        last_speed: std::sync::Arc<RwLock<models::VelocityEvent>>,
-    ) -> MotionMonitor {
-        MotionMonitor {
-            device_name,
+// Refresh rate the daemon would pace to on the current display, if there is one
+fn display_refresh_rate() -> Option<f64> {
+    let (conn, screen_num) =
+        xcb::Connection::connect_with_extensions(None, &[], &[xcb::Extension::RandR]).ok()?;
+    let root = conn.get_setup().roots().nth(screen_num as _)?.root();
+    conn.get_refresh_rate(root)
+}
+
+fn run_command(command: cli::Command) -> ! {
+    match command {
+        cli::Command::Config { print_default } => {
+            if print_default {
+                print!("{}", config::default_text());
+            } else {
+                println!("{:#?}", config::load());
+            }
+        }
+        cli::Command::Analyze {
             capture,
-            last_speed,
-            last: models::PointerInputEvent {
-                x: 0,
-                y: 0,
-                time: std::time::Instant::now(),
-            },
-            working: models::PointerInputEvent {
-                x: 0,
-                y: 0,
-                time: std::time::Instant::now(),
-            },
-            ignore_block: false,
+            refresh_rate,
+        } => {
+            let refresh_rate = refresh_rate.or_else(display_refresh_rate);
+            if let Err(e) = analyze::run(&capture, config::load(), refresh_rate) {
+                eprintln!("Unable to analyze {}: {}", capture.display(), e);
+                std::process::exit(1);
+            }
+        }
+        cli::Command::Record { duration, output } => {
+            if let Err(e) = record::run(config::load(), duration, &output) {
+                eprintln!("Unable to record {}: {}", output.display(), e);
+                std::process::exit(1);
+            }
+        }
+        cli::Command::Convert {
+            capture,
+            to,
+            output,
+        } => match record::convert(&capture, to, output.as_deref()) {
+            Ok(output) => println!("Converted to {}", output.display()),
+            Err(e) => {
+                eprintln!("Unable to convert {}: {}", capture.display(), e);
+                std::process::exit(1);
+            }
+        },
+        cli::Command::Tune => {
+            if let Err(e) = tune::run(config::load()) {
+                eprintln!("Unable to run tuner: {}", e);
+                std::process::exit(1);
+            }
+        }
+        cli::Command::Status { stats } => send_ipc(if stats { "stats" } else { "status" }),
+        cli::Command::Pause => send_ipc("pause"),
+        cli::Command::Resume => send_ipc("resume"),
+        cli::Command::Toggle => send_ipc("toggle"),
+        cli::Command::Reveal => send_ipc("reveal"),
+        cli::Command::Highlight => send_ipc("highlight"),
+        cli::Command::Timing => send_ipc("timing"),
+        cli::Command::SelfTest => {
+            if !self_test::run() {
+                std::process::exit(1);
+            }
+        }
+        cli::Command::FuzzDetector { iterations, seed } => {
+            if !fuzz::fuzz(iterations, seed) {
+                std::process::exit(1);
+            }
+        }
+        cli::Command::Presentation => send_ipc("presentation"),
+        cli::Command::Profile { name } => {
+            send_ipc(format!("profile {}", name.unwrap_or_default()).trim_end())
+        }
+        cli::Command::Set { key, value } => send_ipc(&format!("set {} {}", key, value)),
+        #[cfg(feature = "gui")]
+        cli::Command::Settings => {
+            if let Err(e) = gui::run(config::load()) {
+                eprintln!("Unable to open settings: {}", e);
+                std::process::exit(1);
+            }
         }
     }
+    std::process::exit(0);
+}
 
-    fn get_device(&self) -> Option<evdev::Device> {
-        evdev::enumerate()
-            .find(|(_, device)| {
-                device
-                    .name()
-                    .unwrap_or_default()
-                    .contains(self.device_name.as_str())
-            })
-            .map(|(_, device)| device)
+fn send_ipc(command: &str) {
+    match ipc::send(command) {
+        Ok(reply) if reply.starts_with("error") => {
+            eprintln!("{}", reply);
+            std::process::exit(1);
+        }
+        Ok(reply) => println!("{}", reply),
+        Err(e) => {
+            eprintln!(
+                "Unable to reach the daemon at {}: {}",
+                ipc::socket_path().display(),
+                e
+            );
+            std::process::exit(1);
+        }
     }
+}
 
-    pub fn start_until_error(&mut self) {
-        let device = match self.get_device() {
-            Some(device) => device,
-            None => {
-                println!("No device found!");
-                return;
+// Consumers of reveal/hide events, without any the events are dropped
+fn start_event_threads(
+    config: &mut Config,
+    events: std::sync::mpsc::Receiver<status::OverlayEvent>,
+    ipc: std::sync::mpsc::Sender<ipc::Request>,
+    haptics: haptics::Haptics,
+) {
+    let events = if config.haptic_feedback_ms > 0 {
+        haptics::forward(haptics, events)
+    } else {
+        events
+    };
+
+    // Without the bridge the overlay is drawn as usual
+    #[cfg(feature = "gnome-shell")]
+    let events = match config.shell_bridge {
+        true => match shell_bridge::connect(config, ipc.clone()) {
+            Ok(conn) => shell_bridge::forward(conn, events),
+            Err(e) => {
+                eprintln!("Unable to start the GNOME Shell bridge: {}", e);
+                config.shell_bridge = false;
+                events
+            }
+        },
+        false => events,
+    };
+    #[cfg(not(feature = "gnome-shell"))]
+    if config.shell_bridge {
+        eprintln!("shell_bridge is set, but this build has no GNOME Shell support");
+        config.shell_bridge = false;
+    }
+
+    #[cfg(feature = "mqtt")]
+    if let Some(broker) = config.mqtt_broker.as_deref() {
+        mqtt::start_mqtt_thread(broker, &config.mqtt_topic, events, ipc);
+        return;
+    }
+
+    if config.mqtt_broker.is_some() {
+        eprintln!("mqtt_broker is set, but this build has no MQTT support");
+    }
+    drop((events, ipc));
+}
+
+fn start_http_thread(config: &Config, ipc: std::sync::mpsc::Sender<ipc::Request>) {
+    #[cfg(feature = "http")]
+    if let Some(port) = config.http_port {
+        http::start_http_thread(port, ipc);
+        return;
+    }
+
+    if config.http_port.is_some() {
+        eprintln!("http_port is set, but this build has no HTTP support");
+    }
+    drop(ipc);
+}
+
+fn start_pause_thread(paused: std::sync::Arc<AtomicBool>) {
+    let mut signals = signal_hook::iterator::Signals::new([signal_hook::consts::SIGUSR1]).unwrap();
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            let was_paused = paused.fetch_xor(true, Ordering::SeqCst);
+            eprintln!("{}", if was_paused { "Resumed" } else { "Paused" });
+        }
+    });
+}
+
+// Learning the threshold keeps consuming events after the capture finished
+fn capture_expiry(config: &Config) -> Duration {
+    if config.learn_threshold == models::LearnMode::Off {
+        Duration::from_secs_f64(config.capture_seconds.max(0.0))
+    } else {
+        Duration::MAX
+    }
+}
+
+fn start_capture_thread(
+    config: Config,
+    receiver: std::sync::mpsc::Receiver<logging::LogEvent>,
+    stats: std::sync::Arc<logging::CaptureStats>,
+    learned: std::sync::mpsc::Sender<f64>,
+    shutdown: shutdown::Shutdown,
+) -> thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let start = std::time::Instant::now();
+        let mut capture = logging::Capture::new();
+        let mut capturing = config.capturing();
+        let mut learner = learning::Learner::new(&config);
+        // Binary captures are streamed to disk, long captures don't pile up in memory
+        let mut stream = open_capture_stream(&config);
+        loop {
+            if capturing
+                && start.elapsed() > Duration::from_secs_f64(config.capture_seconds.max(0.0))
+            {
+                eprintln!("Capture finished!");
+                capturing = false;
+                finish_capture(&config, &capture, &stats, start, stream.take());
+
+                if learner.is_none() {
+                    break;
+                }
+            }
+
+            // Events queued before the shutdown still make it into the capture
+            let event = match receiver.recv_timeout(Duration::from_millis(100)) {
+                Err(RecvTimeoutError::Timeout) if shutdown.requested() => {
+                    Err(RecvTimeoutError::Disconnected)
+                }
+                event => event,
+            };
+            match event {
+                Ok(event) => {
+                    if let Some(learner) = learner.as_mut() {
+                        learner.push(&event);
+                    }
+                    if capturing {
+                        match stream.as_mut() {
+                            Some(writer) => {
+                                if let Err(e) =
+                                    writer.write(&logging::CaptureRecord::new(&event, start))
+                                {
+                                    eprintln!("Unable to write capture: {}", e);
+                                    stream = None;
+                                }
+                            }
+                            None => capture.push(event),
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    if !shutdown.requested() {
+                        eprintln!("Receiver disconnected");
+                    }
+                    if capturing {
+                        finish_capture(&config, &capture, &stats, start, stream.take());
+                    }
+                    break;
+                }
+            }
+
+            if let Some(learner) = learner.as_mut() {
+                learner.tick(&learned);
+            }
+        }
+    })
+}
+
+type CaptureStream = logging::BinaryWriter<std::io::BufWriter<std::fs::File>>;
+
+fn open_capture_stream(config: &Config) -> Option<CaptureStream> {
+    let path = std::path::Path::new(config.capture_output.as_deref()?);
+    if logging::CaptureFormat::of(path) != logging::CaptureFormat::Bin {
+        return None;
+    }
+
+    match std::fs::File::create(path)
+        .and_then(|file| logging::BinaryWriter::new(std::io::BufWriter::new(file)))
+    {
+        Ok(writer) => Some(writer),
+        Err(e) => {
+            eprintln!("Unable to write capture to {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn finish_capture(
+    config: &Config,
+    capture: &logging::Capture,
+    stats: &logging::CaptureStats,
+    start: std::time::Instant,
+    stream: Option<CaptureStream>,
+) {
+    stats.report_dropped();
+
+    if let Some(writer) = stream {
+        let path = config.capture_output.as_deref().unwrap_or_default();
+        match writer.finish() {
+            Ok(()) => eprintln!("Capture written to {}", path),
+            Err(e) => eprintln!("Unable to write capture to {}: {}", path, e),
+        }
+        return;
+    }
+
+    // Asked for with --capture, without an output the events are printed
+    if config.capture_output.is_none() || logging::enabled(logging::Verbosity::Trace) {
+        capture.events().iter().for_each(|x| match x {
+            logging::LogEvent::PointerInput { x, y, time } => {
+                eprintln!(
+                    "{:1.6}s PointerInputEvent, x: {}, y: {}",
+                    (*time - start).as_secs_f64(),
+                    x,
+                    y
+                );
+            }
+            logging::LogEvent::Velocity { velocity, time } => {
+                eprintln!(
+                    "{:1.6}s VelocityEvent, velocity: {:1.1}",
+                    (*time - start).as_secs_f64(),
+                    velocity
+                );
+            }
+            logging::LogEvent::Evdev { time, evdev_event } => {
+                eprintln!(
+                    "{:1.6}s EvdevEvent, evdev_event: {:?}, value: {}",
+                    (*time - start).as_secs_f64(),
+                    evdev_event.kind(),
+                    evdev_event.value()
+                );
+            }
+        });
+    }
+
+    if let Some(path) = config.capture_output.as_deref() {
+        match logging::write_records(std::path::Path::new(path), &capture.records(start)) {
+            Ok(()) => eprintln!("Capture written to {}", path),
+            Err(e) => eprintln!("Unable to write capture to {}: {}", path, e),
+        }
+    }
+}
+
+const TELEPORT_INTENSITY: f64 = 1000.0;
+const DESKTOP_INTENSITY: f64 = 800.0;
+const OUTPUT_FOCUS_INTENSITY: f64 = 800.0;
+const REQUESTED_INTENSITY: f64 = 1000.0;
+const PEN_INTENSITY: f64 = 1000.0;
+const EDGE_INTENSITY: f64 = 1000.0;
+const HOT_CORNER_INTENSITY: f64 = 1000.0;
+const CURSOR_SHOWN_INTENSITY: f64 = 1000.0;
+// Units per millimeter pen positions are scaled to, similar to touchpads
+const PEN_RESOLUTION: f64 = 20.0;
+const EDGE_FLASH_SATURATION: f64 = 1000.0;
+// Older kernel timestamps were made on another clock, reading never lags that far
+const MAX_EVENT_AGE: Duration = Duration::from_secs(1);
+// Idle devices are still returned from this often, to notice a shutdown
+const IDLE_WAKEUP: Duration = Duration::from_millis(250);
+
+// Polling interval while nothing is shown
+const HIDDEN_INTERVAL: Duration = Duration::from_millis(1000 / 20);
+
+// Polls once per refresh and renders animation frames on whole refresh intervals
+fn pacing(
+    config: &models::Config,
+    refresh_rate: Option<f64>,
+    theme: &themes::Theme,
+) -> (Duration, Duration) {
+    let interval = Duration::from_millis(theme.frame_interval_ms);
+    match refresh_rate.filter(|_| config.refresh_pacing) {
+        Some(rate) => {
+            let refresh = Duration::from_secs_f64(1.0 / rate);
+            let frames = (interval.as_secs_f64() * rate).round().max(1.0);
+            (refresh, refresh.mul_f64(frames))
+        }
+        None => (Duration::from_millis(1000 / 120), interval),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_ui_loop(
+    mut config: models::Config,
+    _capture: logging::CaptureEmitter,
+    history: std::sync::Arc<models::VelocityHistory>,
+    paused: std::sync::Arc<AtomicBool>,
+    status: std::sync::mpsc::Sender<status::Status>,
+    events: std::sync::mpsc::Sender<status::OverlayEvent>,
+    learned: std::sync::mpsc::Receiver<f64>,
+    ipc: std::sync::mpsc::Receiver<ipc::Request>,
+    overrides: cli::Overrides,
+    shutdown: shutdown::Shutdown,
+) {
+    let mut appearance = appearance::Appearance::lookup();
+    let (conn, screen_num) = match xcb::Connection::connect_with_extensions(
+        None,
+        &[],
+        &[
+            xcb::Extension::RandR,
+            xcb::Extension::Shape,
+            xcb::Extension::Dpms,
+            xcb::Extension::Shm,
+            xcb::Extension::XFixes,
+        ],
+    ) {
+        Ok(conn) => conn,
+        Err(e) => notify::fatal(
+            "Unable to connect to the X server",
+            &format!("Is DISPLAY set and an X session running? ({})", e),
+        ),
+    };
+    let conn = std::sync::Arc::new(conn);
+    let mut win = OverlayWindow::new(
+        config.clone(),
+        std::sync::Arc::clone(&conn),
+        screen_num as _,
+    );
+    let desktop = desktop::resolve(config.desktop);
+    desktop::report(desktop, desktop::is_xwayland(&conn));
+    report::startup(&config, &conn, screen_num, win.get_depth());
+    win.raise_on_show = desktop::raise_on_show(desktop);
+    // Windows of the extras are created once, so is the decision to leave them out
+    let dry_run = overrides.dry_run(&config);
+    // With the shell bridge the extension draws, reloads can't bring the overlay back
+    let drawn = !dry_run && !config.shell_bridge;
+    win.dry_run = !drawn;
+    if dry_run {
+        eprintln!("Dry run, reveals are logged instead of shown");
+    }
+    let mut color_scheme = appearance.color_scheme(config.color_scheme);
+    let mut reduce_motion = appearance.reduce_motion(config.reduced_motion);
+    let mut theme = themes::Theme::load(&config.theme)
+        .for_scheme(color_scheme)
+        .with_reduced_motion(reduce_motion);
+    win.resize(scaled_size(&config, &win.layout));
+    win.set_opacity(config.fallback_opacity);
+    let mut animation = Animation::new(config.clone(), win.size, theme.clone());
+    let mut magnifier = (config.magnifier && drawn)
+        .then(|| magnifier::Magnifier::new(config.magnifier_zoom, win.size, config.mit_shm));
+    let mut edge_flash = (config.edge_flash && drawn).then(|| {
+        edge_flash::EdgeFlash::new(
+            std::sync::Arc::clone(&conn),
+            screen_num as _,
+            &win.layout,
+            theme.clone(),
+            config.edge_flash_width,
+        )
+    });
+    let mut compass = (config.compass && drawn).then(|| {
+        compass::Compass::new(
+            OverlayWindow::with_size(conn, screen_num as _, config.compass_size as _),
+            theme.clone(),
+        )
+    });
+
+    let mut diagonal = None;
+    let mut device_class = None;
+    let mut last_reappearance = None;
+    let mut detector =
+        detection::Detector::new(detection::device_threshold(&config, win.layout.diagonal()));
+    let mut last_pointer = None;
+    let mut edge_armed = true;
+    let mut hot_corner: Option<(models::ScreenEdge, std::time::Instant, bool)> = None;
+    // The hidden cursor would be just as visible as an overlay
+    let hide_cursor_seconds = |config: &Config| {
+        if drawn {
+            config.hide_cursor_seconds
+        } else {
+            0.0
+        }
+    };
+    let mut cursor_hider = cursor_hider::CursorHider::new(&win.conn, hide_cursor_seconds(&config));
+    let root_visual = win
+        .conn
+        .get_setup()
+        .roots()
+        .nth(screen_num as _)
+        .unwrap()
+        .root_visual_type();
+    let recorder = |config: &Config| {
+        config
+            .reveal_screenshots
+            .as_deref()
+            .map(|dir| screenshots::Recorder::new(dir, config.reveal_screenshot_size, &root_visual))
+    };
+    let mut screenshots = recorder(&config);
+    let mut screenshot_settings = (
+        config.reveal_screenshots.clone(),
+        config.reveal_screenshot_size,
+    );
+    let mut highlight_toggled = false;
+    let mut profile_before_presentation = None;
+    let mut button_down = false;
+
+    let mut last_render = std::time::Instant::now();
+    let mut dumps = logging::DumpTimer::new(config.debug_dump_seconds);
+    let mut dump_output = config.debug_dump_output.clone();
+
+    let fps_hidden = HIDDEN_INTERVAL;
+    let mut refresh_rate = win.conn.get_refresh_rate(win.get_root());
+    let (mut fps_visible, mut fps_animation) = pacing(&config, refresh_rate, &theme);
+
+    let mut last_status = status::Status::new();
+
+    let mut base = config.clone();
+    // Settings changed over IPC, kept when the config file is reloaded
+    let mut runtime_values = std::collections::BTreeMap::new();
+    let mut config_modified = config::modified();
+    let mut last_config_check = std::time::Instant::now();
+    let mut active_class = None;
+    let mut minute_of_day = None;
+    let mut display_off = false;
+    let mut last_dpms_check = std::time::Instant::now();
+    let mut revealing = false;
+    let mut last_reveal_end: Option<std::time::Instant> = None;
+    let mut locked = false;
+    let mut predictor = models::PointerPredictor::new();
+    let mut interpolator = models::PointerInterpolator::new();
+    let mut frame_stats = timing::FrameStats::new();
+    let mut reveal_requested = false;
+    let mut screencast: Option<std::sync::Arc<AtomicBool>> = None;
+    let mut typing: Option<typing::Typing> = None;
+    let mut learned_threshold = None;
+    let mut saved_state = None;
+    let mut usage = usage::Usage::load();
+    // Triggered while hidden, tells the reason of the next reveal
+    let mut trigger = None;
+    let wm_changes = win.wm.subscribe();
+
+    // Everything is connected by now, later config edits can't lift the filter. Nothing
+    // can be spawned under it, the desktop preferences have to be known before.
+    if config.seccomp != models::SeccompMode::Off {
+        appearance.wait();
+    }
+    if config.seccomp == models::SeccompMode::Enforce && config.hide_during_screencast {
+        eprintln!("hide_during_screencast runs pw-dump, which the seccomp filter doesn't allow");
+    }
+    if let Err(e) = seccomp::install(config.seccomp) {
+        eprintln!("Unable to install the seccomp filter: {}", e);
+    }
+
+    while !shutdown.requested() {
+        usage.visible(win.visible);
+        let layout_changed = win.handle_event();
+        if layout_changed {
+            if let Some(edge_flash) = edge_flash.as_mut() {
+                edge_flash.rebuild(&win.layout);
+            }
+            refresh_rate = win.conn.get_refresh_rate(win.get_root());
+            (fps_visible, fps_animation) = pacing(&config, refresh_rate, &theme);
+            if diagonal.is_none() {
+                detector.reconfigure(detection::device_threshold(&config, win.layout.diagonal()));
+            }
+        }
+        if layout_changed && win.size != scaled_size(&config, &win.layout) {
+            win.resize(scaled_size(&config, &win.layout));
+            animation = Animation::new(config.clone(), win.size, theme.clone());
+            if magnifier.is_some() {
+                magnifier = Some(magnifier::Magnifier::new(
+                    config.magnifier_zoom,
+                    win.size,
+                    config.mit_shm,
+                ));
+            }
+        }
+
+        let current_status = status::Status {
+            enabled: !paused.load(Ordering::SeqCst),
+            reveals: last_status.reveals,
+        };
+        if current_status != last_status {
+            last_status = current_status;
+            // Nobody listens when the status output is disabled
+            let _ = status.send(last_status);
+        }
+
+        // Settings changed at runtime apply to the base config, below any app profile
+        let mut reload = false;
+        if std::mem::take(&mut highlight_toggled) {
+            base.highlight = !base.highlight;
+            runtime_values.insert(String::from("highlight"), toml::Value::from(base.highlight));
+            reload = true;
+        }
+        if win.take_dismissed() {
+            detector.reset();
+            locked = false;
+            if base.highlight {
+                base.highlight = false;
+                runtime_values.insert(String::from("highlight"), toml::Value::from(false));
+                reload = true;
+            }
+        }
+
+        let class = history.latest().class();
+        if class.is_some() && class != device_class {
+            device_class = class;
+            reload = true;
+        }
+
+        if let Ok(threshold) = learned.try_recv() {
+            learned_threshold = Some(threshold);
+            // Learned thresholds are in device units
+            base.threshold = match (base.threshold_unit, diagonal.or(win.layout.diagonal())) {
+                (models::ThresholdUnit::Screen, Some(diagonal)) => threshold / diagonal * 100.0,
+                _ => threshold,
+            };
+            base.explicit.insert(String::from("threshold"));
+            reload = true;
+        }
+
+        while let Ok(request) = ipc.try_recv() {
+            let reply = match request.command {
+                ipc::Command::Pause => {
+                    paused.store(true, Ordering::SeqCst);
+                    String::from("paused")
+                }
+                ipc::Command::Resume => {
+                    paused.store(false, Ordering::SeqCst);
+                    String::from("enabled")
+                }
+                ipc::Command::Toggle => {
+                    let was_paused = paused.fetch_xor(true, Ordering::SeqCst);
+                    String::from(if was_paused { "enabled" } else { "paused" })
+                }
+                ipc::Command::Status => last_status.to_json(),
+                ipc::Command::Reveal => {
+                    reveal_requested = true;
+                    String::from("revealed")
+                }
+                ipc::Command::Timing => frame_stats.to_json(),
+                ipc::Command::Stats => usage.to_json(),
+                ipc::Command::Highlight => {
+                    base.highlight = !base.highlight;
+                    runtime_values
+                        .insert(String::from("highlight"), toml::Value::from(base.highlight));
+                    reload = true;
+                    String::from(if base.highlight {
+                        "highlight on"
+                    } else {
+                        "highlight off"
+                    })
+                }
+                ipc::Command::Profile { name } => match name {
+                    Some(name) if config::named_profile(&base, &name).is_none() => {
+                        format!("error: there is no profile `{}`", name)
+                    }
+                    name => {
+                        let reply = format!("profile {}", name.as_deref().unwrap_or("off"));
+                        base.active_profile = name;
+                        reload = true;
+                        reply
+                    }
+                },
+                ipc::Command::Presentation => {
+                    let presenting =
+                        base.active_profile.as_deref() == Some(config::PRESENTATION_PROFILE);
+                    base.active_profile = if presenting {
+                        profile_before_presentation.take()
+                    } else {
+                        profile_before_presentation = base.active_profile.take();
+                        Some(config::PRESENTATION_PROFILE.to_string())
+                    };
+                    reload = true;
+                    String::from(if presenting {
+                        "presentation off"
+                    } else {
+                        "presentation on"
+                    })
+                }
+                ipc::Command::Set { key, value } => {
+                    match config::with_value(&base, &key, value.clone()) {
+                        Ok(updated) => {
+                            base = updated;
+                            runtime_values.insert(key, value);
+                            reload = true;
+                            String::from("ok")
+                        }
+                        Err(e) => format!("error: {}", e),
+                    }
+                }
+            };
+            let _ = request.reply.send(reply);
+        }
+
+        // Edits of the config file apply without a restart
+        if last_config_check.elapsed() > Duration::from_secs(1) {
+            last_config_check = std::time::Instant::now();
+            let modified = config::modified();
+            if modified != config_modified {
+                config_modified = modified;
+                eprintln!("Config changed, reloading");
+                if let Some(mut loaded) = config::reload() {
+                    overrides.apply(&mut loaded);
+                    if loaded.dry_run != base.dry_run {
+                        eprintln!("Changes of dry_run apply after a restart");
+                    }
+                    // The profile is switched at runtime and kept in the state file
+                    loaded.active_profile = base.active_profile.take();
+                    for (key, value) in &runtime_values {
+                        if let Ok(updated) = config::with_value(&loaded, key, value.clone()) {
+                            loaded = updated;
+                        }
+                    }
+                    base = loaded;
+                    learned_threshold = None;
+                    reload = true;
+                }
+            }
+        }
+
+        let state = state::State {
+            paused: paused.load(Ordering::SeqCst),
+            active_profile: base.active_profile.clone(),
+            learned_threshold,
+            config_modified: state::stamp(config_modified),
+        };
+        if saved_state.as_ref() != Some(&state) {
+            if let Err(e) = state::save(&state) {
+                eprintln!(
+                    "Unable to save state to {}: {}",
+                    state::state_path().display(),
+                    e
+                );
+            }
+            saved_state = Some(state);
+        }
+
+        if win.get_active_class() != active_class.as_ref() {
+            active_class = win.get_active_class().cloned();
+            reload = true;
+        }
+
+        let now = chrono::Timelike::num_seconds_from_midnight(&chrono::Local::now()) / 60;
+        if minute_of_day != Some(now) {
+            minute_of_day = Some(now);
+            reload |= !base.schedule.is_empty();
+        }
+
+        if appearance.take_ready() {
+            color_scheme = appearance.color_scheme(config.color_scheme);
+            reduce_motion = appearance.reduce_motion(config.reduced_motion);
+            eprintln!("Color scheme: {:?}", color_scheme);
+            eprintln!("Reduced motion: {}", reduce_motion);
+            reload = true;
+        }
+
+        if reload {
+            config = config::effective(&base, device_class, now, active_class.as_ref());
+            let scheme = appearance.color_scheme(config.color_scheme);
+            if scheme != color_scheme {
+                color_scheme = scheme;
+                eprintln!("Color scheme: {:?}", color_scheme);
+            }
+            let reduced = appearance.reduce_motion(config.reduced_motion);
+            if reduced != reduce_motion {
+                reduce_motion = reduced;
+                eprintln!("Reduced motion: {}", reduce_motion);
+            }
+            theme = themes::Theme::load(&config.theme)
+                .for_scheme(color_scheme)
+                .with_reduced_motion(reduce_motion);
+            (fps_visible, fps_animation) = pacing(&config, refresh_rate, &theme);
+            detector.reconfigure(detection::device_threshold(
+                &config,
+                diagonal.or(win.layout.diagonal()),
+            ));
+            win.resize(scaled_size(&config, &win.layout));
+            win.set_opacity(config.fallback_opacity);
+            animation = Animation::new(config.clone(), win.size, theme.clone());
+            if magnifier.is_some() {
+                magnifier = Some(magnifier::Magnifier::new(
+                    config.magnifier_zoom,
+                    win.size,
+                    config.mit_shm,
+                ));
+            }
+            if let Some(compass) = compass.as_mut() {
+                compass.resize(config.compass_size as _, theme.clone());
+            }
+            if let Some(hider) = cursor_hider.as_mut() {
+                hider.show(&win.conn, win.get_root());
+            }
+            cursor_hider = cursor_hider::CursorHider::new(&win.conn, hide_cursor_seconds(&config));
+            // Reloads follow every focus change, the writer thread is only replaced on changes
+            let settings = (
+                config.reveal_screenshots.clone(),
+                config.reveal_screenshot_size,
+            );
+            if settings != screenshot_settings {
+                screenshot_settings = settings;
+                screenshots = recorder(&config);
+            }
+            dumps.set_interval(config.debug_dump_seconds);
+            if config.debug_dump_output != dump_output {
+                dump_output = config.debug_dump_output.clone();
+                logging::set_dump_output(dump_output.as_deref());
+            }
+        }
+
+        let suppressed = active_class
+            .as_ref()
+            .is_some_and(|class| class.matches_any(&config.exclude_apps))
+            || (config.exclude_fullscreen && win.wm.fullscreen());
+
+        if win.wm.take(&wm_changes).compositor {
+            if win.wm.compositor() {
+                eprintln!("Compositor started");
+            } else if win.get_depth() == 32 {
+                // Nothing blends the alpha channel any more, the opaque fallback is shaped
+                eprintln!("Compositor stopped, transparent parts of the overlay show up black");
+            } else {
+                eprintln!("Compositor stopped");
+            }
+        }
+
+        // Querying DPMS is a round trip, the display state rarely changes
+        if last_dpms_check.elapsed() > Duration::from_secs(1) {
+            last_dpms_check = std::time::Instant::now();
+            display_off = win.conn.display_off();
+        }
+
+        if config.hide_during_screencast && screencast.is_none() {
+            screencast = Some(screencast::start_screencast_thread());
+        }
+        let casting = config.hide_during_screencast
+            && screencast
+                .as_ref()
+                .is_some_and(|casting| casting.load(Ordering::Relaxed));
+
+        if !last_status.enabled || suppressed || display_off || casting {
+            detector.reset();
+            revealing = false;
+            locked = false;
+            if let Some(hider) = cursor_hider.as_mut() {
+                hider.show(&win.conn, win.get_root());
+            }
+
+            if win.visible {
+                win.hide();
+                let _ = events.send(status::OverlayEvent::Hide);
+                if let Some(compass) = compass.as_mut() {
+                    compass.hide();
+                }
+                if let Some(edge_flash) = edge_flash.as_mut() {
+                    edge_flash.hide();
+                }
+            }
+            win.conn.flush().unwrap();
+
+            thread::sleep(fps_hidden);
+            continue;
+        }
+
+        let velocity_event = history.latest();
+        if velocity_event.reappeared().is_some() && velocity_event.reappeared() != last_reappearance
+        {
+            last_reappearance = velocity_event.reappeared();
+            if config.pen_reveal {
+                detector.trigger(PEN_INTENSITY);
+                trigger = Some(usage::Trigger::Pen);
             }
+        }
+        if velocity_event.diagonal().is_some() && velocity_event.diagonal() != diagonal {
+            diagonal = velocity_event.diagonal();
+            detector.reconfigure(detection::device_threshold(
+                &config,
+                diagonal.or(win.layout.diagonal()),
+            ));
+        }
+        let velocity = if velocity_event.expired(config.motion_expiry()) {
+            0.0
+        } else if config.velocity_window_ms > 0.0 {
+            history.mean(std::time::Duration::from_secs_f64(
+                config.velocity_window_ms / 1000.0,
+            ))
+        } else {
+            velocity_event.velocity()
+        };
+
+        if let Some(hider) = cursor_hider.as_mut() {
+            if hider.update(&win.conn, win.get_root(), velocity > 0.0) {
+                detector.trigger(CURSOR_SHOWN_INTENSITY);
+                trigger = Some(usage::Trigger::CursorShown);
+            }
+        }
+
+        // Sampling the pointer while hidden costs a round trip, only done when needed
+        let sampled_pointer = (config.teleport_reveal
+            || !config.edge_reveal.is_empty()
+            || !config.hot_corners.is_empty()
+            || !config.output_sensitivity.is_empty())
+        .then(|| frame_stats.pointer(|| win.pointer()))
+        .flatten();
+        if let Some(pointer) = sampled_pointer {
+            // A large jump without local motion: warped or handed over by synergy/barrier
+            let teleported = last_pointer.is_some_and(|last: models::Position32| {
+                last.distance(&pointer) > config.teleport_distance
+            });
+            if config.teleport_reveal && teleported && velocity == 0.0 {
+                detector.trigger(TELEPORT_INTENSITY);
+                trigger = Some(usage::Trigger::Teleport);
+            }
+
+            // Pushing into an edge keeps the device moving while the cursor stays put,
+            // hitting it again needs the cursor to leave the edge first
+            let at_edge = config
+                .edge_reveal
+                .iter()
+                .any(|edge| edge.contains(pointer, &win.layout));
+            if !at_edge {
+                edge_armed = true;
+            } else if edge_armed
+                && last_pointer == Some(pointer)
+                && velocity > config.edge_reveal_velocity
+            {
+                edge_armed = false;
+                detector.trigger(EDGE_INTENSITY);
+                trigger = Some(usage::Trigger::Edge);
+            }
+
+            let corner = config
+                .hot_corners
+                .iter()
+                .find(|(corner, _)| corner.contains(pointer, &win.layout))
+                .map(|(corner, action)| (*corner, *action));
+            hot_corner = match (corner, hot_corner) {
+                (Some((corner, _)), Some(visit)) if visit.0 == corner => Some(visit),
+                (Some((corner, _)), _) => Some((corner, std::time::Instant::now(), false)),
+                (None, _) => None,
+            };
+            // Fires once per visit, after resting in the corner
+            if let (Some((_, action)), Some((corner, since, false))) = (corner, hot_corner) {
+                if since.elapsed()
+                    >= Duration::from_secs_f64(config.hot_corner_ms.max(0.0) / 1000.0)
+                {
+                    hot_corner = Some((corner, since, true));
+                    match action {
+                        models::HotCornerAction::Reveal => {
+                            detector.trigger(HOT_CORNER_INTENSITY);
+                            trigger = Some(usage::Trigger::HotCorner);
+                        }
+                        models::HotCornerAction::Highlight => highlight_toggled = true,
+                    }
+                }
+            }
+            last_pointer = Some(pointer);
+        }
+
+        if win.take_desktop_switch() && config.desktop_reveal {
+            detector.trigger(DESKTOP_INTENSITY);
+            trigger = Some(usage::Trigger::Desktop);
+        }
+
+        if config.output_focus_reveal && win.take_output_switch() {
+            detector.trigger(OUTPUT_FOCUS_INTENSITY);
+            trigger = Some(usage::Trigger::OutputFocus);
+        }
+
+        if std::mem::take(&mut reveal_requested) {
+            detector.trigger(REQUESTED_INTENSITY);
+            trigger = Some(usage::Trigger::Requested);
+        }
+
+        // The same hand motion covers fewer pixels on a large output of the same resolution
+        let sensitivity = sampled_pointer
+            .and_then(|pointer| win.layout.monitor_at(pointer))
+            .and_then(|monitor| config.output_sensitivity.get(&monitor.name))
+            .copied()
+            .unwrap_or(1.0);
+
+        // Motion right after keystrokes is likely a palm, triggered reveals still show
+        let keyboard_name = (config.typing_pause_ms > 0.0).then_some(&config.keyboard_name);
+        if typing.as_ref().map(typing::Typing::keyboard_name) != keyboard_name.map(String::as_str) {
+            if let Some(typing) = typing.take() {
+                typing.stop();
+            }
+            typing = keyboard_name.map(|name| typing::Typing::start(name));
+        }
+        let typed = config.typing_pause_ms > 0.0
+            && typing.as_ref().is_some_and(|typing| {
+                typing.typed_within(Duration::from_secs_f64(config.typing_pause_ms / 1000.0))
+            });
+        let velocity = if typed { 0.0 } else { velocity * sensitivity };
+
+        let was_revealing = revealing;
+        revealing = detector.update(velocity);
+        if revealing && !was_revealing {
+            // A second shake shortly after the first latches the overlay, a third one releases it
+            let double_shake = last_reveal_end.is_some_and(|end| {
+                end.elapsed() < Duration::from_secs_f64(config.shake_lock_seconds.max(0.0))
+            });
+            if locked {
+                locked = false;
+                last_reveal_end = None;
+            } else if config.shake_lock && double_shake {
+                locked = true;
+            }
+        } else if was_revealing && !revealing {
+            last_reveal_end = Some(std::time::Instant::now());
+        }
+        // Buttons as of the pointer query of the last frame, locked overlays query each frame
+        if locked && !revealing && win.button_pressed {
+            locked = false;
+            last_reveal_end = None;
+        }
+
+        // Only a latched overlay is dismissed, a passing reveal leaves clicks through
+        let hotspot = if locked || config.highlight {
+            config.dismiss_hotspot
+        } else {
+            0
+        };
+        if win.hotspot != hotspot.min(win.size) {
+            win.set_hotspot(hotspot);
+        }
+        animation.set_dismiss(win.hotspot);
+
+        let showing = revealing || config.highlight || locked;
+        if !showing || win.visible {
+            trigger = None;
+        }
+        // Drag and drop, menus and games grab the pointer, the overlay would get in the way
+        let grabbed = showing && config.hide_during_grabs && win.pointer_grabbed;
+
+        let frame_start = std::time::Instant::now();
+        // The highlight keeps the ring visible at a fixed intensity between reveals
+        let pause = if showing && !grabbed {
+            let avg_ui = detector.intensity().max(if config.highlight || locked {
+                config.highlight_intensity
+            } else {
+                0.0
+            });
+
+            let Some(pointer) = sampled_pointer.or_else(|| frame_stats.pointer(|| win.pointer()))
+            else {
+                thread::sleep(fps_visible);
+                continue;
+            };
+            // Read from the pointer query of this frame, no extra round trip
+            if config.click_rings {
+                if win.button_pressed && !button_down {
+                    animation.click();
+                }
+                button_down = win.button_pressed;
+            }
+
+            if !win.visible {
+                if dry_run {
+                    eprintln!(
+                        "Dry run: reveal at {},{} with velocity {:.0}, smoothed {:.0}",
+                        pointer.x,
+                        pointer.y,
+                        velocity,
+                        detector.weighted()
+                    );
+                }
+                last_status.reveals += 1;
+                usage.reveal(trigger.take().unwrap_or(if revealing {
+                    usage::Trigger::Shake
+                } else {
+                    usage::Trigger::Highlight
+                }));
+                let _ = status.send(last_status);
+                let _ = events.send(status::OverlayEvent::Reveal);
+
+                if let Some(recorder) = screenshots.as_ref() {
+                    recorder.record(
+                        win.get_conn(),
+                        win.get_root(),
+                        &win.layout,
+                        pointer,
+                        avg_ui,
+                        win.get_active_class(),
+                    );
+                }
+                if let Some(compass) = compass.as_mut() {
+                    compass.reveal(&win.layout);
+                }
+                if let Some(magnifier) = magnifier.as_mut() {
+                    magnifier.capture(win.get_conn(), win.get_root(), &win.layout, pointer);
+                }
+                animation.start_intro();
+            }
+
+            let position = if config.interpolate_pointer {
+                interpolator.interpolate(pointer)
+            } else {
+                pointer
+            };
+            let center = predictor.predict(
+                position,
+                Duration::from_secs_f64(config.prediction_ms.max(0.0) / 1000.0),
+            );
+            // Queries above are round trips, from here on requests are only queued and
+            // the server applies them in order: move, map, draw, then the extras
+            win.set_center_position(center);
+            win.show();
+            if last_render.elapsed() > fps_animation {
+                last_render = std::time::Instant::now();
+                frame_stats.draw(|| match magnifier.as_mut() {
+                    Some(magnifier) => {
+                        // The lens image replaces the whole window, no clear needed
+                        magnifier.draw(
+                            &win.conn,
+                            win.get_win(),
+                            win.get_gfx(),
+                            pointer,
+                            (win.size / 2).saturating_sub(16),
+                            win.get_depth(),
+                        );
+                        animation.draw(
+                            win.get_conn(),
+                            win.get_win(),
+                            win.get_gfx(),
+                            win.size,
+                            avg_ui,
+                        );
+                    }
+                    None => animation.play(
+                        win.get_conn(),
+                        win.get_win(),
+                        win.get_gfx(),
+                        win.size,
+                        avg_ui,
+                    ),
+                });
+            }
+
+            if let Some(compass) = compass.as_mut() {
+                compass.point_at(center);
+            }
+            if let Some(edge_flash) = edge_flash.as_mut() {
+                edge_flash.show(pointer, (avg_ui / EDGE_FLASH_SATURATION).min(1.0));
+            }
+            fps_visible
+        } else if win.visible && !grabbed && (animation.start_outro() || animation.outro_playing())
+        {
+            // Keep the window up until the outro of the theme finished
+            if let Some(compass) = compass.as_mut() {
+                compass.hide();
+            }
+            frame_stats.draw(|| {
+                animation.play(win.get_conn(), win.get_win(), win.get_gfx(), win.size, 0.0)
+            });
+            fps_visible
+        } else {
+            if win.visible {
+                win.hide();
+                let _ = events.send(status::OverlayEvent::Hide);
+                if let Some(compass) = compass.as_mut() {
+                    compass.hide();
+                }
+                if let Some(edge_flash) = edge_flash.as_mut() {
+                    edge_flash.hide();
+                }
+            }
+            fps_hidden
         };
 
-        println!("Device found: {}", device.name().unwrap_or("(unknown)"));
+        // A single flush per frame, including the cursor hider and everything else queued
+        if win.visible {
+            frame_stats.flush(|| win.conn.flush().unwrap());
+        } else {
+            win.conn.flush().unwrap();
+        }
+        if showing && !grabbed {
+            frame_stats.frame(frame_start.elapsed(), fps_visible);
+        }
+        thread::sleep(pause);
+
+        if dumps.due() {
+            logging::dump(format_args!("{:?}", win));
+            logging::dump(format_args!("{}", frame_stats.summary()));
+        }
+    }
+
+    // Leaves the screen as it was, the extras unmap with the connection
+    if win.visible {
+        win.hide();
+        let _ = events.send(status::OverlayEvent::Hide);
+    }
+    usage.visible(false);
+    if let Some(cursor_hider) = cursor_hider.as_mut() {
+        cursor_hider.show(&win.conn, win.get_root());
+    }
+    if let Err(e) = win.conn.flush() {
+        eprintln!("Unable to flush the X connection: {}", e);
+    }
+}
+
+fn scaled_size(config: &models::Config, layout: &models::ScreenLayout) -> u32 {
+    let scale = match layout.primary() {
+        Some(monitor) if config.dpi_scaling => monitor.dpi_scale(),
+        _ => 1.0,
+    };
+    ((config.window_size as f64) * scale).round() as u32
+}
+
+fn start_motion_thread(
+    config: models::Config,
+    capture: logging::CaptureEmitter,
+    history: std::sync::Arc<models::VelocityHistory>,
+    haptics: haptics::Haptics,
+    shutdown: shutdown::Shutdown,
+) -> thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut notified = false;
+        let mut poller: Option<pointer_source::PointerPoller> = None;
+        let mut tracker = motion::MotionTracker::new(
+            capture.clone(),
+            std::sync::Arc::clone(&history),
+            Some(detection::SpikeFilter::new(&config)),
+            detection::RateSmoother::new(&config),
+            shutdown.clone(),
+        );
+        let mut poll_tracker = motion::MotionTracker::new(
+            capture.clone(),
+            std::sync::Arc::clone(&history),
+            None,
+            detection::RateSmoother::new(&config),
+            shutdown.clone(),
+        );
 
-        let result = self.listen_event_loop(device);
-        if let Err(e) = result {
-            println!("Error while monitoring: {}", e);
+        if config.pointer_only {
+            let mut poller = pointer_source::PointerPoller::connect(
+                config.pointer_poll_hz,
+                config.motion_expiry(),
+                capture.clone(),
+            )
+            .unwrap_or_else(|| {
+                notify::fatal("Pointer polling failed", "Unable to poll the X pointer")
+            });
+            let _ = poll_tracker.run(&mut poller, None);
         }
+        while !shutdown.requested() {
+            // While falling back the poller keeps sampling between device retries
+            match poller.as_mut() {
+                Some(poller) => {
+                    let until = std::time::Instant::now() + Duration::from_secs(1);
+                    let _ = poll_tracker.run(poller, Some(until));
+                }
+                None => thread::sleep(Duration::from_secs(1)),
+            }
+
+            let monitor = MotionMonitor::open(
+                DeviceFilter::new(&config),
+                capture.clone(),
+                logging::DumpTimer::new(config.debug_dump_seconds),
+                config.capture_keys,
+                Duration::from_secs_f64(config.pen_reveal_seconds.max(0.0)),
+                config.motion_expiry(),
+                touch::TouchSettings::new(&config),
+            );
+
+            if let Some(mut monitor) = monitor {
+                poller = None;
+                if config.haptic_feedback_ms > 0 {
+                    haptics.attach(&mut monitor.device, config.haptic_feedback_ms);
+                }
+                if let Err(e) = tracker.run(&mut monitor, None) {
+                    eprintln!("Error while monitoring: {}", e);
+                }
+                haptics.detach();
+                eprintln!("Device disconnected!");
+                continue;
+            }
+
+            // Without the input devices granted polling is all a sandbox can do
+            let sandboxed = desktop::sandboxed();
+            if (config.pointer_fallback || sandboxed) && poller.is_none() {
+                poller = pointer_source::PointerPoller::connect(
+                    config.pointer_poll_hz,
+                    config.motion_expiry(),
+                    capture.clone(),
+                );
+            }
+
+            // Only notify once, the device is retried every second
+            if !notified {
+                notified = true;
+                let fallback = if poller.is_some() {
+                    " Falling back to polling the pointer position."
+                } else {
+                    ""
+                };
+                let reason = if sandboxed {
+                    "the sandbox hides /dev/input unless granted --device=input"
+                } else {
+                    "access to /dev/input was denied"
+                };
+                notify::error(
+                    "No input device found",
+                    &format!(
+                        "No device matching \"{}\" is available, or {}.{}",
+                        config.device_name, reason, fallback
+                    ),
+                );
+            }
+        }
+    })
+}
+
+// Udev derives ID_INPUT_POINTINGSTICK from the same property, older drivers only name them
+fn device_class(device: &evdev::Device) -> models::DeviceClass {
+    let keys = device.supported_keys();
+    let has_key = |key| keys.is_some_and(|keys| keys.contains(key));
+    if device
+        .properties()
+        .contains(evdev::PropType::POINTING_STICK)
+        || device.name().unwrap_or_default().contains("TrackPoint")
+    {
+        models::DeviceClass::Trackpoint
+    } else if has_key(evdev::Key::BTN_TOOL_PEN) {
+        models::DeviceClass::Tablet
+    } else if has_key(evdev::Key::BTN_TOOL_FINGER) {
+        models::DeviceClass::Touchpad
+    } else {
+        models::DeviceClass::Mouse
+    }
+}
+
+// Picks the input device to monitor, touchscreens jump instead of moving
+struct DeviceFilter {
+    name: String,
+    exclude: Vec<String>,
+    exclude_touchscreens: bool,
+}
+
+impl DeviceFilter {
+    fn new(config: &Config) -> DeviceFilter {
+        DeviceFilter {
+            name: config.device_name.clone(),
+            exclude: config.exclude_devices.clone(),
+            exclude_touchscreens: config.exclude_touchscreens,
+        }
+    }
+
+    fn matches(&self, device: &evdev::Device) -> bool {
+        let name = device.name().unwrap_or_default();
+        if !name.contains(self.name.as_str()) {
+            return false;
+        }
+        if self
+            .exclude
+            .iter()
+            .any(|exclude| name.contains(exclude.as_str()))
+        {
+            return false;
+        }
+        // Direct devices map touches to screen positions, e.g. touchscreens and pen displays
+        !(self.exclude_touchscreens && device.properties().contains(evdev::PropType::DIRECT))
+    }
+}
+
+struct MotionMonitor {
+    devices: DeviceFilter,
+    device: evdev::Device,
+    capture: logging::CaptureEmitter,
+    working: models::PointerInputEvent,
+    pending: std::collections::VecDeque<motion::Motion>,
+    events: Vec<evdev::InputEvent>,
+    touches: Option<touch::Touches>,
+    dumps: logging::DumpTimer,
+    capture_keys: models::KeyCapture,
+    keyboard: bool,
+    diagonal: Option<f64>,
+    class: Option<models::DeviceClass>,
+    pen_scale: Option<f64>,
+    pen_reveal_after: Duration,
+    proximity_out: Option<std::time::Instant>,
+    skip_velocity: bool,
+    motion_expiry: Duration,
+    moving: bool,
+    // Clock of the kernel timestamps of the events
+    clock: libc::clockid_t,
+}
+
+impl Debug for MotionMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MotionMonitor")
+            .field("device_name", &self.devices.name)
+            .field("working", &self.working)
+            .field("pending", &self.pending.len())
+            .field("touches", &self.touches)
+            .finish()
+    }
+}
 
-        println!("Device disconnected!");
+impl MotionMonitor {
+    // Applies the key privacy setting to an event before it is captured
+    fn captured(&self, event: evdev::InputEvent) -> Option<evdev::InputEvent> {
+        let is_key = event.event_type() == evdev::EventType::KEY;
+        match self.capture_keys {
+            models::KeyCapture::Keep => Some(event),
+            models::KeyCapture::Drop if self.keyboard => None,
+            models::KeyCapture::Redact | models::KeyCapture::Drop if is_key => {
+                Some(evdev::InputEvent::new(event.event_type(), 0, event.value()))
+            }
+            _ => Some(event),
+        }
     }
 
-    fn listen_event_loop(&mut self, mut device: evdev::Device) -> Result<(), evdev::Error> {
-        println!(
+    // Finds the device to monitor, None when no device matches
+    pub fn open(
+        devices: DeviceFilter,
+        capture: logging::CaptureEmitter,
+        dumps: logging::DumpTimer,
+        capture_keys: models::KeyCapture,
+        pen_reveal_after: Duration,
+        motion_expiry: Duration,
+        touch_settings: touch::TouchSettings,
+    ) -> Option<MotionMonitor> {
+        let device = match evdev::enumerate().find(|(_, device)| devices.matches(device)) {
+            Some((_, device)) => device,
+            None => {
+                eprintln!("No device found!");
+                return None;
+            }
+        };
+
+        eprintln!("Device found: {}", device.name().unwrap_or("(unknown)"));
+
+        // Letter keys mean keystrokes could end up in captures
+        let keyboard = device.supported_keys().is_some_and(|keys| {
+            keys.contains(evdev::Key::KEY_A) && keys.contains(evdev::Key::KEY_Z)
+        });
+        if keyboard && capture_keys == models::KeyCapture::Drop {
+            eprintln!("Device is a keyboard, its events are not captured");
+        }
+
+        let state = device.get_abs_state().ok();
+        let class = device_class(&device);
+        eprintln!("Device class: {:?}", class);
+
+        // Tablets resolve far finer than touchpads, pen velocities are scaled to match
+        let pen = device
+            .supported_keys()
+            .is_some_and(|keys| keys.contains(evdev::Key::BTN_TOOL_PEN));
+        let pen_scale = state
+            .map(|state| state[evdev::AbsoluteAxisType::ABS_X.0 as usize].resolution)
+            .filter(|resolution| pen && *resolution > 0)
+            .map(|resolution| PEN_RESOLUTION / resolution as f64);
+        if let Some(scale) = pen_scale {
+            eprintln!(
+                "Device is a tablet, pen velocities are scaled by {:.3}",
+                scale
+            );
+        }
+
+        // The axis ranges span the whole screen, velocities relative to them are comparable
+        let diagonal = state.and_then(|state| {
+            let range = |axis: evdev::AbsoluteAxisType| {
+                let info = state[axis.0 as usize];
+                (info.maximum - info.minimum) as f64
+            };
+            let diagonal =
+                range(evdev::AbsoluteAxisType::ABS_X).hypot(range(evdev::AbsoluteAxisType::ABS_Y));
+            (diagonal > 0.0).then_some(diagonal * pen_scale.unwrap_or(1.0))
+        });
+
+        let clock = event_clock(&device);
+        // Multitouch devices are followed by their first contact
+        let touches = touch::Touches::open(&device, touch_settings);
+
+        eprintln!(
             "Starts monitoring device: {}",
             device.name().unwrap_or("(unknown)")
         );
 
-        let mut last_debug = std::time::Instant::now();
-        loop {
-            self.ignore_block = false;
-            device.fetch_events()?.for_each(|e| self.handle_event(e));
+        Some(MotionMonitor {
+            devices,
+            device,
+            capture,
+            working: models::PointerInputEvent {
+                x: 0,
+                y: 0,
+                time: std::time::Instant::now(),
+            },
+            pending: std::collections::VecDeque::new(),
+            events: Vec::new(),
+            touches,
+            dumps,
+            capture_keys,
+            keyboard,
+            diagonal,
+            class: Some(class),
+            pen_scale,
+            pen_reveal_after,
+            proximity_out: None,
+            skip_velocity: false,
+            motion_expiry,
+            moving: false,
+            clock,
+        })
+    }
+
+    // The kernel stamps events when they happen, reading them may be much later under load
+    fn event_time(&self, event: &evdev::InputEvent) -> std::time::Instant {
+        let now = std::time::Instant::now();
+        let mut clock_now = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        if unsafe { libc::clock_gettime(self.clock, &mut clock_now) } != 0 {
+            return now;
+        }
+        let clock_now = Duration::new(clock_now.tv_sec as _, clock_now.tv_nsec as _);
+        let stamp = event
+            .timestamp()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        match clock_now.checked_sub(stamp) {
+            Some(age) if age <= MAX_EVENT_AGE => now.checked_sub(age).unwrap_or(now),
+            // Stamped on another clock than expected
+            _ => now,
+        }
+    }
 
-            if last_debug.elapsed() > Duration::from_secs(1) {
-                last_debug = std::time::Instant::now();
-                println!("{:?}", self);
+    fn readable(&self, timeout: Duration) -> std::io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: std::os::fd::AsRawFd::as_raw_fd(&self.device),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = timeout.as_millis().min(i32::MAX as u128) as i32;
+        match unsafe { libc::poll(&mut fd, 1, timeout) } {
+            -1 => {
+                let error = std::io::Error::last_os_error();
+                match error.kind() {
+                    std::io::ErrorKind::Interrupted => Ok(true),
+                    _ => Err(error),
+                }
             }
+            ready => Ok(ready > 0),
         }
     }
 
     fn handle_event(&mut self, input_event: evdev::InputEvent) {
-        self.capture.emit(logging::LogEvent::Evdev {
-            time: std::time::Instant::now(),
-            evdev_event: input_event,
-        });
+        // Only reports and captured events need the time, reading the clocks is skipped otherwise
+        if self.capture.active() {
+            if let Some(evdev_event) = self.captured(input_event) {
+                let time = self.event_time(&input_event);
+                self.capture
+                    .emit(logging::LogEvent::Evdev { time, evdev_event });
+            }
+        }
         match (
             input_event.event_type(),
             input_event.kind(),
@@ -533,56 +2619,184 @@ impl MotionMonitor {
             (
                 evdev::EventType::ABSOLUTE,
                 evdev::InputEventKind::AbsAxis(evdev::AbsoluteAxisType::ABS_MT_SLOT),
-                _num,
+                slot,
             ) => {
-                self.ignore_block = true;
+                if let Some(touches) = self.touches.as_mut() {
+                    touches.set_slot(slot);
+                }
             }
             (
                 evdev::EventType::ABSOLUTE,
-                evdev::InputEventKind::AbsAxis(evdev::AbsoluteAxisType::ABS_X),
+                evdev::InputEventKind::AbsAxis(evdev::AbsoluteAxisType::ABS_MT_TRACKING_ID),
+                id,
+            ) => {
+                // A contact taking over starts where the last one ended, that is a jump
+                if let Some(touches) = self.touches.as_mut() {
+                    self.skip_velocity |= touches.tracking_id(id);
+                }
+            }
+            (
+                evdev::EventType::ABSOLUTE,
+                evdev::InputEventKind::AbsAxis(evdev::AbsoluteAxisType::ABS_MT_POSITION_X),
+                val,
+            ) => {
+                if let Some(touches) = self.touches.as_mut() {
+                    touches.set_x(val);
+                }
+            }
+            (
+                evdev::EventType::ABSOLUTE,
+                evdev::InputEventKind::AbsAxis(evdev::AbsoluteAxisType::ABS_MT_POSITION_Y),
+                val,
+            ) => {
+                if let Some(touches) = self.touches.as_mut() {
+                    touches.set_y(val);
+                }
+            }
+            (
+                evdev::EventType::ABSOLUTE,
+                evdev::InputEventKind::AbsAxis(evdev::AbsoluteAxisType::ABS_MT_PRESSURE),
+                val,
+            ) => {
+                if let Some(touches) = self.touches.as_mut() {
+                    touches.set_pressure(val);
+                }
+            }
+            (
+                evdev::EventType::ABSOLUTE,
+                evdev::InputEventKind::AbsAxis(evdev::AbsoluteAxisType::ABS_MT_TOUCH_MAJOR),
                 val,
             ) => {
-                if self.ignore_block {
-                    return;
+                if let Some(touches) = self.touches.as_mut() {
+                    touches.set_touch_major(val);
                 }
+            }
+            // Single touch emulation of multitouch devices is left out, it may
+            // switch contacts on its own
+            (
+                evdev::EventType::ABSOLUTE,
+                evdev::InputEventKind::AbsAxis(evdev::AbsoluteAxisType::ABS_X),
+                val,
+            ) if self.touches.is_none() => {
                 self.working.x = val;
             }
             (
                 evdev::EventType::ABSOLUTE,
                 evdev::InputEventKind::AbsAxis(evdev::AbsoluteAxisType::ABS_Y),
                 val,
+            ) if self.touches.is_none() => {
+                self.working.y = val;
+            }
+            (
+                evdev::EventType::RELATIVE,
+                evdev::InputEventKind::RelAxis(evdev::RelativeAxisType::REL_X),
+                val,
             ) => {
-                if self.ignore_block {
-                    return;
+                self.working.x = self.working.x.wrapping_add(val);
+            }
+            (
+                evdev::EventType::RELATIVE,
+                evdev::InputEventKind::RelAxis(evdev::RelativeAxisType::REL_Y),
+                val,
+            ) => {
+                self.working.y = self.working.y.wrapping_add(val);
+            }
+            (evdev::EventType::KEY, evdev::InputEventKind::Key(evdev::Key::BTN_TOOL_PEN), 0) => {
+                self.proximity_out = Some(self.event_time(&input_event));
+            }
+            (evdev::EventType::KEY, evdev::InputEventKind::Key(evdev::Key::BTN_TOOL_PEN), _) => {
+                // The first position after proximity is no motion
+                self.skip_velocity = true;
+                if self
+                    .proximity_out
+                    .take()
+                    .is_some_and(|out| out.elapsed() >= self.pen_reveal_after)
+                {
+                    self.pending.push_back(motion::Motion::Reappeared);
                 }
-                self.working.y = val;
             }
             (evdev::EventType::SYNCHRONIZATION, _, _) => {
-                if self.ignore_block {
-                    self.ignore_block = false;
-                    return;
+                if let Some(touches) = self.touches.as_mut() {
+                    self.skip_velocity |= touches.sync();
+                    // Nothing moves without a contact
+                    let Some((x, y)) = touches.primary() else {
+                        return;
+                    };
+                    // Motion resumes after the gesture without a velocity across it
+                    if touches.excluded() {
+                        self.skip_velocity = true;
+                        return;
+                    }
+                    (self.working.x, self.working.y) = (x, y);
                 }
-                self.working.time = std::time::Instant::now();
+                self.working.time = self.event_time(&input_event);
+
+                self.pending
+                    .push_back(if std::mem::take(&mut self.skip_velocity) {
+                        motion::Motion::Jump(self.working)
+                    } else {
+                        motion::Motion::Position(self.working)
+                    });
+            }
+            _ => {
+                // Other events are ignored
+            }
+        }
+    }
+}
 
-                let velocity = self.working.velocity(&self.last);
-                self.last = self.working;
+// Events carry realtime stamps by default, which jump with the wall clock
+fn event_clock(device: &evdev::Device) -> libc::clockid_t {
+    // _IOW('E', 0xa0, int)
+    const EVIOCSCLOCKID: libc::c_ulong = 0x400445a0;
+    let clock: libc::c_int = libc::CLOCK_MONOTONIC;
+    let fd = std::os::fd::AsRawFd::as_raw_fd(device);
+    match unsafe { libc::ioctl(fd, EVIOCSCLOCKID as _, &clock) } {
+        0 => libc::CLOCK_MONOTONIC,
+        _ => libc::CLOCK_REALTIME,
+    }
+}
 
-                if velocity > 5000.0 {
-                    // Ignore extreme values
-                    return;
-                }
+impl motion::MotionSource for MotionMonitor {
+    fn next_motion(&mut self) -> std::io::Result<Option<motion::Motion>> {
+        while self.pending.is_empty() {
+            // Waits for events only up to the expiry while the device is moving
+            let timeout = if self.moving {
+                self.motion_expiry
+            } else {
+                IDLE_WAKEUP
+            };
+            if !self.readable(timeout)? {
+                return Ok(std::mem::take(&mut self.moving).then_some(motion::Motion::Stopped));
+            }
 
-                let velocity_event = models::VelocityEvent::new(velocity);
-                self.capture.emit(logging::LogEvent::Velocity {
-                    velocity: velocity_event.velocity(),
-                    time: velocity_event.time(),
-                });
+            // The buffer is kept across reads, reading events allocates nothing once it grew
+            let mut events = std::mem::take(&mut self.events);
+            events.clear();
+            events.extend(self.device.fetch_events()?);
+            events.iter().for_each(|e| self.handle_event(*e));
+            self.events = events;
 
-                *self.last_speed.write().unwrap() = velocity_event;
-            }
-            _ => {
-                // Other events are ignored
+            if self.dumps.due() {
+                logging::dump(format_args!("{:?}", self));
             }
         }
+        let motion = self.pending.pop_front();
+        if let Some(motion::Motion::Position(_)) = motion {
+            self.moving = true;
+        }
+        Ok(motion)
+    }
+
+    fn scale(&self) -> f64 {
+        self.pen_scale.unwrap_or(1.0)
+    }
+
+    fn diagonal(&self) -> Option<f64> {
+        self.diagonal
+    }
+
+    fn class(&self) -> Option<models::DeviceClass> {
+        self.class
     }
 }
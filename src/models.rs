@@ -1,28 +1,356 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Formatter};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Config {
+    pub capture: bool,
     pub capture_seconds: f64,
     pub window_size: i32,
     pub device_name: String,
+    pub exclude_devices: Vec<String>,
+    pub exclude_touchscreens: bool,
     pub decay: f64,
     pub accel: f64,
     pub accel_decay: f64,
     pub accel_inc: f64,
+    pub attack_ms: f64,
+    pub release_ms: f64,
     pub threshold: f64,
+    pub threshold_unit: ThresholdUnit,
+    pub output_sensitivity: std::collections::BTreeMap<String, f64>,
+    pub status_output: Option<String>,
+    pub dry_run: bool,
+    pub theme: String,
+    pub color_mode: ColorMode,
+    pub color_scheme: ColorScheme,
+    pub reduced_motion: ReducedMotion,
+    pub hue_cycle_seconds: f64,
+    pub compass: bool,
+    pub compass_size: i32,
+    pub magnifier: bool,
+    pub magnifier_zoom: f64,
+    pub response_gamma: f64,
+    pub width_curve: WidthCurve,
+    pub min_width: u32,
+    pub max_width: u32,
+    pub dpi_scaling: bool,
+    pub capture_output: Option<String>,
+    pub reveal_screenshots: Option<String>,
+    pub reveal_screenshot_size: u32,
+    pub learn_threshold: LearnMode,
+    pub learn_hours: f64,
+    pub learn_percentile: f64,
+    pub debug_dumps: bool,
+    pub debug_dump_seconds: f64,
+    pub debug_dump_output: Option<String>,
+    pub color: Option<crate::themes::Color>,
+    pub teleport_reveal: bool,
+    pub hide_cursor_seconds: f64,
+    pub pen_reveal: bool,
+    pub pen_reveal_seconds: f64,
+    pub haptic_feedback_ms: u32,
+    pub velocity_window_ms: f64,
+    pub motion_expiry_ms: f64,
+    pub teleport_distance: f64,
+    pub edge_reveal: Vec<ScreenEdge>,
+    pub edge_reveal_velocity: f64,
+    pub gesture_reveal: Vec<Gesture>,
+    pub palm_pressure: f64,
+    pub palm_size: f64,
+    pub hot_corners: std::collections::BTreeMap<ScreenEdge, HotCornerAction>,
+    pub hot_corner_ms: f64,
+    pub pointer_fallback: bool,
+    pub pointer_only: bool,
+    pub pointer_poll_hz: f64,
+    pub exclude_apps: Vec<String>,
+    pub exclude_fullscreen: bool,
+    pub apps: std::collections::BTreeMap<String, toml::Table>,
+    pub schedule: std::collections::BTreeMap<String, toml::Table>,
+    pub devices: std::collections::BTreeMap<DeviceClass, toml::Table>,
+    pub profile: std::collections::BTreeMap<String, toml::Table>,
+    pub active_profile: Option<String>,
+    pub highlight: bool,
+    pub highlight_intensity: f64,
+    pub click_rings: bool,
+    pub shake_lock: bool,
+    pub shake_lock_seconds: f64,
+    pub prediction_ms: f64,
+    pub interpolate_pointer: bool,
+    pub edge_flash: bool,
+    pub edge_flash_width: u32,
+    pub capture_keys: KeyCapture,
+    pub spike_cutoff: f64,
+    pub median_filter: bool,
+    pub smoothing_ms: f64,
+    pub desktop_reveal: bool,
+    pub output_focus_reveal: bool,
+    pub hide_during_grabs: bool,
+    pub hide_during_screencast: bool,
+    pub typing_pause_ms: f64,
+    pub keyboard_name: String,
+    pub mit_shm: bool,
+    pub desktop: Desktop,
+    pub dismiss_hotspot: u32,
+    pub fallback_opacity: f64,
+    pub mqtt_broker: Option<String>,
+    pub mqtt_topic: String,
+    pub http_port: Option<u16>,
+    pub shell_bridge: bool,
+    pub seccomp: SeccompMode,
+    pub refresh_pacing: bool,
+    // Keys set in the config file or changed at runtime, built-in defaults leave them alone
+    #[serde(skip)]
+    pub explicit: std::collections::BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Desktop {
+    Auto,
+    Gnome,
+    Kde,
+    Other,
+}
+
+// Handling of key events in captures, redacting keeps the timing of clicks
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyCapture {
+    Keep,
+    Redact,
+    Drop,
+}
+
+// Kind of the monitored input device, selects a device profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceClass {
+    Touchpad,
+    Trackpoint,
+    Tablet,
+    Mouse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HotCornerAction {
+    Reveal,
+    Highlight,
+}
+
+// Touchpad gestures recognized from the multitouch contacts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Gesture {
+    Scroll,
+    Pinch,
+    EdgeSwipe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScreenEdge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl ScreenEdge {
+    // Whether the pointer is at this edge of the whole screen
+    pub fn contains(&self, pointer: Position32, layout: &ScreenLayout) -> bool {
+        let top = pointer.y <= 0;
+        let bottom = pointer.y >= layout.height - 1;
+        let left = pointer.x <= 0;
+        let right = pointer.x >= layout.width - 1;
+        match self {
+            ScreenEdge::Top => top,
+            ScreenEdge::Bottom => bottom,
+            ScreenEdge::Left => left,
+            ScreenEdge::Right => right,
+            ScreenEdge::TopLeft => top && left,
+            ScreenEdge::TopRight => top && right,
+            ScreenEdge::BottomLeft => bottom && left,
+            ScreenEdge::BottomRight => bottom && right,
+        }
+    }
+}
+
+// Device units per second, or percent of the screen diagonal per second
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThresholdUnit {
+    Device,
+    Screen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LearnMode {
+    Off,
+    Suggest,
+    Apply,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeccompMode {
+    Off,
+    Log,
+    Enforce,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    Theme,
+    Rainbow,
+}
+
+// Mapping of the speed to the border width of the ring
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WidthCurve {
+    Linear,
+    Sqrt,
+    Constant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorScheme {
+    Auto,
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReducedMotion {
+    Auto,
+    On,
+    Off,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::new()
+    }
 }
 
 impl Config {
+    // Setting an output asks for a capture as well
+    pub fn capturing(&self) -> bool {
+        self.capture || self.capture_output.is_some()
+    }
+
+    pub fn motion_expiry(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(self.motion_expiry_ms.max(0.0) / 1000.0)
+    }
+
     pub fn new() -> Config {
         Config {
+            capture: false,
             capture_seconds: 5.0,
             window_size: 200,
             decay: 0.98,
             accel: 1500.0f64,
             accel_decay: 0.1,
             accel_inc: 0.3,
+            attack_ms: 325.0,
+            release_ms: 325.0,
             threshold: 1500.0,
+            threshold_unit: ThresholdUnit::Device,
+            output_sensitivity: std::collections::BTreeMap::new(),
             device_name: String::from("Apple"),
+            exclude_devices: Vec::new(),
+            exclude_touchscreens: true,
+            status_output: None,
+            dry_run: false,
+            theme: String::from("classic"),
+            color_mode: ColorMode::Theme,
+            color_scheme: ColorScheme::Auto,
+            reduced_motion: ReducedMotion::Auto,
+            hue_cycle_seconds: 5.0,
+            compass: false,
+            compass_size: 120,
+            magnifier: false,
+            magnifier_zoom: 2.0,
+            response_gamma: 1.0,
+            width_curve: WidthCurve::Linear,
+            min_width: 1,
+            max_width: 0,
+            dpi_scaling: false,
+            capture_output: None,
+            reveal_screenshots: None,
+            reveal_screenshot_size: 200,
+            learn_threshold: LearnMode::Off,
+            learn_hours: 24.0,
+            learn_percentile: 99.5,
+            debug_dumps: false,
+            debug_dump_seconds: 1.0,
+            debug_dump_output: None,
+            color: None,
+            pen_reveal: true,
+            pen_reveal_seconds: 1.0,
+            haptic_feedback_ms: 0,
+            velocity_window_ms: 0.0,
+            motion_expiry_ms: 250.0,
+            teleport_reveal: false,
+            hide_cursor_seconds: 0.0,
+            teleport_distance: 500.0,
+            edge_reveal: Vec::new(),
+            edge_reveal_velocity: 500.0,
+            gesture_reveal: vec![Gesture::EdgeSwipe],
+            palm_pressure: 0.0,
+            palm_size: 0.0,
+            hot_corners: std::collections::BTreeMap::new(),
+            hot_corner_ms: 300.0,
+            pointer_fallback: true,
+            pointer_only: false,
+            pointer_poll_hz: 60.0,
+            exclude_apps: Vec::new(),
+            exclude_fullscreen: false,
+            apps: std::collections::BTreeMap::new(),
+            schedule: std::collections::BTreeMap::new(),
+            devices: std::collections::BTreeMap::new(),
+            profile: std::collections::BTreeMap::new(),
+            active_profile: None,
+            highlight: false,
+            highlight_intensity: 250.0,
+            click_rings: false,
+            shake_lock: false,
+            shake_lock_seconds: 1.0,
+            prediction_ms: 0.0,
+            interpolate_pointer: false,
+            edge_flash: false,
+            edge_flash_width: 6,
+            capture_keys: KeyCapture::Redact,
+            spike_cutoff: 5000.0,
+            median_filter: true,
+            smoothing_ms: 8.0,
+            desktop_reveal: false,
+            output_focus_reveal: false,
+            hide_during_grabs: true,
+            hide_during_screencast: false,
+            typing_pause_ms: 0.0,
+            keyboard_name: String::new(),
+            mit_shm: true,
+            desktop: Desktop::Auto,
+            dismiss_hotspot: 0,
+            fallback_opacity: 1.0,
+            mqtt_broker: None,
+            mqtt_topic: String::from("mouse-reveal"),
+            http_port: None,
+            shell_bridge: false,
+            seccomp: SeccompMode::Off,
+            refresh_pacing: true,
+            explicit: std::collections::BTreeSet::new(),
         }
     }
 }
@@ -37,12 +365,198 @@ impl Position32 {
     pub fn new(x: i32, y: i32) -> Position32 {
         Position32 { x, y }
     }
+
+    pub fn distance(&self, other: &Position32) -> f64 {
+        ((self.x - other.x) as f64).hypot((self.y - other.y) as f64)
+    }
+}
+
+// Extrapolates the pointer position to hide the latency of sampling it
+pub struct PointerPredictor {
+    last: Option<(Position32, std::time::Instant)>,
+    velocity: (f64, f64),
+}
+
+impl PointerPredictor {
+    pub fn new() -> PointerPredictor {
+        PointerPredictor {
+            last: None,
+            velocity: (0.0, 0.0),
+        }
+    }
+
+    pub fn predict(&mut self, position: Position32, lookahead: std::time::Duration) -> Position32 {
+        let now = std::time::Instant::now();
+        if let Some((last, time)) = self.last {
+            let dt = now.duration_since(time).as_secs_f64();
+            if dt > 0.1 {
+                // Too old to describe the current motion
+                self.velocity = (0.0, 0.0);
+            } else if dt > 0.0 {
+                // Smoothed against the jitter of single samples
+                let vx = (position.x - last.x) as f64 / dt;
+                let vy = (position.y - last.y) as f64 / dt;
+                self.velocity = (
+                    self.velocity.0 * 0.5 + vx * 0.5,
+                    self.velocity.1 * 0.5 + vy * 0.5,
+                );
+            }
+        }
+        self.last = Some((position, now));
+
+        let ahead = lookahead.as_secs_f64();
+        Position32::new(
+            position.x + (self.velocity.0 * ahead).round() as i32,
+            position.y + (self.velocity.1 * ahead).round() as i32,
+        )
+    }
+}
+
+// Moves between the two latest distinct pointer samples instead of snapping to the
+// latest one, frames faster than the pointer updates stop stuttering. Lags one sample.
+pub struct PointerInterpolator {
+    previous: Option<(Position32, std::time::Instant)>,
+    latest: Option<(Position32, std::time::Instant)>,
+}
+
+impl PointerInterpolator {
+    pub fn new() -> PointerInterpolator {
+        PointerInterpolator {
+            previous: None,
+            latest: None,
+        }
+    }
+
+    pub fn interpolate(&mut self, position: Position32) -> Position32 {
+        let now = std::time::Instant::now();
+        if self.latest.map(|(latest, _)| latest) != Some(position) {
+            self.previous = self.latest.replace((position, now));
+        }
+
+        match (self.previous, self.latest) {
+            (Some((from, start)), Some((to, end))) => {
+                let interval = end.duration_since(start).as_secs_f64();
+                // A pause before the latest sample is no motion to spread out
+                if interval <= 0.0 || interval > 0.1 {
+                    return to;
+                }
+                let t = (now.duration_since(end).as_secs_f64() / interval).min(1.0);
+                Position32::new(
+                    from.x + ((to.x - from.x) as f64 * t).round() as i32,
+                    from.y + ((to.y - from.y) as f64 * t).round() as i32,
+                )
+            }
+            _ => position,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowClass {
+    pub instance: String,
+    pub class: String,
+}
+
+impl WindowClass {
+    // Matches either the instance or class name, ignoring case
+    pub fn matches(&self, name: &str) -> bool {
+        self.instance.eq_ignore_ascii_case(name) || self.class.eq_ignore_ascii_case(name)
+    }
+
+    pub fn matches_any(&self, names: &[String]) -> bool {
+        names.iter().any(|name| self.matches(name))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    // RandR names monitors after their output, e.g. "HDMI-1"
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub width_mm: u32,
+    pub height_mm: u32,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    Normal,
+    Left,
+    Inverted,
+    Right,
+}
+
+impl Monitor {
+    pub fn contains(&self, pos: Position32) -> bool {
+        pos.x >= self.x
+            && pos.y >= self.y
+            && pos.x < self.x + self.width
+            && pos.y < self.y + self.height
+    }
+
+    pub fn center(&self) -> Position32 {
+        Position32::new(self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    // Relative to the 96 DPI X11 assumes, 1.0 when the physical size is unknown.
+    // Uses the diagonal as drivers don't agree on swapping the physical size
+    // of rotated outputs, while the pixel size always is.
+    pub fn dpi_scale(&self) -> f64 {
+        let diagonal_mm = (self.width_mm as f64).hypot(self.height_mm as f64);
+        if diagonal_mm == 0.0 {
+            return 1.0;
+        }
+        let diagonal_px = (self.width as f64).hypot(self.height as f64);
+        let dpi = diagonal_px / (diagonal_mm / 25.4);
+        (dpi / 96.0).max(0.5)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScreenLayout {
+    pub width: i32,
+    pub height: i32,
+    pub monitors: Vec<Monitor>,
+}
+
+impl ScreenLayout {
+    // Pointer positions are in pixels, sources without a diagonal of their own use this one
+    pub fn diagonal(&self) -> Option<f64> {
+        let diagonal = (self.width as f64).hypot(self.height as f64);
+        (diagonal > 0.0).then_some(diagonal)
+    }
+
+    pub fn monitor_at(&self, pos: Position32) -> Option<&Monitor> {
+        self.monitors.iter().find(|m| m.contains(pos))
+    }
+
+    pub fn primary(&self) -> Option<&Monitor> {
+        self.monitors
+            .iter()
+            .find(|m| m.primary)
+            .or(self.monitors.first())
+    }
+
+    pub fn clamp(&self, pos: Position32) -> Position32 {
+        Position32::new(
+            pos.x.clamp(0, (self.width - 1).max(0)),
+            pos.y.clamp(0, (self.height - 1).max(0)),
+        )
+    }
 }
 
 #[derive(Clone, Copy)]
 pub struct VelocityEvent {
     velocity: f64,
     time: std::time::Instant,
+    diagonal: Option<f64>,
+    class: Option<DeviceClass>,
+    // When the source last reappeared, carried by all its later events
+    reappeared: Option<std::time::Instant>,
+    stopped: bool,
 }
 
 impl Debug for VelocityEvent {
@@ -58,9 +572,53 @@ impl VelocityEvent {
         VelocityEvent {
             velocity,
             time: std::time::Instant::now(),
+            diagonal: None,
+            class: None,
+            reappeared: None,
+            stopped: false,
         }
     }
 
+    // The source reported no motion for the motion expiry
+    pub fn motion_stopped() -> VelocityEvent {
+        VelocityEvent {
+            stopped: true,
+            ..VelocityEvent::new(0.0)
+        }
+    }
+
+    // A pen entering proximity again, its position jumped somewhere unknown
+    pub fn reappearance() -> VelocityEvent {
+        let event = VelocityEvent::new(0.0);
+        event.with_reappearance(Some(event.time))
+    }
+
+    // Later motion would overwrite the reappearance event before it is read otherwise
+    pub fn with_reappearance(self, reappeared: Option<std::time::Instant>) -> VelocityEvent {
+        VelocityEvent { reappeared, ..self }
+    }
+
+    pub fn reappeared(&self) -> Option<std::time::Instant> {
+        self.reappeared
+    }
+
+    // Diagonal of the source in the units of the velocity, e.g. from the axis ranges
+    pub fn with_diagonal(self, diagonal: Option<f64>) -> VelocityEvent {
+        VelocityEvent { diagonal, ..self }
+    }
+
+    pub fn diagonal(&self) -> Option<f64> {
+        self.diagonal
+    }
+
+    pub fn with_class(self, class: Option<DeviceClass>) -> VelocityEvent {
+        VelocityEvent { class, ..self }
+    }
+
+    pub fn class(&self) -> Option<DeviceClass> {
+        self.class
+    }
+
     pub fn velocity(&self) -> f64 {
         self.velocity
     }
@@ -69,8 +627,164 @@ impl VelocityEvent {
         self.time
     }
 
-    pub fn expired(&self) -> bool {
-        self.time.elapsed() > std::time::Duration::from_millis(250)
+    // Stale events expire as well, not every source reports when the motion stopped
+    pub fn expired(&self, expiry: std::time::Duration) -> bool {
+        self.stopped || self.time.elapsed() > expiry
+    }
+}
+
+const HISTORY_LEN: usize = 256;
+
+// Recent velocities, written by the motion thread and read without locking by the
+// UI loop. Every slot is a seqlock, readers retry while the writer is updating it.
+pub struct VelocityHistory {
+    epoch: std::time::Instant,
+    head: std::sync::atomic::AtomicUsize,
+    slots: Vec<HistorySlot>,
+}
+
+#[derive(Default)]
+struct HistorySlot {
+    seq: std::sync::atomic::AtomicU64,
+    velocity: std::sync::atomic::AtomicU64,
+    nanos: std::sync::atomic::AtomicU64,
+    diagonal: std::sync::atomic::AtomicU64,
+    // Nanoseconds after the epoch plus one, zero when the source never reappeared
+    reappeared: std::sync::atomic::AtomicU64,
+    flags: std::sync::atomic::AtomicU64,
+}
+
+const CLASSES: [DeviceClass; 4] = [
+    DeviceClass::Touchpad,
+    DeviceClass::Trackpoint,
+    DeviceClass::Tablet,
+    DeviceClass::Mouse,
+];
+const STOPPED: u64 = 1 << 8;
+
+impl VelocityHistory {
+    pub fn new() -> VelocityHistory {
+        VelocityHistory {
+            epoch: std::time::Instant::now(),
+            head: std::sync::atomic::AtomicUsize::new(0),
+            slots: (0..HISTORY_LEN).map(|_| HistorySlot::default()).collect(),
+        }
+    }
+
+    // Only one thread may push
+    pub fn push(&self, event: VelocityEvent) {
+        use std::sync::atomic::Ordering;
+        let head = self.head.load(Ordering::Relaxed);
+        let slot = &self.slots[head % HISTORY_LEN];
+
+        let seq = slot.seq.load(Ordering::Relaxed);
+        slot.seq.store(seq + 1, Ordering::Relaxed);
+        std::sync::atomic::fence(Ordering::Release);
+
+        let class = event
+            .class
+            .and_then(|class| CLASSES.iter().position(|c| *c == class))
+            .map_or(0, |index| index as u64 + 1);
+        let nanos =
+            |time: std::time::Instant| time.saturating_duration_since(self.epoch).as_nanos() as u64;
+        slot.velocity
+            .store(event.velocity.to_bits(), Ordering::Relaxed);
+        slot.nanos.store(nanos(event.time), Ordering::Relaxed);
+        slot.diagonal.store(
+            event.diagonal.unwrap_or(f64::NAN).to_bits(),
+            Ordering::Relaxed,
+        );
+        slot.reappeared.store(
+            event.reappeared.map_or(0, |time| nanos(time) + 1),
+            Ordering::Relaxed,
+        );
+        slot.flags.store(
+            class | if event.stopped { STOPPED } else { 0 },
+            Ordering::Relaxed,
+        );
+
+        slot.seq.store(seq + 2, Ordering::Release);
+        self.head.store(head + 1, Ordering::Release);
+    }
+
+    fn read(&self, index: usize) -> Option<VelocityEvent> {
+        use std::sync::atomic::Ordering;
+        let slot = &self.slots[index % HISTORY_LEN];
+        loop {
+            let seq = slot.seq.load(Ordering::Acquire);
+            if seq == 0 {
+                return None;
+            }
+            if seq % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+
+            let velocity = f64::from_bits(slot.velocity.load(Ordering::Relaxed));
+            let nanos = slot.nanos.load(Ordering::Relaxed);
+            let diagonal = f64::from_bits(slot.diagonal.load(Ordering::Relaxed));
+            let reappeared = slot.reappeared.load(Ordering::Relaxed);
+            let flags = slot.flags.load(Ordering::Relaxed);
+            std::sync::atomic::fence(Ordering::Acquire);
+            if slot.seq.load(Ordering::Relaxed) != seq {
+                continue;
+            }
+
+            return Some(VelocityEvent {
+                velocity,
+                time: self.epoch + std::time::Duration::from_nanos(nanos),
+                diagonal: (!diagonal.is_nan()).then_some(diagonal),
+                class: CLASSES
+                    .get(((flags & 0xff) as usize).wrapping_sub(1))
+                    .copied(),
+                reappeared: reappeared
+                    .checked_sub(1)
+                    .map(|nanos| self.epoch + std::time::Duration::from_nanos(nanos)),
+                stopped: flags & STOPPED != 0,
+            });
+        }
+    }
+
+    pub fn latest(&self) -> VelocityEvent {
+        let head = self.head.load(std::sync::atomic::Ordering::Acquire);
+        head.checked_sub(1)
+            .and_then(|index| self.read(index))
+            .unwrap_or(VelocityEvent {
+                time: self.epoch,
+                ..VelocityEvent::new(0.0)
+            })
+    }
+
+    // Visits the events newer than the given time, newest first
+    pub fn for_each_since(&self, since: std::time::Instant, mut f: impl FnMut(VelocityEvent)) {
+        let head = self.head.load(std::sync::atomic::Ordering::Acquire);
+        // The oldest slots may be overwritten while reading, those are skipped
+        for index in (head.saturating_sub(HISTORY_LEN - 1)..head).rev() {
+            match self.read(index) {
+                Some(event) if event.time > since => f(event),
+                _ => break,
+            }
+        }
+    }
+
+    // Average velocity of the motion within the window, zero without any
+    pub fn mean(&self, window: std::time::Duration) -> f64 {
+        let since = std::time::Instant::now()
+            .checked_sub(window)
+            .unwrap_or(self.epoch);
+        let (mut sum, mut count) = (0.0, 0);
+        self.for_each_since(since, |event| {
+            // The reappearance itself is no motion
+            if event.reappeared != Some(event.time) {
+                sum += event.velocity;
+                count += 1;
+            }
+        });
+        if count == 0 {
+            0.0
+        } else {
+            sum / count as f64
+        }
     }
 }
 
@@ -90,11 +804,105 @@ impl Debug for PointerInputEvent {
     }
 }
 
+// Coalesced reports can share a timestamp, even 8 kHz mice report slower than this
+const MIN_EVENT_DELTA: f64 = 0.0001;
+
 impl PointerInputEvent {
     pub fn velocity(&self, previous: &PointerInputEvent) -> f64 {
-        let delta = (self.time - previous.time).as_secs_f64();
+        let delta = self
+            .time
+            .saturating_duration_since(previous.time)
+            .as_secs_f64()
+            .max(MIN_EVENT_DELTA);
         let w = ((self.x as f64) - (previous.x as f64)) / delta;
         let h = ((self.y as f64) - (previous.y as f64)) / delta;
         (w * h).abs().sqrt()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn history_keeps_the_newest_events_after_wrapping() {
+        let history = VelocityHistory::new();
+        let start = std::time::Instant::now();
+        let events = HISTORY_LEN * 2 + 10;
+        for i in 0..events {
+            history.push(VelocityEvent {
+                time: start + Duration::from_micros(i as u64),
+                ..VelocityEvent::new(i as f64)
+            });
+        }
+        assert_eq!(history.latest().velocity(), (events - 1) as f64);
+
+        let mut visited = Vec::new();
+        history.for_each_since(history.epoch, |event| visited.push(event.velocity()));
+        let expected: Vec<f64> = (events - (HISTORY_LEN - 1)..events)
+            .rev()
+            .map(|i| i as f64)
+            .collect();
+        assert_eq!(visited, expected);
+    }
+
+    #[test]
+    fn history_reads_whole_events_while_writing() {
+        let history = std::sync::Arc::new(VelocityHistory::new());
+        let writer = {
+            let history = std::sync::Arc::clone(&history);
+            std::thread::spawn(move || {
+                for i in 1..=200_000 {
+                    history.push(VelocityEvent::new(i as f64).with_diagonal(Some(i as f64)));
+                }
+            })
+        };
+
+        let mut last = 0.0;
+        while !writer.is_finished() {
+            let event = history.latest();
+            // Torn reads would mix the fields of two events
+            if event.velocity() > 0.0 {
+                assert_eq!(event.diagonal(), Some(event.velocity()));
+            }
+            assert!(event.velocity() >= last);
+            last = event.velocity();
+            history.for_each_since(history.epoch, |event| {
+                assert_eq!(event.diagonal(), Some(event.velocity()));
+            });
+        }
+        writer.join().unwrap();
+        assert_eq!(history.latest().velocity(), 200_000.0);
+    }
+
+    #[test]
+    fn history_round_trips_every_field() {
+        let history = VelocityHistory::new();
+        let reappeared = std::time::Instant::now();
+        let event = VelocityEvent {
+            time: reappeared + Duration::from_millis(3),
+            stopped: true,
+            ..VelocityEvent::new(42.5)
+        }
+        .with_diagonal(Some(1000.0))
+        .with_class(Some(DeviceClass::Tablet))
+        .with_reappearance(Some(reappeared));
+        history.push(event);
+
+        let read = history.latest();
+        assert_eq!(read.velocity(), 42.5);
+        assert_eq!(read.time(), event.time());
+        assert_eq!(read.diagonal(), Some(1000.0));
+        assert_eq!(read.class(), Some(DeviceClass::Tablet));
+        assert_eq!(read.reappeared(), Some(reappeared));
+        assert!(read.stopped);
+
+        history.push(VelocityEvent::reappearance());
+        let read = history.latest();
+        assert_eq!(read.reappeared(), Some(read.time()));
+        assert_eq!(read.diagonal(), None);
+        assert_eq!(read.class(), None);
+        assert!(!read.stopped);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::detection::{RateSmoother, SpikeFilter};
+use crate::logging::{CaptureEmitter, LogEvent};
+use crate::models::{DeviceClass, PointerInputEvent, VelocityEvent, VelocityHistory};
+use crate::shutdown::Shutdown;
+use std::sync::Arc;
+
+pub enum Motion {
+    // The pointer moved to a position, in the units of the source
+    Position(PointerInputEvent),
+    // The pointer jumped, velocities continue from this position
+    Jump(PointerInputEvent),
+    // A pen came back into proximity somewhere unknown
+    Reappeared,
+    // No motion was reported for the motion expiry
+    Stopped,
+}
+
+// Contract of the motion thread. Evdev devices and the X pointer position hand
+// their motion to a MotionTracker, replays or synthetic sources can do the same.
+pub trait MotionSource {
+    // Waits for the next motion, None when the source was idle and Err once it is gone.
+    // Sources return idle now and then, the tracker checks for a shutdown in between.
+    fn next_motion(&mut self) -> std::io::Result<Option<Motion>>;
+
+    // Factor from distances of the source to velocity units
+    fn scale(&self) -> f64 {
+        1.0
+    }
+
+    // Diagonal of the screen in velocity units, see threshold_unit
+    fn diagonal(&self) -> Option<f64> {
+        None
+    }
+
+    fn class(&self) -> Option<DeviceClass> {
+        None
+    }
+}
+
+// Turns the motion of a source into the velocities read by the UI loop
+pub struct MotionTracker {
+    capture: CaptureEmitter,
+    history: Arc<VelocityHistory>,
+    spike_filter: Option<SpikeFilter>,
+    smoother: Option<RateSmoother>,
+    last: Option<PointerInputEvent>,
+    reappeared: Option<std::time::Instant>,
+    shutdown: Shutdown,
+}
+
+impl MotionTracker {
+    pub fn new(
+        capture: CaptureEmitter,
+        history: Arc<VelocityHistory>,
+        spike_filter: Option<SpikeFilter>,
+        smoother: Option<RateSmoother>,
+        shutdown: Shutdown,
+    ) -> MotionTracker {
+        MotionTracker {
+            capture,
+            history,
+            spike_filter,
+            smoother,
+            last: None,
+            reappeared: None,
+            shutdown,
+        }
+    }
+
+    // Tracks the source until it is gone, the deadline passed or on shutdown
+    pub fn run(
+        &mut self,
+        source: &mut dyn MotionSource,
+        until: Option<std::time::Instant>,
+    ) -> std::io::Result<()> {
+        loop {
+            if self.shutdown.requested() {
+                return Ok(());
+            }
+            if until.is_some_and(|until| std::time::Instant::now() >= until) {
+                // Don't compute a velocity across the gap until the next run
+                self.last = None;
+                if let Some(smoother) = self.smoother.as_mut() {
+                    smoother.reset();
+                }
+                return Ok(());
+            }
+
+            match source.next_motion()? {
+                Some(Motion::Position(position)) => self.track(&*source, position),
+                Some(Motion::Jump(position)) => self.last = Some(position),
+                Some(Motion::Reappeared) => {
+                    let reappearance = VelocityEvent::reappearance();
+                    self.reappeared = reappearance.reappeared();
+                    self.history.push(reappearance);
+                }
+                Some(Motion::Stopped) => {
+                    if let Some(smoother) = self.smoother.as_mut() {
+                        smoother.reset();
+                    }
+                    self.history.push(
+                        VelocityEvent::motion_stopped()
+                            .with_diagonal(source.diagonal())
+                            .with_class(source.class())
+                            .with_reappearance(self.reappeared),
+                    );
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn track(&mut self, source: &dyn MotionSource, position: PointerInputEvent) {
+        let last = match self.last.replace(position) {
+            Some(last) => last,
+            None => return,
+        };
+
+        let velocity = position.velocity(&last) * source.scale();
+        let velocity = match self.spike_filter.as_mut() {
+            Some(filter) => match filter.filter(velocity) {
+                Some(velocity) => velocity,
+                None => return,
+            },
+            None => velocity,
+        };
+        let velocity = match self.smoother.as_mut() {
+            Some(smoother) => smoother.smooth(velocity, position.time),
+            None => velocity,
+        };
+
+        let velocity_event = VelocityEvent::new(velocity)
+            .with_diagonal(source.diagonal())
+            .with_class(source.class())
+            .with_reappearance(self.reappeared);
+        self.capture.emit(LogEvent::Velocity {
+            velocity: velocity_event.velocity(),
+            time: velocity_event.time(),
+        });
+        self.history.push(velocity_event);
+    }
+}
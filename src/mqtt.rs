@@ -0,0 +1,74 @@
+use crate::ipc::Request;
+use crate::status::OverlayEvent;
+
+// Publishes reveal/hide on <topic>/event and runs commands received on <topic>/control,
+// replying on <topic>/reply.
+pub fn start_mqtt_thread(
+    broker: &str,
+    topic: &str,
+    events: std::sync::mpsc::Receiver<OverlayEvent>,
+    ipc: std::sync::mpsc::Sender<Request>,
+) {
+    let (host, port) = match broker.rsplit_once(':') {
+        Some((host, port)) => match port.parse() {
+            Ok(port) => (host.to_string(), port),
+            Err(_) => {
+                eprintln!("Invalid MQTT broker \"{}\", expected host:port", broker);
+                return;
+            }
+        },
+        None => (broker.to_string(), 1883),
+    };
+
+    // Brokers disconnect the older of two clients with the same id
+    let client_id = format!("mouse-reveal-{}-{}", hostname(), std::process::id());
+    let mut options = rumqttc::MqttOptions::new(client_id, host, port);
+    options.set_keep_alive(std::time::Duration::from_secs(30));
+    let (client, mut connection) = rumqttc::Client::new(options, 16);
+
+    let control = format!("{}/control", topic);
+
+    let event_topic = format!("{}/event", topic);
+    let publisher = client.clone();
+    std::thread::spawn(move || {
+        for event in events {
+            let payload = match event {
+                OverlayEvent::Reveal => "reveal",
+                OverlayEvent::Hide => "hide",
+            };
+            // Events are dropped while the broker is unreachable
+            let _ = publisher.try_publish(&event_topic, rumqttc::QoS::AtMostOnce, false, payload);
+        }
+    });
+
+    let reply_topic = format!("{}/reply", topic);
+    std::thread::spawn(move || {
+        for notification in connection.iter() {
+            match notification {
+                // Clean sessions forget the subscription, each reconnect subscribes again
+                Ok(rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_))) => {
+                    if let Err(e) = client.try_subscribe(&control, rumqttc::QoS::AtMostOnce) {
+                        eprintln!("Unable to subscribe to {}: {}", control, e);
+                    }
+                }
+                Ok(rumqttc::Event::Incoming(rumqttc::Packet::Publish(publish))) => {
+                    let line = String::from_utf8_lossy(&publish.payload);
+                    let reply = crate::ipc::request(&ipc, line.trim());
+                    let _ =
+                        client.try_publish(&reply_topic, rumqttc::QoS::AtMostOnce, false, reply);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("MQTT connection error: {}", e);
+                    std::thread::sleep(std::time::Duration::from_secs(5));
+                }
+            }
+        }
+    });
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
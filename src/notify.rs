@@ -0,0 +1,22 @@
+// Startup problems are usually invisible when launched from an autostart
+// file, so they are raised as desktop notifications as well.
+pub fn error(summary: &str, body: &str) {
+    eprintln!("{}: {}", summary, body);
+
+    let result = notify_rust::Notification::new()
+        .appname("Mouse Reveal")
+        .summary(summary)
+        .body(body)
+        .icon("dgs-mouse-reveal")
+        .urgency(notify_rust::Urgency::Critical)
+        .show();
+
+    if let Err(e) = result {
+        eprintln!("Unable to send desktop notification: {}", e);
+    }
+}
+
+pub fn fatal(summary: &str, body: &str) -> ! {
+    error(summary, body);
+    std::process::exit(1);
+}
@@ -0,0 +1,76 @@
+// Checks of a running overlay through XTest, shared by the self-test and
+// tests/nested_x.rs. Only depends on xcb, the tests include the file by path.
+
+use std::time::{Duration, Instant};
+use xcb::BaseEvent;
+
+// Override-redirect children of the root with whether they are mapped
+pub fn overlays(conn: &xcb::Connection, root: xcb::x::Window) -> Vec<(xcb::x::Window, bool)> {
+    let tree = match conn.wait_for_reply(conn.send_request(&xcb::x::QueryTree { window: root })) {
+        Ok(tree) => tree,
+        Err(_) => return Vec::new(),
+    };
+    tree.children()
+        .iter()
+        .filter_map(|window| {
+            let attributes = conn
+                .wait_for_reply(conn.send_request(&xcb::x::GetWindowAttributes { window: *window }))
+                .ok()?;
+            let mapped = attributes.map_state() == xcb::x::MapState::Viewable;
+            attributes.override_redirect().then_some((*window, mapped))
+        })
+        .collect()
+}
+
+pub fn wait_for(timeout: Duration, mut condition: impl FnMut() -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if condition() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+pub fn warp(conn: &xcb::Connection, root: xcb::x::Window, x: i32, y: i32) {
+    conn.send_request(&xcb::xtest::FakeInput {
+        r#type: xcb::x::MotionNotifyEvent::NUMBER as u8,
+        detail: 0,
+        time: xcb::x::CURRENT_TIME,
+        root,
+        root_x: x as i16,
+        root_y: y as i16,
+        deviceid: 0,
+    });
+    let _ = conn.flush();
+}
+
+// Moves left and right around the start position, far faster than any threshold
+pub fn shake(conn: &xcb::Connection, root: xcb::x::Window, (x, y): (i32, i32), duration: Duration) {
+    let start = Instant::now();
+    let mut offset = 300;
+    while start.elapsed() < duration {
+        warp(conn, root, x + offset, y);
+        offset = -offset;
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+pub fn drawn(conn: &xcb::Connection, window: xcb::x::Window) -> bool {
+    let geometry = conn.wait_for_reply(conn.send_request(&xcb::x::GetGeometry {
+        drawable: xcb::x::Drawable::Window(window),
+    }));
+    let image = geometry.and_then(|geometry| {
+        conn.wait_for_reply(conn.send_request(&xcb::x::GetImage {
+            format: xcb::x::ImageFormat::ZPixmap,
+            drawable: xcb::x::Drawable::Window(window),
+            x: 0,
+            y: 0,
+            width: geometry.width(),
+            height: geometry.height(),
+            plane_mask: u32::MAX,
+        }))
+    });
+    image.is_ok_and(|image| image.data().iter().any(|byte| *byte != 0))
+}
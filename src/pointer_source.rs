@@ -0,0 +1,97 @@
+use crate::logging::{CaptureEmitter, LogEvent};
+use crate::models::PointerInputEvent;
+use crate::motion::{Motion, MotionSource};
+use crate::ConnExt;
+
+// Degraded motion source for when evdev devices are not accessible,
+// samples the X pointer position instead of reading device events.
+// The portals are no alternative inside a sandbox: RemoteDesktop only sends input,
+// and InputCapture only delivers events while it holds the pointer, which then
+// stops moving on screen.
+pub struct PointerPoller {
+    conn: xcb::Connection,
+    root: xcb::x::Window,
+    interval: std::time::Duration,
+    capture: CaptureEmitter,
+    last: Option<PointerInputEvent>,
+    diagonal: Option<f64>,
+    motion_expiry: std::time::Duration,
+    moving: bool,
+}
+
+impl PointerPoller {
+    pub fn connect(
+        rate_hz: f64,
+        motion_expiry: std::time::Duration,
+        capture: CaptureEmitter,
+    ) -> Option<PointerPoller> {
+        let (conn, screen_num) = match xcb::Connection::connect(None) {
+            Ok(conn) => conn,
+            Err(e) => {
+                eprintln!(
+                    "Unable to connect to the X server for pointer polling: {}",
+                    e
+                );
+                return None;
+            }
+        };
+        let root = conn.get_setup().roots().nth(screen_num as _)?.root();
+        let diagonal = conn
+            .get_layout(root)
+            .primary()
+            .map(|monitor| (monitor.width as f64).hypot(monitor.height as f64));
+
+        Some(PointerPoller {
+            conn,
+            root,
+            interval: std::time::Duration::from_secs_f64(1.0 / rate_hz.max(1.0)),
+            capture,
+            last: None,
+            diagonal,
+            motion_expiry,
+            moving: false,
+        })
+    }
+}
+
+impl MotionSource for PointerPoller {
+    fn next_motion(&mut self) -> std::io::Result<Option<Motion>> {
+        std::thread::sleep(self.interval);
+
+        let (position, _) = self
+            .conn
+            .get_pointer(self.root)
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        let current = PointerInputEvent {
+            x: position.x,
+            y: position.y,
+            time: std::time::Instant::now(),
+        };
+        self.capture.emit(LogEvent::PointerInput {
+            time: current.time,
+            x: current.x,
+            y: current.y,
+        });
+
+        // Stationary samples would keep the last velocity alive, the last moving
+        // sample is kept to tell how long the pointer rested
+        match self.last {
+            Some(last) if last.x == current.x && last.y == current.y => {
+                if self.moving && last.time.elapsed() >= self.motion_expiry {
+                    self.moving = false;
+                    return Ok(Some(Motion::Stopped));
+                }
+                Ok(None)
+            }
+            _ => {
+                self.moving = self.last.is_some();
+                self.last = Some(current);
+                Ok(Some(Motion::Position(current)))
+            }
+        }
+    }
+
+    fn diagonal(&self) -> Option<f64> {
+        self.diagonal
+    }
+}
@@ -0,0 +1,84 @@
+use crate::logging::{BinaryWriter, CaptureEmitter, CaptureFormat, CaptureRecord};
+use crate::models::{Config, VelocityHistory};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+
+// Records of a capture in progress, binary ones are streamed to the file
+enum Sink {
+    Binary(BinaryWriter<std::io::BufWriter<std::fs::File>>),
+    Json(Vec<CaptureRecord>),
+}
+
+// Records the monitored device like the startup capture, for as long as asked or
+// until interrupted, without starting the overlay
+pub fn run(config: Config, seconds: f64, output: &std::path::Path) -> std::io::Result<()> {
+    let mut sink = match CaptureFormat::of(output) {
+        CaptureFormat::Bin => Sink::Binary(BinaryWriter::new(std::io::BufWriter::new(
+            std::fs::File::create(output)?,
+        ))?),
+        CaptureFormat::Json => Sink::Json(Vec::new()),
+    };
+
+    let shutdown = crate::shutdown::Shutdown::install();
+    let duration = Duration::from_secs_f64(seconds.max(0.0));
+    let start = Instant::now();
+    let (tx, rx) = std::sync::mpsc::sync_channel(crate::logging::CAPTURE_QUEUE);
+    let capture = CaptureEmitter::new(start, duration, tx);
+    let stats = capture.stats();
+    crate::start_motion_thread(
+        config,
+        capture,
+        std::sync::Arc::new(VelocityHistory::new()),
+        crate::haptics::Haptics::default(),
+        shutdown.clone(),
+    );
+    eprintln!(
+        "Recording to {} for {:.0}s, interrupt to stop earlier",
+        output.display(),
+        duration.as_secs_f64()
+    );
+
+    let mut count = 0;
+    while start.elapsed() < duration && !shutdown.requested() {
+        let event = match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let record = CaptureRecord::new(&event, start);
+        match &mut sink {
+            Sink::Binary(writer) => writer.write(&record)?,
+            Sink::Json(records) => records.push(record),
+        }
+        count += 1;
+    }
+
+    stats.report_dropped();
+    match sink {
+        Sink::Binary(writer) => writer.finish()?,
+        Sink::Json(records) => crate::logging::write_records(output, &records)?,
+    }
+    eprintln!("{} records written to {}", count, output.display());
+    Ok(())
+}
+
+// Converts a capture to the other format, next to it unless an output is given
+pub fn convert(
+    capture: &std::path::Path,
+    format: CaptureFormat,
+    output: Option<&std::path::Path>,
+) -> std::io::Result<std::path::PathBuf> {
+    let records = crate::logging::read_records(capture)?;
+    let output = output.map_or_else(
+        || capture.with_extension(format.extension()),
+        std::path::Path::to_path_buf,
+    );
+    if output == capture {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            "the output would replace the capture",
+        ));
+    }
+    crate::logging::write_records_as(&output, &records, format)?;
+    Ok(output)
+}
@@ -0,0 +1,132 @@
+use crate::models::Config;
+use crate::ConnExt;
+
+// Everything a bug report needs to reproduce the setup, logged once at startup
+pub fn startup(config: &Config, conn: &xcb::Connection, screen_num: i32, depth: u8) {
+    let screen = match conn.get_setup().roots().nth(screen_num as _) {
+        Some(screen) => screen,
+        None => return,
+    };
+    let layout = conn.get_layout(screen.root());
+    let rotations = conn.get_rotations(screen.root());
+
+    eprintln!("Startup report:");
+    eprintln!("  version: {}", env!("CARGO_PKG_VERSION"));
+    eprintln!("  features: {}", features());
+    eprintln!(
+        "  screen {}: {}x{}, root depth {}, overlay depth {}",
+        screen_num,
+        screen.width_in_pixels(),
+        screen.height_in_pixels(),
+        screen.root_depth(),
+        depth
+    );
+    for monitor in &layout.monitors {
+        eprintln!(
+            "  monitor {}: {}x{}+{}+{}, {}x{} mm, {:?}{}",
+            monitor.name,
+            monitor.width,
+            monitor.height,
+            monitor.x,
+            monitor.y,
+            monitor.width_mm,
+            monitor.height_mm,
+            rotations
+                .get(&monitor.name)
+                .copied()
+                .unwrap_or(crate::models::Rotation::Normal),
+            if monitor.primary { ", primary" } else { "" }
+        );
+    }
+    eprintln!(
+        "  compositor: {}",
+        if crate::wm::compositor(conn, screen_num) {
+            "running"
+        } else {
+            "none, the overlay is drawn without transparency"
+        }
+    );
+    eprintln!(
+        "  extensions: {}",
+        conn.active_extensions()
+            .map(|extension| format!("{:?}", extension))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    eprintln!("  motion: {}", motion_backend(config));
+    if !config.pointer_only {
+        devices(config);
+    }
+    eprintln!(
+        "  status output: {}",
+        config.status_output.as_deref().unwrap_or("none")
+    );
+
+    eprintln!("  state: {}", crate::state::state_path().display());
+    eprintln!("  config: {}", crate::config::config_path().display());
+    for (key, value) in crate::config::changed_values(config) {
+        eprintln!("    {} = {}", key, value);
+    }
+}
+
+fn features() -> String {
+    let features = [
+        ("gui", cfg!(feature = "gui")),
+        ("mqtt", cfg!(feature = "mqtt")),
+        ("http", cfg!(feature = "http")),
+        ("gnome-shell", cfg!(feature = "gnome-shell")),
+        ("static", cfg!(feature = "static")),
+    ];
+    let enabled: Vec<_> = features
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+    if enabled.is_empty() {
+        String::from("none")
+    } else {
+        enabled.join(", ")
+    }
+}
+
+fn motion_backend(config: &Config) -> String {
+    if config.pointer_only {
+        format!("pointer polling at {} Hz", config.pointer_poll_hz)
+    } else if config.pointer_fallback || crate::desktop::sandboxed() {
+        format!(
+            "evdev, falling back to pointer polling at {} Hz",
+            config.pointer_poll_hz
+        )
+    } else {
+        String::from("evdev")
+    }
+}
+
+fn devices(config: &Config) {
+    let filter = crate::DeviceFilter::new(config);
+    let mut found = false;
+    for (path, device) in evdev::enumerate() {
+        if !filter.matches(&device) {
+            continue;
+        }
+        let axes = |absolute| {
+            if absolute {
+                "absolute axes"
+            } else {
+                "relative axes"
+            }
+        };
+        eprintln!(
+            "  device{}: {} at {}, {:?}, {}",
+            if found { "" } else { " (selected)" },
+            device.name().unwrap_or("(unknown)"),
+            path.display(),
+            crate::device_class(&device),
+            axes(device.supported_absolute_axes().is_some())
+        );
+        found = true;
+    }
+    if !found {
+        eprintln!("  device: none matching or accessible");
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// The portal hands screen casts to PipeWire as video source nodes. Unlike webcams
+// those nodes are not backed by a device, which is what tells them apart.
+pub fn start_screencast_thread() -> std::sync::Arc<AtomicBool> {
+    let active = std::sync::Arc::new(AtomicBool::new(false));
+    let shared = std::sync::Arc::clone(&active);
+
+    std::thread::spawn(move || loop {
+        match casting() {
+            Ok(casting) => shared.store(casting, Ordering::Relaxed),
+            Err(e) => {
+                eprintln!("Unable to detect screen casts, pw-dump failed: {}", e);
+                return;
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_secs(2));
+    });
+
+    active
+}
+
+fn casting() -> std::io::Result<bool> {
+    let output = std::process::Command::new("pw-dump")
+        .stderr(std::process::Stdio::null())
+        .output()?;
+    let objects: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    Ok(objects.iter().any(|object| {
+        let info = &object["info"];
+        let props = &info["props"];
+        object["type"] == "PipeWire:Interface:Node"
+            && props["media.class"] == "Video/Source"
+            && props.get("device.api").is_none()
+            && info["state"] == "running"
+    }))
+}
@@ -0,0 +1,114 @@
+use crate::models::{Position32, ScreenLayout};
+use std::io::Write;
+
+struct Shot {
+    time: chrono::DateTime<chrono::Local>,
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+    velocity: f64,
+    class: Option<String>,
+}
+
+// Saves the area around the cursor on every reveal, to review what triggered it.
+// Grabbing happens before the overlay maps, encoding and writing on a thread.
+pub struct Recorder {
+    size: u32,
+    sender: std::sync::mpsc::Sender<Shot>,
+}
+
+impl Recorder {
+    pub fn new(dir: &str, size: u32, visual: &xcb::x::Visualtype) -> Recorder {
+        let dir = std::path::PathBuf::from(dir);
+        let masks = [visual.red_mask(), visual.green_mask(), visual.blue_mask()];
+        let (sender, receiver) = std::sync::mpsc::channel::<Shot>();
+        std::thread::spawn(move || {
+            if let Err(e) = std::fs::create_dir_all(&dir) {
+                eprintln!("Unable to create {}: {}", dir.display(), e);
+                return;
+            }
+            for shot in receiver {
+                if let Err(e) = write(&dir, &shot, masks) {
+                    eprintln!("Unable to save reveal screenshot: {}", e);
+                }
+            }
+        });
+        Recorder { size, sender }
+    }
+
+    pub fn record(
+        &self,
+        conn: &xcb::Connection,
+        root: xcb::x::Window,
+        layout: &ScreenLayout,
+        center: Position32,
+        velocity: f64,
+        class: Option<&crate::models::WindowClass>,
+    ) {
+        let half = (self.size / 2) as i32;
+        let Position32 { x, y } = layout.clamp(Position32::new(center.x - half, center.y - half));
+        let width = (self.size as i32).min(layout.width - x);
+        let height = (self.size as i32).min(layout.height - y);
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        let image = conn.wait_for_reply(conn.send_request(&xcb::x::GetImage {
+            format: xcb::x::ImageFormat::ZPixmap,
+            drawable: xcb::x::Drawable::Window(root),
+            x: x as _,
+            y: y as _,
+            width: width as _,
+            height: height as _,
+            plane_mask: u32::MAX,
+        }));
+        match image {
+            // Like the magnifier only 32 bits per pixel layouts are supported
+            Ok(image) if image.data().len() == (width * height * 4) as usize => {
+                let _ = self.sender.send(Shot {
+                    time: chrono::Local::now(),
+                    width: width as _,
+                    height: height as _,
+                    data: image.data().to_vec(),
+                    velocity,
+                    class: class.map(|class| class.class.clone()),
+                });
+            }
+            Ok(image) => eprintln!("Unsupported root depth for screenshots: {}", image.depth()),
+            Err(e) => eprintln!("Unable to grab reveal screenshot: {}", e),
+        }
+    }
+}
+
+// Binary PPM keeps this free of image crates, any viewer opens it
+fn write(dir: &std::path::Path, shot: &Shot, masks: [u32; 3]) -> std::io::Result<()> {
+    let name = format!("reveal-{}.ppm", shot.time.format("%Y%m%d-%H%M%S%.3f"));
+    let mut ppm = format!("P6\n{} {}\n255\n", shot.width, shot.height).into_bytes();
+    for pixel in shot.data.chunks_exact(4) {
+        let value = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+        ppm.extend(masks.iter().map(|mask| channel(value, *mask)));
+    }
+    std::fs::write(dir.join(&name), ppm)?;
+
+    let mut index = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join("index.tsv"))?;
+    writeln!(
+        index,
+        "{}\t{}\t{:.0}\t{}",
+        shot.time.to_rfc3339(),
+        name,
+        shot.velocity,
+        shot.class.as_deref().unwrap_or("-")
+    )
+}
+
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let shift = mask.trailing_zeros();
+    let max = (mask >> shift) as u64;
+    (((value & mask) >> shift) as u64 * 255 / max) as u8
+}
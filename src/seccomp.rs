@@ -0,0 +1,259 @@
+use crate::models::SeccompMode;
+
+// Offsets into struct seccomp_data, the low half of the first argument on little endian
+const NR_OFFSET: u32 = 0;
+const ARCH_OFFSET: u32 = 4;
+const ARG0_OFFSET: u32 = 16;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+// What the threads do once running: reading devices and the config, talking to the X
+// server, the IPC socket, the session bus and MQTT or HTTP clients, and saving state.
+// Starting threads and opening sockets are allowed by their arguments, see install.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const ALLOWED: &[libc::c_long] = &[
+    libc::SYS_read,
+    libc::SYS_write,
+    libc::SYS_readv,
+    libc::SYS_writev,
+    libc::SYS_pread64,
+    libc::SYS_pwrite64,
+    libc::SYS_close,
+    libc::SYS_openat,
+    libc::SYS_fstat,
+    libc::SYS_newfstatat,
+    libc::SYS_statx,
+    libc::SYS_statfs,
+    libc::SYS_fstatfs,
+    libc::SYS_lseek,
+    libc::SYS_getdents64,
+    libc::SYS_readlinkat,
+    libc::SYS_faccessat,
+    libc::SYS_faccessat2,
+    libc::SYS_getcwd,
+    libc::SYS_mkdirat,
+    libc::SYS_renameat,
+    libc::SYS_renameat2,
+    libc::SYS_unlinkat,
+    libc::SYS_ftruncate,
+    libc::SYS_fsync,
+    libc::SYS_fdatasync,
+    libc::SYS_fchmod,
+    libc::SYS_umask,
+    libc::SYS_ioctl,
+    libc::SYS_fcntl,
+    libc::SYS_dup,
+    libc::SYS_dup3,
+    libc::SYS_pipe2,
+    libc::SYS_ppoll,
+    libc::SYS_inotify_init1,
+    libc::SYS_inotify_add_watch,
+    libc::SYS_pselect6,
+    libc::SYS_epoll_create1,
+    libc::SYS_epoll_ctl,
+    libc::SYS_epoll_pwait,
+    libc::SYS_eventfd2,
+    libc::SYS_mmap,
+    libc::SYS_munmap,
+    libc::SYS_mremap,
+    libc::SYS_mprotect,
+    libc::SYS_madvise,
+    libc::SYS_brk,
+    libc::SYS_membarrier,
+    libc::SYS_shmget,
+    libc::SYS_shmat,
+    libc::SYS_shmdt,
+    libc::SYS_shmctl,
+    libc::SYS_futex,
+    libc::SYS_sched_yield,
+    libc::SYS_sched_getaffinity,
+    libc::SYS_nanosleep,
+    libc::SYS_clock_nanosleep,
+    libc::SYS_clock_gettime,
+    libc::SYS_clock_getres,
+    libc::SYS_gettimeofday,
+    libc::SYS_getrandom,
+    libc::SYS_rt_sigaction,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_sigaltstack,
+    libc::SYS_restart_syscall,
+    libc::SYS_getpid,
+    libc::SYS_getppid,
+    libc::SYS_gettid,
+    libc::SYS_getuid,
+    libc::SYS_geteuid,
+    libc::SYS_getgid,
+    libc::SYS_getegid,
+    libc::SYS_kill,
+    libc::SYS_tgkill,
+    libc::SYS_uname,
+    libc::SYS_sysinfo,
+    libc::SYS_prlimit64,
+    libc::SYS_prctl,
+    libc::SYS_set_robust_list,
+    libc::SYS_set_tid_address,
+    libc::SYS_rseq,
+    libc::SYS_wait4,
+    libc::SYS_waitid,
+    libc::SYS_exit,
+    libc::SYS_exit_group,
+    libc::SYS_socketpair,
+    libc::SYS_connect,
+    libc::SYS_accept4,
+    libc::SYS_bind,
+    libc::SYS_listen,
+    libc::SYS_sendto,
+    libc::SYS_recvfrom,
+    libc::SYS_sendmsg,
+    libc::SYS_recvmsg,
+    libc::SYS_shutdown,
+    libc::SYS_getsockopt,
+    libc::SYS_setsockopt,
+    libc::SYS_getsockname,
+    libc::SYS_getpeername,
+];
+
+// Their generic replacements are all aarch64 has
+#[cfg(target_arch = "x86_64")]
+const ALLOWED_LEGACY: &[libc::c_long] = &[
+    libc::SYS_open,
+    libc::SYS_stat,
+    libc::SYS_lstat,
+    libc::SYS_access,
+    libc::SYS_readlink,
+    libc::SYS_rename,
+    libc::SYS_unlink,
+    libc::SYS_mkdir,
+    libc::SYS_getdents,
+    libc::SYS_poll,
+    libc::SYS_select,
+    libc::SYS_pipe,
+    libc::SYS_dup2,
+    libc::SYS_epoll_create,
+    libc::SYS_epoll_wait,
+    libc::SYS_arch_prctl,
+    libc::SYS_time,
+];
+#[cfg(target_arch = "aarch64")]
+const ALLOWED_LEGACY: &[libc::c_long] = &[];
+
+// The X server, the session bus and the IPC socket are Unix sockets, MQTT and HTTP use IPv4
+const SOCKET_FAMILIES: &[libc::c_int] = &[libc::AF_UNIX, libc::AF_INET];
+
+// Applies to every thread of the process and everything spawned afterwards,
+// there's no way back once installed
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub fn install(mode: SeccompMode) -> std::io::Result<()> {
+    let blocked = match mode {
+        SeccompMode::Off => return Ok(()),
+        // Logged to the audit log, to find what the list lacks
+        SeccompMode::Log => libc::SECCOMP_RET_LOG,
+        SeccompMode::Enforce => libc::SECCOMP_RET_ERRNO | libc::EPERM as u32,
+    };
+
+    let statement = |code: u32, k: u32| libc::sock_filter {
+        code: code as _,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+    let load = |offset| statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, offset);
+    // Skips the next instruction when the loaded value matches, or unless it does
+    let jump_if = |value, jt, jf| libc::sock_filter {
+        code: (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as _,
+        jt,
+        jf,
+        k: value,
+    };
+    let ret = |action| statement(libc::BPF_RET | libc::BPF_K, action);
+    // Skips the given number of instructions unless the loaded value matches
+    let unless = |value, skip| jump_if(value, 0, skip);
+
+    // Syscall numbers of other architectures, e.g. i386 on x86_64, mean other syscalls
+    let mut program = vec![
+        load(ARCH_OFFSET),
+        jump_if(AUDIT_ARCH, 1, 0),
+        ret(blocked),
+        load(NR_OFFSET),
+    ];
+    for nr in ALLOWED.iter().chain(ALLOWED_LEGACY) {
+        program.push(unless(*nr as u32, 1));
+        program.push(ret(libc::SECCOMP_RET_ALLOW));
+    }
+
+    // Threads share the memory of the process, new processes could exec outside the filter
+    program.extend([
+        unless(libc::SYS_clone as u32, 4),
+        load(ARG0_OFFSET),
+        libc::sock_filter {
+            code: (libc::BPF_JMP | libc::BPF_JSET | libc::BPF_K) as _,
+            jt: 0,
+            jf: 1,
+            k: libc::CLONE_THREAD as u32,
+        },
+        ret(libc::SECCOMP_RET_ALLOW),
+        ret(blocked),
+    ]);
+    // The flags of clone3 are behind a pointer, ENOSYS makes libc fall back to clone
+    program.extend([
+        unless(libc::SYS_clone3 as u32, 1),
+        ret(match mode {
+            SeccompMode::Enforce => libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32,
+            _ => blocked,
+        }),
+    ]);
+    program.extend([
+        unless(libc::SYS_socket as u32, SOCKET_FAMILIES.len() as u8 + 3),
+        load(ARG0_OFFSET),
+    ]);
+    for (index, family) in SOCKET_FAMILIES.iter().enumerate() {
+        // Matches jump over the remaining checks and the block to the allow
+        program.push(jump_if(
+            *family as u32,
+            (SOCKET_FAMILIES.len() - index) as u8,
+            0,
+        ));
+    }
+    program.extend([ret(blocked), ret(libc::SECCOMP_RET_ALLOW)]);
+    program.push(ret(blocked));
+
+    let filter = libc::sock_fprog {
+        len: program.len() as _,
+        filter: program.as_mut_ptr(),
+    };
+    // Unprivileged processes may only install filters they can't escape with setuid
+    if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            libc::SECCOMP_FILTER_FLAG_TSYNC,
+            &filter as *const libc::sock_fprog,
+        )
+    };
+    match result {
+        0 => Ok(()),
+        // A thread already runs under another filter, its ID is returned
+        id if id > 0 => Err(std::io::Error::other(format!(
+            "thread {} has a different filter",
+            id
+        ))),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn install(mode: SeccompMode) -> std::io::Result<()> {
+    match mode {
+        SeccompMode::Off => Ok(()),
+        _ => Err(std::io::Error::other(
+            "the syscall list only covers x86_64 and aarch64",
+        )),
+    }
+}
@@ -0,0 +1,107 @@
+use crate::overlay_probe::{drawn, overlays, shake, wait_for, warp};
+use std::time::Duration;
+
+// Starts a private instance that polls the pointer, shakes the pointer through XTest
+// and checks the overlay of that instance. Returns whether all checks passed.
+pub fn run() -> bool {
+    let (conn, screen_num) =
+        match xcb::Connection::connect_with_extensions(None, &[xcb::Extension::Test], &[]) {
+            Ok(conn) => conn,
+            Err(e) => {
+                eprintln!("Unable to connect to the X server with XTest: {}", e);
+                return false;
+            }
+        };
+    let root = conn
+        .get_setup()
+        .roots()
+        .nth(screen_num as _)
+        .unwrap()
+        .root();
+    let pointer = match crate::ConnExt::get_pointer(&conn, root) {
+        Ok((pointer, _)) => pointer,
+        Err(e) => {
+            eprintln!("Unable to query the pointer: {}", e);
+            return false;
+        }
+    };
+
+    let dir = std::env::temp_dir().join(format!("mouse-reveal-self-test-{}", std::process::id()));
+    let config = "pointer_only = true\npointer_poll_hz = 120.0\n";
+    let written = std::fs::create_dir_all(dir.join("mouse-reveal"))
+        .and_then(|_| std::fs::write(dir.join("mouse-reveal").join("config.toml"), config));
+    if let Err(e) = written {
+        eprintln!("Unable to write the self-test config: {}", e);
+        return false;
+    }
+
+    // Windows of other clients, e.g. a running daemon, are left out of the checks
+    let existing = overlays(&conn, root);
+    let instance = std::env::current_exe().and_then(|exe| {
+        std::process::Command::new(exe)
+            .env("XDG_CONFIG_HOME", &dir)
+            .env("XDG_RUNTIME_DIR", &dir)
+            .stderr(std::process::Stdio::null())
+            .spawn()
+    });
+    let mut instance = match instance {
+        Ok(instance) => instance,
+        Err(e) => {
+            eprintln!("Unable to start the self-test instance: {}", e);
+            let _ = std::fs::remove_dir_all(&dir);
+            return false;
+        }
+    };
+    let own = |conn: &xcb::Connection| {
+        overlays(conn, root)
+            .into_iter()
+            .filter(|(window, _)| !existing.iter().any(|(other, _)| other == window))
+            .collect::<Vec<_>>()
+    };
+
+    let mut passed = true;
+    let mut check = |name: &str, ok: bool| {
+        println!("{} {}", if ok { "PASS" } else { "FAIL" }, name);
+        passed &= ok;
+    };
+
+    check(
+        "overlay window created",
+        wait_for(Duration::from_secs(5), || !own(&conn).is_empty()),
+    );
+    // With pointer_only the instance exits when its pointer poller can't connect
+    let exited = instance.try_wait().ok().flatten();
+    check(
+        &match exited {
+            Some(status) => format!("instance running, exited with {}", status),
+            None => String::from("instance running"),
+        },
+        exited.is_none(),
+    );
+
+    shake(
+        &conn,
+        root,
+        (pointer.x, pointer.y),
+        Duration::from_millis(700),
+    );
+    let mapped = own(&conn).into_iter().find(|(_, mapped)| *mapped);
+    check("overlay mapped while shaking", mapped.is_some());
+    check(
+        "overlay rendered",
+        mapped.is_some_and(|(window, _)| drawn(&conn, window)),
+    );
+
+    warp(&conn, root, pointer.x, pointer.y);
+    check(
+        "overlay unmapped after the motion stopped",
+        wait_for(Duration::from_secs(5), || {
+            own(&conn).iter().all(|(_, mapped)| !mapped)
+        }),
+    );
+
+    let _ = instance.kill();
+    let _ = instance.wait();
+    let _ = std::fs::remove_dir_all(&dir);
+    passed
+}
@@ -0,0 +1,64 @@
+use crate::ipc::Request;
+use crate::status::OverlayEvent;
+
+// See dbus/nl.degroen.MouseReveal1.xml for the protocol
+const NAME: &str = "nl.degroen.MouseReveal";
+const PATH: &str = "/nl/degroen/MouseReveal";
+const INTERFACE: &str = "nl.degroen.MouseReveal1";
+
+struct Bridge {
+    ipc: std::sync::Mutex<std::sync::mpsc::Sender<Request>>,
+    size: u32,
+}
+
+#[zbus::interface(name = "nl.degroen.MouseReveal1")]
+impl Bridge {
+    fn command(&self, line: &str) -> String {
+        crate::ipc::request(&self.ipc.lock().unwrap(), line.trim())
+    }
+
+    #[zbus(property)]
+    fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+// Takes the bus name so a GNOME Shell extension can draw the highlight natively on
+// Wayland, while the daemon goes on detecting
+pub fn connect(
+    config: &crate::models::Config,
+    ipc: std::sync::mpsc::Sender<Request>,
+) -> zbus::Result<zbus::blocking::Connection> {
+    let bridge = Bridge {
+        ipc: std::sync::Mutex::new(ipc),
+        size: config.window_size.max(0) as u32,
+    };
+    zbus::blocking::connection::Builder::session()?
+        .name(NAME)?
+        .serve_at(PATH, bridge)?
+        .build()
+}
+
+// Signals every reveal and hide to the extension and passes the events on
+pub fn forward(
+    conn: zbus::blocking::Connection,
+    events: std::sync::mpsc::Receiver<OverlayEvent>,
+) -> std::sync::mpsc::Receiver<OverlayEvent> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for event in events {
+            let signal = match event {
+                OverlayEvent::Reveal => "Reveal",
+                OverlayEvent::Hide => "Hide",
+            };
+            if let Err(e) = conn.emit_signal(None::<&str>, PATH, INTERFACE, signal, &()) {
+                eprintln!(
+                    "Unable to signal {} to the GNOME Shell extension: {}",
+                    signal, e
+                );
+            }
+            let _ = tx.send(event);
+        }
+    });
+    rx
+}
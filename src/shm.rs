@@ -0,0 +1,99 @@
+// Image buffer shared with the X server through MIT-SHM, uploading a frame
+// then only sends a small request instead of the pixels over the socket.
+pub struct ShmImage {
+    conn: std::sync::Arc<xcb::Connection>,
+    seg: xcb::shm::Seg,
+    addr: *mut u8,
+    len: usize,
+    // The server reads the segment asynchronously, this round trip tells when it's done
+    pending: Option<xcb::x::GetInputFocusCookie>,
+}
+
+impl ShmImage {
+    pub fn new(conn: std::sync::Arc<xcb::Connection>, len: usize) -> Option<ShmImage> {
+        conn.wait_for_reply(conn.send_request(&xcb::shm::QueryVersion {}))
+            .ok()?;
+
+        let id = unsafe { libc::shmget(libc::IPC_PRIVATE, len, libc::IPC_CREAT | 0o600) };
+        if id < 0 {
+            return None;
+        }
+        let addr = unsafe { libc::shmat(id, std::ptr::null(), 0) };
+
+        let seg: xcb::shm::Seg = conn.generate_id();
+        let attached = addr as isize != -1
+            && conn
+                .check_request(conn.send_request_checked(&xcb::shm::Attach {
+                    shmseg: seg,
+                    shmid: id as u32,
+                    read_only: true,
+                }))
+                .is_ok();
+
+        // Freed as soon as both sides detached
+        unsafe { libc::shmctl(id, libc::IPC_RMID, std::ptr::null_mut()) };
+
+        if !attached {
+            if addr as isize != -1 {
+                unsafe { libc::shmdt(addr) };
+            }
+            return None;
+        }
+
+        Some(ShmImage {
+            conn,
+            seg,
+            addr: addr as *mut u8,
+            len,
+            pending: None,
+        })
+    }
+
+    // Waits for the server to finish reading the previous frame before handing out the buffer
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        if let Some(cookie) = self.pending.take() {
+            let _ = self.conn.wait_for_reply(cookie);
+        }
+        unsafe { std::slice::from_raw_parts_mut(self.addr, self.len) }
+    }
+
+    pub fn put(
+        &mut self,
+        window: xcb::x::Window,
+        gc: xcb::x::Gcontext,
+        width: u16,
+        height: u16,
+        depth: u8,
+    ) {
+        self.conn.send_request(&xcb::shm::PutImage {
+            drawable: xcb::x::Drawable::Window(window),
+            gc,
+            total_width: width,
+            total_height: height,
+            src_x: 0,
+            src_y: 0,
+            src_width: width,
+            src_height: height,
+            dst_x: 0,
+            dst_y: 0,
+            depth,
+            format: xcb::x::ImageFormat::ZPixmap as u8,
+            send_event: false,
+            shmseg: self.seg,
+            offset: 0,
+        });
+        self.pending = Some(self.conn.send_request(&xcb::x::GetInputFocus {}));
+    }
+}
+
+impl Drop for ShmImage {
+    fn drop(&mut self) {
+        if let Some(cookie) = self.pending.take() {
+            let _ = self.conn.wait_for_reply(cookie);
+        }
+        self.conn
+            .send_request(&xcb::shm::Detach { shmseg: self.seg });
+        let _ = self.conn.flush();
+        unsafe { libc::shmdt(self.addr as *const libc::c_void) };
+    }
+}
@@ -0,0 +1,30 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Raised by SIGTERM, SIGINT or SIGQUIT. The capture, motion and UI loops watch it
+// and return, main joins them and cleans up. A second signal exits right away,
+// should one of them hang.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown(Arc<AtomicBool>);
+
+impl Shutdown {
+    pub fn install() -> Shutdown {
+        let shutdown = Shutdown::default();
+        for signal in signal_hook::consts::TERM_SIGNALS {
+            let registered = signal_hook::flag::register_conditional_shutdown(
+                *signal,
+                1,
+                Arc::clone(&shutdown.0),
+            )
+            .and_then(|_| signal_hook::flag::register(*signal, Arc::clone(&shutdown.0)));
+            if let Err(e) = registered {
+                eprintln!("Unable to handle signal {}: {}", signal, e);
+            }
+        }
+        shutdown
+    }
+
+    pub fn requested(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+// Runtime state kept across restarts, a restarted daemon stays paused and keeps the
+// profile switched to at runtime and the learned threshold
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct State {
+    pub paused: bool,
+    pub active_profile: Option<String>,
+    // In device units, as sent by the learner
+    pub learned_threshold: Option<f64>,
+    // Modification time of the config the threshold was learned with
+    pub config_modified: Option<i64>,
+}
+
+pub fn state_dir() -> std::path::PathBuf {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".local/state"))
+        })
+        .unwrap_or_default()
+        .join("mouse-reveal")
+}
+
+pub fn state_path() -> std::path::PathBuf {
+    state_dir().join("state.toml")
+}
+
+// None when no state was saved yet
+pub fn load() -> Option<State> {
+    let path = state_path();
+    match std::fs::read_to_string(&path) {
+        Ok(content) => match toml::from_str(&content) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                eprintln!("Invalid state {}: {}", path.display(), e);
+                None
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            eprintln!("Unable to read state {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// Nanoseconds since the epoch as stored in the state, only compared for equality
+pub fn stamp(time: Option<std::time::SystemTime>) -> Option<i64> {
+    let since = time?.duration_since(std::time::UNIX_EPOCH).ok()?;
+    i64::try_from(since.as_nanos()).ok()
+}
+
+pub fn save(state: &State) -> std::io::Result<()> {
+    write(&state_path(), state)
+}
+
+// Renaming is atomic, a crash leaves either the old or the new file behind
+pub fn write(path: &std::path::Path, value: &impl Serialize) -> std::io::Result<()> {
+    let content = toml::to_string(value)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let temporary = path.with_extension("toml.tmp");
+    std::fs::write(&temporary, content)?;
+    std::fs::rename(&temporary, path)
+}
@@ -0,0 +1,131 @@
+use serde::Serialize;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverlayEvent {
+    Reveal,
+    Hide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Status {
+    pub enabled: bool,
+    pub reveals: u64,
+}
+
+impl Status {
+    pub fn new() -> Status {
+        Status {
+            enabled: true,
+            reveals: 0,
+        }
+    }
+
+    pub fn to_json(self) -> String {
+        serde_json::to_string(&StatusLine::from(&self)).unwrap_or_default()
+    }
+
+    fn state(&self) -> &'static str {
+        if self.enabled {
+            "enabled"
+        } else {
+            "paused"
+        }
+    }
+}
+
+// Follows the Waybar custom module `return-type: json` format, Polybar can
+// pick the `text` field with a `tail = true` custom/script module.
+#[derive(Serialize)]
+struct StatusLine<'a> {
+    text: String,
+    alt: &'a str,
+    class: &'a str,
+    tooltip: String,
+    reveals: u64,
+}
+
+impl<'a> From<&'a Status> for StatusLine<'a> {
+    fn from(status: &'a Status) -> StatusLine<'a> {
+        StatusLine {
+            text: status.reveals.to_string(),
+            alt: status.state(),
+            class: status.state(),
+            tooltip: format!(
+                "Mouse reveal {}, {} reveals",
+                status.state(),
+                status.reveals
+            ),
+            reveals: status.reveals,
+        }
+    }
+}
+
+pub enum StatusOutput {
+    Stdout,
+    Fifo(std::path::PathBuf),
+}
+
+impl StatusOutput {
+    pub fn parse(value: &str) -> StatusOutput {
+        match value {
+            "-" | "stdout" => StatusOutput::Stdout,
+            path => StatusOutput::Fifo(std::path::PathBuf::from(path)),
+        }
+    }
+
+    fn open(&self) -> std::io::Result<Box<dyn Write>> {
+        match self {
+            StatusOutput::Stdout => Ok(Box::new(std::io::stdout())),
+            // Opening a FIFO blocks until the bar starts reading from it
+            StatusOutput::Fifo(path) => Ok(Box::new(
+                std::fs::OpenOptions::new().write(true).open(path)?,
+            )),
+        }
+    }
+}
+
+pub fn write_line(writer: &mut dyn Write, status: &Status) -> std::io::Result<()> {
+    writeln!(writer, "{}", status.to_json())?;
+    writer.flush()
+}
+
+pub fn start_status_thread(output: StatusOutput, receiver: std::sync::mpsc::Receiver<Status>) {
+    std::thread::spawn(move || {
+        let mut current = Status::new();
+        let mut writer = None;
+        loop {
+            if writer.is_none() {
+                match output.open() {
+                    Ok(w) => writer = Some(w),
+                    Err(e) => {
+                        eprintln!("Unable to open status output: {}", e);
+                        std::thread::sleep(std::time::Duration::from_secs(1));
+                        continue;
+                    }
+                }
+            }
+
+            // Always start a (re)opened output with the current state
+            if let Some(w) = writer.as_mut() {
+                if let Err(e) = write_line(w.as_mut(), &current) {
+                    // Reopening a FIFO waits for the next reader, stdout can't be reopened
+                    if let StatusOutput::Stdout = output {
+                        eprintln!("Status output closed: {}", e);
+                        break;
+                    }
+                    writer = None;
+                    continue;
+                }
+            }
+
+            match receiver.recv() {
+                Ok(status) => current = status,
+                Err(_) => {
+                    eprintln!("Status receiver disconnected");
+                    break;
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,355 @@
+use crate::models::ColorScheme;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    Ring,
+    Disc,
+}
+
+static PIXEL_FORMAT: std::sync::OnceLock<PixelFormat> = std::sync::OnceLock::new();
+
+// Channel layout of the visual the overlay windows are created with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelFormat {
+    red_mask: u32,
+    green_mask: u32,
+    blue_mask: u32,
+    alpha: bool,
+}
+
+impl PixelFormat {
+    pub fn argb32() -> PixelFormat {
+        PixelFormat {
+            red_mask: 0xff0000,
+            green_mask: 0xff00,
+            blue_mask: 0xff,
+            alpha: true,
+        }
+    }
+
+    pub fn from_visual(visual: &xcb::x::Visualtype, alpha: bool) -> PixelFormat {
+        PixelFormat {
+            red_mask: visual.red_mask(),
+            green_mask: visual.green_mask(),
+            blue_mask: visual.blue_mask(),
+            alpha,
+        }
+    }
+
+    // All overlay windows share the visual, the first one wins
+    pub fn install(self) {
+        let _ = PIXEL_FORMAT.set(self);
+    }
+
+    fn pack(&self, red: u32, green: u32, blue: u32, alpha: u8) -> u32 {
+        // Scales an 8-bit channel into the bits of the mask, e.g. 5/6/5 or 10/10/10
+        let channel = |value: u32, mask: u32| {
+            if mask == 0 {
+                return 0;
+            }
+            let shift = mask.trailing_zeros();
+            let max = mask >> shift;
+            ((value * max + 127) / 255) << shift
+        };
+        let alpha = if self.alpha { (alpha as u32) << 24 } else { 0 };
+        alpha
+            | channel(red, self.red_mask)
+            | channel(green, self.green_mask)
+            | channel(blue, self.blue_mask)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Color {
+    pub fn new(red: u8, green: u8, blue: u8) -> Color {
+        Color { red, green, blue }
+    }
+
+    // Fully saturated color for a hue in degrees
+    pub fn from_hue(hue: f64) -> Color {
+        let h = hue.rem_euclid(360.0) / 60.0;
+        let x = 1.0 - (h % 2.0 - 1.0).abs();
+        let (r, g, b) = match h as u32 {
+            0 => (1.0, x, 0.0),
+            1 => (x, 1.0, 0.0),
+            2 => (0.0, 1.0, x),
+            3 => (0.0, x, 1.0),
+            4 => (x, 0.0, 1.0),
+            _ => (1.0, 0.0, x),
+        };
+        let channel = |c: f64| (c * 255.0).round() as u8;
+        Color::new(channel(r), channel(g), channel(b))
+    }
+
+    // Scales the channels with the given intensity (0.0 - 1.0) into a pixel of the overlay visual
+    pub fn pixel(&self, intensity: f64, alpha: u8) -> u32 {
+        let scale = |c: u8| ((c as f64) * intensity.clamp(0.0, 1.0)) as u32;
+        PIXEL_FORMAT
+            .get()
+            .copied()
+            .unwrap_or_else(PixelFormat::argb32)
+            .pack(scale(self.red), scale(self.green), scale(self.blue), alpha)
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> String {
+        format!("#{:02x}{:02x}{:02x}", color.red, color.green, color.blue)
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Color, String> {
+        let hex = value.trim_start_matches('#');
+        if hex.len() != 6 {
+            return Err(format!("invalid color \"{}\", expected #rrggbb", value));
+        }
+        let channel = |i: usize| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| format!("invalid color \"{}\", expected #rrggbb", value))
+        };
+        Ok(Color::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+// Ring radius and alpha are relative to their maximum, width is in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Keyframe {
+    pub time_ms: u64,
+    pub radius: f64,
+    pub alpha: f64,
+    pub width: f64,
+}
+
+impl Default for Keyframe {
+    fn default() -> Keyframe {
+        Keyframe {
+            time_ms: 0,
+            radius: 1.0,
+            alpha: 1.0,
+            width: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub shape: Shape,
+    pub color: Color,
+    // Replace color on desktops preferring that color scheme
+    pub light_color: Option<Color>,
+    pub dark_color: Option<Color>,
+    pub max_alpha: u8,
+    pub rings: u32,
+    pub ring_spacing: u32,
+    // Sizes of the rings relative to the outer ring, replacing ring_spacing when set
+    pub ring_scales: Vec<f64>,
+    // Alpha of each ring relative to the ring outside of it
+    pub ring_falloff: f64,
+    // Played when the cursor is revealed and after it stopped, instead of following the velocity
+    pub intro: Vec<Keyframe>,
+    pub outro: Vec<Keyframe>,
+    pub frame_interval_ms: u64,
+    // Part of the circle drawn, rotating once per spin_seconds when that is set
+    pub arc_degrees: f64,
+    pub spin_seconds: f64,
+    // Fainter and wider copies of the rings drawn behind them, as a soft glow
+    pub glow_rings: u32,
+    pub glow_spread: u32,
+    pub glow_alpha: f64,
+    // Drawn at full size and alpha while shown, regardless of the velocity
+    pub steady: bool,
+    // Dashed rings when set, with the gaps in dash_color or left out without it
+    pub dash_length: u32,
+    pub dash_color: Option<Color>,
+    // Raises the minimum ring width of the config
+    pub min_width: u32,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::classic()
+    }
+}
+
+impl Theme {
+    pub fn classic() -> Theme {
+        Theme {
+            shape: Shape::Ring,
+            color: Color::new(255, 0, 0),
+            light_color: None,
+            dark_color: None,
+            max_alpha: 200,
+            rings: 1,
+            ring_spacing: 0,
+            ring_scales: Vec::new(),
+            ring_falloff: 1.0,
+            intro: Vec::new(),
+            outro: Vec::new(),
+            frame_interval_ms: 1000 / 30,
+            arc_degrees: 360.0,
+            spin_seconds: 0.0,
+            glow_rings: 0,
+            glow_spread: 3,
+            glow_alpha: 0.35,
+            steady: false,
+            dash_length: 0,
+            dash_color: None,
+            min_width: 0,
+        }
+    }
+
+    pub fn ripple() -> Theme {
+        Theme {
+            rings: 3,
+            ring_spacing: 12,
+            color: Color::new(0, 160, 255),
+            frame_interval_ms: 1000 / 60,
+            ..Theme::classic()
+        }
+    }
+
+    pub fn spotlight() -> Theme {
+        Theme {
+            shape: Shape::Disc,
+            color: Color::new(255, 255, 255),
+            // A white spotlight disappears on light windows
+            light_color: Some(Color::new(40, 40, 40)),
+            max_alpha: 96,
+            ..Theme::classic()
+        }
+    }
+
+    pub fn target() -> Theme {
+        Theme {
+            rings: 4,
+            ring_scales: vec![1.0, 0.72, 0.46, 0.22],
+            ring_falloff: 0.7,
+            intro: vec![
+                Keyframe {
+                    time_ms: 0,
+                    radius: 0.2,
+                    alpha: 0.0,
+                    width: 2.0,
+                },
+                Keyframe {
+                    time_ms: 150,
+                    radius: 1.0,
+                    alpha: 1.0,
+                    width: 4.0,
+                },
+            ],
+            outro: vec![
+                Keyframe {
+                    time_ms: 0,
+                    radius: 1.0,
+                    alpha: 1.0,
+                    width: 4.0,
+                },
+                Keyframe {
+                    time_ms: 200,
+                    radius: 0.1,
+                    alpha: 0.0,
+                    width: 1.0,
+                },
+            ],
+            color: Color::new(255, 40, 40),
+            ..Theme::classic()
+        }
+    }
+
+    pub fn spinner() -> Theme {
+        Theme {
+            color: Color::new(255, 160, 0),
+            arc_degrees: 270.0,
+            spin_seconds: 0.8,
+            frame_interval_ms: 1000 / 60,
+            ..Theme::classic()
+        }
+    }
+
+    // Thick yellow and black dashes stand out on any background, for low vision
+    pub fn high_contrast() -> Theme {
+        Theme {
+            color: Color::new(255, 215, 0),
+            dash_length: 16,
+            dash_color: Some(Color::new(0, 0, 0)),
+            min_width: 10,
+            max_alpha: 255,
+            ..Theme::classic()
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Theme> {
+        match name {
+            "classic" => Some(Theme::classic()),
+            "ripple" => Some(Theme::ripple()),
+            "spotlight" => Some(Theme::spotlight()),
+            "target" => Some(Theme::target()),
+            "spinner" => Some(Theme::spinner()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    pub fn for_scheme(self, scheme: ColorScheme) -> Theme {
+        let color = match scheme {
+            ColorScheme::Light => self.light_color,
+            ColorScheme::Dark => self.dark_color,
+            ColorScheme::Auto => None,
+        };
+        Theme {
+            color: color.unwrap_or(self.color),
+            ..self
+        }
+    }
+
+    // Keeps the look of the theme but nothing that moves
+    pub fn with_reduced_motion(self, reduce: bool) -> Theme {
+        if !reduce {
+            return self;
+        }
+        Theme {
+            max_alpha: 255,
+            intro: Vec::new(),
+            outro: Vec::new(),
+            spin_seconds: 0.0,
+            steady: true,
+            ..self
+        }
+    }
+
+    // Theme files in the config directory take precedence over the built-in themes
+    pub fn load(name: &str) -> Theme {
+        let path = crate::config::config_dir()
+            .join("themes")
+            .join(format!("{}.toml", name));
+
+        match std::fs::read_to_string(&path) {
+            Ok(content) => match toml::from_str(&content) {
+                Ok(theme) => return theme,
+                Err(e) => eprintln!("Invalid theme {}: {}", path.display(), e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("Unable to read theme {}: {}", path.display(), e),
+        }
+
+        Theme::builtin(name).unwrap_or_else(|| {
+            eprintln!("Unknown theme \"{}\", using classic", name);
+            Theme::classic()
+        })
+    }
+}
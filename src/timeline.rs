@@ -0,0 +1,64 @@
+use crate::themes::Keyframe;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub radius: f64,
+    pub alpha: f64,
+    pub width: f64,
+}
+
+// Plays keyframes from the moment it is created, interpolating linearly between them
+pub struct Playback {
+    keyframes: Vec<Keyframe>,
+    start: std::time::Instant,
+}
+
+impl Playback {
+    pub fn new(keyframes: &[Keyframe]) -> Option<Playback> {
+        if keyframes.is_empty() {
+            return None;
+        }
+
+        let mut keyframes = keyframes.to_vec();
+        keyframes.sort_by_key(|keyframe| keyframe.time_ms);
+        Some(Playback {
+            keyframes,
+            start: std::time::Instant::now(),
+        })
+    }
+
+    pub fn finished(&self) -> bool {
+        let end = self.keyframes.last().map_or(0, |keyframe| keyframe.time_ms);
+        self.start.elapsed().as_millis() as u64 >= end
+    }
+
+    pub fn sample(&self) -> Frame {
+        let time = self.start.elapsed().as_secs_f64() * 1000.0;
+        let next = self
+            .keyframes
+            .iter()
+            .position(|keyframe| keyframe.time_ms as f64 > time);
+
+        let (from, to) = match next {
+            Some(0) => (self.keyframes[0], self.keyframes[0]),
+            Some(i) => (self.keyframes[i - 1], self.keyframes[i]),
+            None => {
+                let last = *self.keyframes.last().unwrap();
+                (last, last)
+            }
+        };
+
+        let span = (to.time_ms - from.time_ms) as f64;
+        let t = if span > 0.0 {
+            ((time - from.time_ms as f64) / span).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        Frame {
+            radius: lerp(from.radius, to.radius),
+            alpha: lerp(from.alpha, to.alpha),
+            width: lerp(from.width, to.width),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PhaseStats {
+    pub count: u64,
+    pub total_us: u64,
+    pub max_us: u64,
+}
+
+impl PhaseStats {
+    fn record(&mut self, elapsed: std::time::Duration) {
+        let us = elapsed.as_micros() as u64;
+        self.count += 1;
+        self.total_us += us;
+        self.max_us = self.max_us.max(us);
+    }
+
+    fn mean_us(&self) -> u64 {
+        self.total_us.checked_div(self.count).unwrap_or(0)
+    }
+}
+
+// Time spent in the phases of the render path, since startup
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FrameStats {
+    pub pointer: PhaseStats,
+    pub draw: PhaseStats,
+    pub flush: PhaseStats,
+    pub frames: u64,
+    pub dropped: u64,
+}
+
+impl FrameStats {
+    pub fn new() -> FrameStats {
+        FrameStats::default()
+    }
+
+    pub fn pointer<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        time(&mut self.pointer, f)
+    }
+
+    pub fn draw<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        time(&mut self.draw, f)
+    }
+
+    pub fn flush<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        time(&mut self.flush, f)
+    }
+
+    // A frame is dropped when its work alone exceeds the frame interval
+    pub fn frame(&mut self, elapsed: std::time::Duration, budget: std::time::Duration) {
+        self.frames += 1;
+        if elapsed > budget {
+            self.dropped += 1;
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "frames {} dropped {} | pointer {}/{}us draw {}/{}us flush {}/{}us (mean/max)",
+            self.frames,
+            self.dropped,
+            self.pointer.mean_us(),
+            self.pointer.max_us,
+            self.draw.mean_us(),
+            self.draw.max_us,
+            self.flush.mean_us(),
+            self.flush.max_us
+        )
+    }
+}
+
+fn time<T>(phase: &mut PhaseStats, f: impl FnOnce() -> T) -> T {
+    let start = std::time::Instant::now();
+    let result = f();
+    phase.record(start.elapsed());
+    result
+}
@@ -0,0 +1,413 @@
+use crate::models::{Config, Gesture};
+
+// Contacts starting this close to the edge of the touchpad are edge swipes
+const EDGE_FRACTION: f64 = 0.03;
+// Change of the distance between two contacts, relative to the diagonal, that makes a pinch
+const PINCH_FRACTION: f64 = 0.1;
+const MAX_SLOTS: usize = 16;
+
+// Settings of the multitouch tracking, taken from the config when a device opens
+#[derive(Debug, Clone)]
+pub struct TouchSettings {
+    gestures: Vec<Gesture>,
+    palm_pressure: f64,
+    palm_size: f64,
+}
+
+impl TouchSettings {
+    pub fn new(config: &Config) -> TouchSettings {
+        TouchSettings {
+            gestures: config.gesture_reveal.clone(),
+            palm_pressure: config.palm_pressure,
+            palm_size: config.palm_size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Contact {
+    active: bool,
+    // The first position after the tracking ID is where the contact started
+    started: bool,
+    // Once seen as a palm the contact stays one until it lifts
+    palm: bool,
+    x: i32,
+    y: i32,
+    start_x: i32,
+    start_y: i32,
+    pressure: i32,
+    touch_major: i32,
+}
+
+// Multitouch slots of a touchpad, the first contact is followed and the others
+// only tell which gesture is going on
+#[derive(Debug)]
+pub struct Touches {
+    slot: usize,
+    contacts: Vec<Contact>,
+    primary: Option<usize>,
+    x_range: (i32, i32),
+    y_range: (i32, i32),
+    pinched: bool,
+    gestures: Vec<Gesture>,
+    // In the units of the device, None when the device doesn't report it
+    palm_pressure: Option<i32>,
+    palm_size: Option<i32>,
+}
+
+impl Touches {
+    // None for devices without multitouch positions
+    pub fn open(device: &evdev::Device, settings: TouchSettings) -> Option<Touches> {
+        let axes = device.supported_absolute_axes()?;
+        if !axes.contains(evdev::AbsoluteAxisType::ABS_MT_POSITION_X)
+            || !axes.contains(evdev::AbsoluteAxisType::ABS_MT_POSITION_Y)
+        {
+            return None;
+        }
+        let state = device.get_abs_state().ok()?;
+        let info = |axis: evdev::AbsoluteAxisType| state[axis.0 as usize];
+        let range = |axis: evdev::AbsoluteAxisType| (info(axis).minimum, info(axis).maximum);
+        let x_range = range(evdev::AbsoluteAxisType::ABS_MT_POSITION_X);
+
+        // Palms press harder and are wider, touch major is in the units of the positions
+        let palm_pressure = (settings.palm_pressure > 0.0
+            && axes.contains(evdev::AbsoluteAxisType::ABS_MT_PRESSURE))
+        .then(|| {
+            let (min, max) = range(evdev::AbsoluteAxisType::ABS_MT_PRESSURE);
+            min + ((max - min) as f64 * settings.palm_pressure) as i32
+        });
+        let palm_size = (settings.palm_size > 0.0
+            && axes.contains(evdev::AbsoluteAxisType::ABS_MT_TOUCH_MAJOR))
+        .then(|| ((x_range.1 - x_range.0) as f64 * settings.palm_size) as i32);
+        if settings.palm_pressure > 0.0 && palm_pressure.is_none() {
+            eprintln!("Device reports no contact pressure, palm_pressure is ignored");
+        }
+        if settings.palm_size > 0.0 && palm_size.is_none() {
+            eprintln!("Device reports no contact size, palm_size is ignored");
+        }
+
+        let slots = info(evdev::AbsoluteAxisType::ABS_MT_SLOT).maximum.max(0) as usize + 1;
+        Some(Touches {
+            slot: info(evdev::AbsoluteAxisType::ABS_MT_SLOT).value.max(0) as usize,
+            contacts: vec![Contact::default(); slots.clamp(1, MAX_SLOTS)],
+            primary: None,
+            x_range,
+            y_range: range(evdev::AbsoluteAxisType::ABS_MT_POSITION_Y),
+            pinched: false,
+            gestures: settings.gestures,
+            palm_pressure,
+            palm_size,
+        })
+    }
+
+    pub fn set_slot(&mut self, slot: i32) {
+        self.slot = slot.max(0) as usize;
+    }
+
+    // Returns true when another contact became the followed one
+    pub fn tracking_id(&mut self, id: i32) -> bool {
+        let slot = self.slot;
+        let Some(contact) = self.contacts.get_mut(slot) else {
+            return false;
+        };
+        // Only changed values are reported, a new contact starts with the position,
+        // pressure and size the last one in the slot left
+        contact.active = id >= 0;
+        contact.started = false;
+        contact.palm = false;
+
+        if id >= 0 && self.primary.is_none() {
+            self.primary = Some(slot);
+            true
+        } else if id < 0 && self.primary == Some(slot) {
+            // A finger still down takes over
+            self.follow_next()
+        } else {
+            false
+        }
+    }
+
+    pub fn set_x(&mut self, x: i32) {
+        if let Some(contact) = self.contacts.get_mut(self.slot) {
+            contact.x = x;
+        }
+    }
+
+    pub fn set_y(&mut self, y: i32) {
+        if let Some(contact) = self.contacts.get_mut(self.slot) {
+            contact.y = y;
+        }
+    }
+
+    pub fn set_pressure(&mut self, pressure: i32) {
+        if let Some(contact) = self.contacts.get_mut(self.slot) {
+            contact.pressure = pressure;
+        }
+    }
+
+    pub fn set_touch_major(&mut self, touch_major: i32) {
+        if let Some(contact) = self.contacts.get_mut(self.slot) {
+            contact.touch_major = touch_major;
+        }
+    }
+
+    // Called once per report, after all positions of it arrived. Returns true when
+    // the followed contact turned out to be a palm and another one is followed instead.
+    pub fn sync(&mut self) -> bool {
+        let (palm_pressure, palm_size) = (self.palm_pressure, self.palm_size);
+        for contact in self.contacts.iter_mut().filter(|contact| contact.active) {
+            if !contact.started {
+                contact.started = true;
+                contact.start_x = contact.x;
+                contact.start_y = contact.y;
+            }
+            contact.palm |= palm_pressure.is_some_and(|limit| contact.pressure > limit)
+                || palm_size.is_some_and(|limit| contact.touch_major > limit);
+        }
+        if self.fingers().count() < 2 {
+            self.pinched = false;
+        }
+
+        let primary = self.primary.and_then(|slot| self.contacts.get(slot));
+        if !primary.is_some_and(|contact| contact.palm) {
+            return false;
+        }
+        self.follow_next();
+        true
+    }
+
+    // Follows the first finger that is down, returns false without any
+    fn follow_next(&mut self) -> bool {
+        self.primary = self
+            .contacts
+            .iter()
+            .position(|contact| contact.active && !contact.palm);
+        self.primary.is_some()
+    }
+
+    pub fn primary(&self) -> Option<(i32, i32)> {
+        self.primary
+            .and_then(|slot| self.contacts.get(slot))
+            .map(|contact| (contact.x, contact.y))
+    }
+
+    // Whether the current gesture is one whose motion may not reveal the cursor
+    pub fn excluded(&mut self) -> bool {
+        self.gesture()
+            .is_some_and(|gesture| !self.gestures.contains(&gesture))
+    }
+
+    fn gesture(&mut self) -> Option<Gesture> {
+        let (first, second) = {
+            let mut fingers = self.fingers().copied();
+            (fingers.next()?, fingers.next())
+        };
+        let Some(second) = second else {
+            let primary = self.contacts.get(self.primary?)?;
+            return self.at_edge(primary).then_some(Gesture::EdgeSwipe);
+        };
+
+        // Once apart far enough it stays a pinch until a contact lifts
+        let distance =
+            |ax: i32, ay: i32, bx: i32, by: i32| ((ax - bx) as f64).hypot((ay - by) as f64);
+        let start = distance(first.start_x, first.start_y, second.start_x, second.start_y);
+        let now = distance(first.x, first.y, second.x, second.y);
+        self.pinched |= (now - start).abs() > PINCH_FRACTION * self.diagonal();
+        Some(if self.pinched {
+            Gesture::Pinch
+        } else {
+            Gesture::Scroll
+        })
+    }
+
+    // A resting palm next to a moving finger is no scroll
+    fn fingers(&self) -> impl Iterator<Item = &Contact> {
+        self.contacts
+            .iter()
+            .filter(|contact| contact.active && !contact.palm)
+    }
+
+    fn at_edge(&self, contact: &Contact) -> bool {
+        let near = |value: i32, (min, max): (i32, i32)| {
+            let margin = (max - min) as f64 * EDGE_FRACTION;
+            max > min && (((value - min) as f64) < margin || ((max - value) as f64) < margin)
+        };
+        near(contact.start_x, self.x_range) || near(contact.start_y, self.y_range)
+    }
+
+    fn diagonal(&self) -> f64 {
+        let (x_min, x_max) = self.x_range;
+        let (y_min, y_max) = self.y_range;
+        ((x_max - x_min) as f64).hypot((y_max - y_min) as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 1000 by 1000 touchpad with four slots, palms press harder than 100
+    fn touchpad(gestures: Vec<Gesture>) -> Touches {
+        Touches {
+            slot: 0,
+            contacts: vec![Contact::default(); 4],
+            primary: None,
+            x_range: (0, 1000),
+            y_range: (0, 1000),
+            pinched: false,
+            gestures,
+            palm_pressure: Some(100),
+            palm_size: None,
+        }
+    }
+
+    fn down(touches: &mut Touches, slot: i32, x: i32, y: i32) -> bool {
+        touches.set_slot(slot);
+        let followed = touches.tracking_id(slot + 10);
+        touches.set_x(x);
+        touches.set_y(y);
+        touches.set_pressure(30);
+        followed
+    }
+
+    fn moved(touches: &mut Touches, slot: i32, x: i32, y: i32) {
+        touches.set_slot(slot);
+        touches.set_x(x);
+        touches.set_y(y);
+    }
+
+    fn up(touches: &mut Touches, slot: i32) -> bool {
+        touches.set_slot(slot);
+        touches.tracking_id(-1)
+    }
+
+    #[test]
+    fn first_contact_is_followed_until_it_lifts() {
+        let mut touches = touchpad(Vec::new());
+        assert!(down(&mut touches, 0, 500, 500));
+        touches.sync();
+        moved(&mut touches, 0, 520, 510);
+        touches.sync();
+        assert_eq!(touches.primary(), Some((520, 510)));
+
+        assert!(!up(&mut touches, 0));
+        touches.sync();
+        assert_eq!(touches.primary(), None);
+    }
+
+    #[test]
+    fn additional_contacts_are_ignored() {
+        let mut touches = touchpad(Vec::new());
+        down(&mut touches, 0, 500, 500);
+        assert!(!down(&mut touches, 1, 300, 300));
+        touches.sync();
+        moved(&mut touches, 1, 340, 300);
+        touches.sync();
+        assert_eq!(touches.primary(), Some((500, 500)));
+    }
+
+    #[test]
+    fn remaining_contact_takes_over_when_the_followed_one_lifts() {
+        let mut touches = touchpad(Vec::new());
+        down(&mut touches, 0, 500, 500);
+        down(&mut touches, 1, 300, 300);
+        touches.sync();
+        assert!(up(&mut touches, 0));
+        touches.sync();
+        assert_eq!(touches.primary(), Some((300, 300)));
+
+        // A new contact in the freed slot doesn't take the focus back
+        assert!(!down(&mut touches, 0, 700, 700));
+        touches.sync();
+        assert_eq!(touches.primary(), Some((300, 300)));
+    }
+
+    #[test]
+    fn palms_hand_over_to_a_finger() {
+        let mut touches = touchpad(Vec::new());
+        down(&mut touches, 0, 200, 800);
+        touches.set_pressure(150);
+        down(&mut touches, 1, 500, 500);
+        assert!(touches.sync());
+        assert_eq!(touches.primary(), Some((500, 500)));
+
+        // Still a palm once it presses lighter, and no part of a scroll
+        touches.set_slot(0);
+        touches.set_pressure(30);
+        assert!(!touches.sync());
+        moved(&mut touches, 1, 540, 500);
+        touches.sync();
+        assert_eq!(touches.primary(), Some((540, 500)));
+        assert!(!touches.excluded());
+    }
+
+    #[test]
+    fn palm_without_a_finger_is_not_followed() {
+        let mut touches = touchpad(Vec::new());
+        down(&mut touches, 0, 200, 800);
+        touches.set_pressure(150);
+        assert!(touches.sync());
+        assert_eq!(touches.primary(), None);
+    }
+
+    #[test]
+    fn two_fingers_moving_together_scroll() {
+        let mut touches = touchpad(Vec::new());
+        down(&mut touches, 0, 400, 500);
+        down(&mut touches, 1, 500, 500);
+        touches.sync();
+        moved(&mut touches, 0, 400, 300);
+        moved(&mut touches, 1, 500, 300);
+        touches.sync();
+        assert_eq!(touches.gesture(), Some(Gesture::Scroll));
+        assert!(touches.excluded());
+
+        let mut touches = touchpad(vec![Gesture::Scroll]);
+        down(&mut touches, 0, 400, 500);
+        down(&mut touches, 1, 500, 500);
+        touches.sync();
+        assert!(!touches.excluded());
+    }
+
+    #[test]
+    fn fingers_moving_apart_pinch_until_one_lifts() {
+        let mut touches = touchpad(vec![Gesture::Scroll]);
+        down(&mut touches, 0, 450, 500);
+        down(&mut touches, 1, 550, 500);
+        touches.sync();
+        moved(&mut touches, 0, 300, 500);
+        moved(&mut touches, 1, 700, 500);
+        touches.sync();
+        assert_eq!(touches.gesture(), Some(Gesture::Pinch));
+        assert!(touches.excluded());
+
+        // Back to the start distance it is still the same pinch
+        moved(&mut touches, 0, 450, 500);
+        moved(&mut touches, 1, 550, 500);
+        touches.sync();
+        assert_eq!(touches.gesture(), Some(Gesture::Pinch));
+
+        up(&mut touches, 1);
+        touches.sync();
+        assert_eq!(touches.gesture(), None);
+    }
+
+    #[test]
+    fn contacts_starting_at_the_edge_swipe() {
+        let mut touches = touchpad(Vec::new());
+        down(&mut touches, 0, 10, 500);
+        touches.sync();
+        moved(&mut touches, 0, 300, 500);
+        touches.sync();
+        assert_eq!(touches.gesture(), Some(Gesture::EdgeSwipe));
+        assert!(touches.excluded());
+
+        let mut touches = touchpad(Vec::new());
+        down(&mut touches, 0, 500, 500);
+        touches.sync();
+        moved(&mut touches, 0, 990, 500);
+        touches.sync();
+        assert_eq!(touches.gesture(), None);
+        assert!(!touches.excluded());
+    }
+}
@@ -0,0 +1,269 @@
+use crate::detection::Detector;
+use crate::models::{Config, ThresholdUnit, VelocityEvent, VelocityHistory};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::prelude::*;
+use ratatui::widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph};
+use std::io::BufRead;
+use std::os::fd::FromRawFd;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const HISTORY_SECONDS: f64 = 10.0;
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(1000 / 60);
+
+struct Tuner {
+    config: Config,
+    detector: Detector,
+    start: Instant,
+    velocity: Vec<(f64, f64)>,
+    smoothed: Vec<(f64, f64)>,
+    revealed: bool,
+    message: String,
+    diagonal: Option<f64>,
+    diagnostics: Arc<Mutex<String>>,
+}
+
+impl Tuner {
+    fn new(config: Config, diagnostics: Arc<Mutex<String>>) -> Tuner {
+        Tuner {
+            detector: Detector::new(config.clone()),
+            config,
+            start: Instant::now(),
+            velocity: Vec::new(),
+            smoothed: Vec::new(),
+            revealed: false,
+            message: String::from("Shake the cursor to see how the detector responds"),
+            diagonal: None,
+            diagnostics,
+        }
+    }
+
+    fn sample(&mut self, event: VelocityEvent) {
+        let velocity = if event.expired(self.config.motion_expiry()) {
+            0.0
+        } else {
+            event.velocity()
+        };
+        if event.diagonal().is_some() && event.diagonal() != self.diagonal {
+            self.diagonal = event.diagonal();
+            self.apply();
+        }
+        self.revealed = self.detector.update(velocity);
+
+        let now = self.start.elapsed().as_secs_f64();
+        self.velocity.push((now, velocity));
+        self.smoothed.push((now, self.detector.weighted()));
+
+        let oldest = now - HISTORY_SECONDS;
+        self.velocity.retain(|(t, _)| *t >= oldest);
+        self.smoothed.retain(|(t, _)| *t >= oldest);
+    }
+
+    fn apply(&mut self) {
+        self.detector = Detector::new(self.device_threshold());
+    }
+
+    // The chart shows velocities in device units, whatever unit the threshold is in
+    fn device_threshold(&self) -> Config {
+        crate::detection::device_threshold(&self.config, self.diagonal)
+    }
+
+    // Returns false when the tuner should quit
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up => self.config.threshold += self.threshold_step(),
+            KeyCode::Down => {
+                self.config.threshold =
+                    (self.config.threshold - self.threshold_step()).max(self.threshold_step())
+            }
+            KeyCode::Right => self.config.decay = (self.config.decay + 0.005).min(0.999),
+            KeyCode::Left => self.config.decay = (self.config.decay - 0.005).max(0.5),
+            KeyCode::Char('+') => self.config.accel += 100.0,
+            KeyCode::Char('-') => self.config.accel = (self.config.accel - 100.0).max(100.0),
+            KeyCode::Char('s') => {
+                let values = [
+                    ("threshold", toml::Value::Float(self.config.threshold)),
+                    ("decay", toml::Value::Float(self.config.decay)),
+                    ("accel", toml::Value::Float(self.config.accel)),
+                ];
+                self.message = match crate::config::save_values(&values) {
+                    Ok(()) => format!("Saved to {}", crate::config::config_path().display()),
+                    Err(e) => format!("Unable to save config: {}", e),
+                };
+                return true;
+            }
+            _ => return true,
+        }
+        self.apply();
+        true
+    }
+
+    fn threshold_step(&self) -> f64 {
+        match self.config.threshold_unit {
+            ThresholdUnit::Device => 50.0,
+            ThresholdUnit::Screen => 1.0,
+        }
+    }
+
+    fn draw<B: Backend>(&self, frame: &mut Frame<B>) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(10), Constraint::Length(5)])
+            .split(frame.size());
+
+        let now = self.start.elapsed().as_secs_f64();
+        let start = now - HISTORY_SECONDS;
+        let device_threshold = self.device_threshold().threshold;
+        let threshold = [(start, device_threshold), (now, device_threshold)];
+        let max = self
+            .velocity
+            .iter()
+            .map(|(_, v)| *v)
+            .fold(device_threshold * 1.5, f64::max);
+
+        let datasets = vec![
+            Dataset::default()
+                .name("velocity")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::DarkGray))
+                .data(&self.velocity),
+            Dataset::default()
+                .name("smoothed")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Cyan))
+                .data(&self.smoothed),
+            Dataset::default()
+                .name("threshold")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Red))
+                .data(&threshold),
+        ];
+
+        let chart = Chart::new(datasets)
+            .block(Block::default().title("Velocity").borders(Borders::ALL))
+            .x_axis(Axis::default().bounds([start, now]))
+            .y_axis(Axis::default().bounds([0.0, max]).labels(vec![
+                Span::raw("0"),
+                Span::raw(format!("{:.0}", max / 2.0)),
+                Span::raw(format!("{:.0}", max)),
+            ]));
+        frame.render_widget(chart, layout[0]);
+
+        let state = if self.revealed { "REVEALED" } else { "hidden" };
+        let help = Paragraph::new(vec![
+            Line::from(format!(
+                "threshold {:.0} (up/down)  decay {:.3} (left/right)  accel {:.0} (+/-)  [{}]",
+                self.config.threshold, self.config.decay, self.config.accel, state
+            )),
+            Line::from(format!("s: save  q: quit  {}", self.message)),
+            Line::from(Span::styled(
+                self.diagnostics.lock().unwrap().clone(),
+                Style::default().fg(Color::DarkGray),
+            )),
+        ])
+        .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(help, layout[1]);
+    }
+}
+
+// The motion thread reports devices and retries on stderr, which would scribble over
+// the screen. While tuning its latest line is shown below the help instead.
+struct StderrCapture {
+    saved: libc::c_int,
+    latest: Arc<Mutex<String>>,
+}
+
+impl StderrCapture {
+    fn start() -> std::io::Result<StderrCapture> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let saved = unsafe { libc::dup(libc::STDERR_FILENO) };
+        if saved < 0 || unsafe { libc::dup2(fds[1], libc::STDERR_FILENO) } < 0 {
+            let error = std::io::Error::last_os_error();
+            unsafe {
+                libc::close(fds[0]);
+                libc::close(fds[1]);
+                if saved >= 0 {
+                    libc::close(saved);
+                }
+            }
+            return Err(error);
+        }
+        unsafe { libc::close(fds[1]) };
+
+        let latest = Arc::new(Mutex::new(String::new()));
+        let reader = std::io::BufReader::new(unsafe { std::fs::File::from_raw_fd(fds[0]) });
+        let lines = Arc::clone(&latest);
+        // Ends once stderr is restored and the pipe closes
+        std::thread::spawn(move || {
+            for line in reader.lines().map_while(Result::ok) {
+                *lines.lock().unwrap() = line;
+            }
+        });
+        Ok(StderrCapture { saved, latest })
+    }
+}
+
+impl Drop for StderrCapture {
+    fn drop(&mut self) {
+        unsafe {
+            libc::dup2(self.saved, libc::STDERR_FILENO);
+            libc::close(self.saved);
+        }
+    }
+}
+
+pub fn run(config: Config) -> std::io::Result<()> {
+    let stderr = StderrCapture::start()?;
+    let history = Arc::new(VelocityHistory::new());
+    crate::start_motion_thread(
+        // Periodic dumps would scribble over the terminal UI
+        Config {
+            debug_dump_seconds: 0.0,
+            ..config.clone()
+        },
+        // Nothing is captured while tuning
+        crate::logging::CaptureEmitter::disabled(),
+        Arc::clone(&history),
+        crate::haptics::Haptics::default(),
+        // The thread ends with the process, the tuner has nothing to clean up
+        crate::shutdown::Shutdown::default(),
+    );
+
+    crossterm::terminal::enable_raw_mode()?;
+    crossterm::execute!(std::io::stdout(), crossterm::terminal::EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+
+    let tuner = Tuner::new(config, Arc::clone(&stderr.latest));
+    let result = event_loop(&mut terminal, tuner, &history);
+
+    crossterm::terminal::disable_raw_mode()?;
+    crossterm::execute!(std::io::stdout(), crossterm::terminal::LeaveAlternateScreen)?;
+    drop(stderr);
+    result
+}
+
+fn event_loop(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    mut tuner: Tuner,
+    history: &VelocityHistory,
+) -> std::io::Result<()> {
+    loop {
+        tuner.sample(history.latest());
+        terminal.draw(|frame| tuner.draw(frame))?;
+
+        if event::poll(SAMPLE_INTERVAL)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !tuner.handle_key(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Idle watchers wake up this often to notice being stopped. Without inotify the
+// keyboards are looked for again as well, e.g. after plugging one in.
+const IDLE_MS: i32 = 10_000;
+
+const MODIFIERS: &[evdev::Key] = &[
+    evdev::Key::KEY_LEFTCTRL,
+    evdev::Key::KEY_RIGHTCTRL,
+    evdev::Key::KEY_LEFTSHIFT,
+    evdev::Key::KEY_RIGHTSHIFT,
+    evdev::Key::KEY_LEFTALT,
+    evdev::Key::KEY_RIGHTALT,
+    evdev::Key::KEY_LEFTMETA,
+    evdev::Key::KEY_RIGHTMETA,
+];
+
+// Disable while typing: palms brushing the touchpad while typing shake the cursor.
+// Only the time of the last keystroke is kept, never which key it was.
+#[derive(Clone, Default)]
+pub struct Typing {
+    last_key: Arc<Mutex<Option<Instant>>>,
+    keyboard_name: String,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Typing {
+    pub fn start(keyboard_name: &str) -> Typing {
+        let typing = Typing {
+            keyboard_name: keyboard_name.to_string(),
+            ..Typing::default()
+        };
+        let last_key = Arc::clone(&typing.last_key);
+        let stopped = Arc::clone(&typing.stopped);
+        let name = typing.keyboard_name.clone();
+        std::thread::spawn(move || {
+            let hotplug = watch_hotplug();
+            while !stopped.load(Ordering::Relaxed) {
+                let mut keyboards: Vec<evdev::Device> = evdev::enumerate()
+                    .map(|(_, device)| device)
+                    .filter(|device| is_keyboard(device, &name))
+                    .collect();
+                if let Err(e) = watch(&mut keyboards, hotplug.as_ref(), &last_key, &stopped) {
+                    eprintln!("Keyboard lost while watching for typing: {}", e);
+                }
+            }
+        });
+        typing
+    }
+
+    pub fn keyboard_name(&self) -> &str {
+        &self.keyboard_name
+    }
+
+    // The thread ends the next time it wakes up
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    pub fn typed_within(&self, duration: Duration) -> bool {
+        self.last_key
+            .lock()
+            .unwrap()
+            .is_some_and(|last| last.elapsed() < duration)
+    }
+}
+
+fn is_keyboard(device: &evdev::Device, name: &str) -> bool {
+    device.name().unwrap_or_default().contains(name)
+        && device.supported_keys().is_some_and(|keys| {
+            keys.contains(evdev::Key::KEY_A) && keys.contains(evdev::Key::KEY_Z)
+        })
+}
+
+// New device nodes in /dev/input, None when inotify is unavailable. udev grants the
+// access after creating the node, which changes its attributes.
+fn watch_hotplug() -> Option<OwnedFd> {
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+    if fd < 0 {
+        return None;
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let watch = unsafe {
+        libc::inotify_add_watch(
+            fd.as_raw_fd(),
+            c"/dev/input".as_ptr(),
+            libc::IN_CREATE | libc::IN_ATTRIB,
+        )
+    };
+    (watch >= 0).then_some(fd)
+}
+
+// Returns Ok to rescan the keyboards, after a device was plugged in or stopping
+fn watch(
+    keyboards: &mut [evdev::Device],
+    hotplug: Option<&OwnedFd>,
+    last_key: &Mutex<Option<Instant>>,
+    stopped: &AtomicBool,
+) -> std::io::Result<()> {
+    let pollfd = |fd| libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let mut fds: Vec<libc::pollfd> = keyboards
+        .iter()
+        .map(|keyboard| pollfd(keyboard.as_raw_fd()))
+        .collect();
+    // Last, so the keyboards and their fds keep the same indices
+    fds.extend(hotplug.map(|fd| pollfd(fd.as_raw_fd())));
+    loop {
+        match unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, IDLE_MS) } {
+            -1 => {
+                let error = std::io::Error::last_os_error();
+                if error.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+            _ if stopped.load(Ordering::Relaxed) => return Ok(()),
+            0 if hotplug.is_none() => return Ok(()),
+            0 => {}
+            _ => {
+                if let Some(hotplug) = hotplug.filter(|_| fds[keyboards.len()].revents != 0) {
+                    // The events only say something was created, their content doesn't matter
+                    let mut buffer = [0u8; 4096];
+                    while unsafe {
+                        libc::read(hotplug.as_raw_fd(), buffer.as_mut_ptr() as _, buffer.len())
+                    } > 0
+                    {}
+                    return Ok(());
+                }
+                for (fd, keyboard) in fds.iter().zip(keyboards.iter_mut()) {
+                    if fd.revents & (libc::POLLERR | libc::POLLHUP) != 0 {
+                        return Err(std::io::Error::other("device removed"));
+                    }
+                    if fd.revents & libc::POLLIN == 0 {
+                        continue;
+                    }
+                    // Presses and repeats, modifiers are held for clicks and scrolling
+                    let typed = keyboard.fetch_events()?.any(|event| {
+                        matches!(event.kind(), evdev::InputEventKind::Key(key)
+                            if event.value() != 0
+                                && key.code() < evdev::Key::BTN_0.code()
+                                && !MODIFIERS.contains(&key))
+                    });
+                    if typed {
+                        *last_key.lock().unwrap() = Some(Instant::now());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Older days are dropped from the file
+const MAX_DAYS: usize = 90;
+
+// What made the overlay appear
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trigger {
+    Shake,
+    Highlight,
+    Pen,
+    CursorShown,
+    Teleport,
+    Edge,
+    HotCorner,
+    Desktop,
+    OutputFocus,
+    Requested,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Day {
+    pub reveals: u64,
+    pub visible_seconds: f64,
+    pub triggers: BTreeMap<Trigger, u64>,
+}
+
+impl Day {
+    fn add(&mut self, other: &Day) {
+        self.reveals += other.reveals;
+        self.visible_seconds += other.visible_seconds;
+        for (trigger, count) in &other.triggers {
+            *self.triggers.entry(*trigger).or_default() += count;
+        }
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+struct UsageFile {
+    days: BTreeMap<String, Day>,
+}
+
+// Reveals per day and what triggered them, to tell whether tuning reduced accidental
+// reveals. Saved to the state directory whenever a reveal ends.
+pub struct Usage {
+    days: BTreeMap<String, Day>,
+    visible_since: Option<std::time::Instant>,
+}
+
+impl Usage {
+    pub fn load() -> Usage {
+        let path = usage_path();
+        let file = match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("Invalid usage statistics {}: {}", path.display(), e);
+                UsageFile::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => UsageFile::default(),
+            Err(e) => {
+                eprintln!("Unable to read usage statistics {}: {}", path.display(), e);
+                UsageFile::default()
+            }
+        };
+        Usage {
+            days: file.days,
+            visible_since: None,
+        }
+    }
+
+    pub fn reveal(&mut self, trigger: Trigger) {
+        let today = self.today();
+        today.reveals += 1;
+        *today.triggers.entry(trigger).or_default() += 1;
+    }
+
+    // Called once per frame, the time visible counts on the day the overlay hid
+    pub fn visible(&mut self, visible: bool) {
+        match (visible, self.visible_since) {
+            (true, None) => self.visible_since = Some(std::time::Instant::now()),
+            (false, Some(since)) => {
+                self.visible_since = None;
+                self.today().visible_seconds += since.elapsed().as_secs_f64();
+                if let Err(e) = self.save() {
+                    eprintln!(
+                        "Unable to save usage statistics to {}: {}",
+                        usage_path().display(),
+                        e
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let mut total = Day::default();
+        self.days.values().for_each(|day| total.add(day));
+        serde_json::json!({
+            "today": self.days.get(&today).cloned().unwrap_or_default(),
+            "total": total,
+            "days": self.days,
+        })
+        .to_string()
+    }
+
+    fn today(&mut self) -> &mut Day {
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        self.days.entry(today).or_default()
+    }
+
+    fn save(&mut self) -> std::io::Result<()> {
+        // Dates sort chronologically
+        while self.days.len() > MAX_DAYS {
+            self.days.pop_first();
+        }
+        let file = UsageFile {
+            days: self.days.clone(),
+        };
+        crate::state::write(&usage_path(), &file)
+    }
+}
+
+pub fn usage_path() -> std::path::PathBuf {
+    crate::state::state_dir().join("usage.toml")
+}
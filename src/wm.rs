@@ -0,0 +1,213 @@
+use crate::models::WindowClass;
+use crate::ConnExt;
+use xcb::Xid;
+
+// What changed since a subscriber last looked
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Changes {
+    pub active_window: bool,
+    pub fullscreen: bool,
+    pub desktop: bool,
+    pub compositor: bool,
+    // A top-level window was moved or resized
+    pub geometry: bool,
+}
+
+pub struct Subscription(usize);
+
+// EWMH state of the window manager, kept current from the events of the root window.
+// Features subscribe to the changes instead of each querying the properties.
+pub struct Wm {
+    root: xcb::x::Window,
+    net_active_window: xcb::x::Atom,
+    net_current_desktop: xcb::x::Atom,
+    net_wm_state: xcb::x::Atom,
+    net_wm_state_fullscreen: xcb::x::Atom,
+    net_wm_cm: xcb::x::Atom,
+    active_window: Option<xcb::x::Window>,
+    active_class: Option<WindowClass>,
+    fullscreen: bool,
+    desktop: Option<u32>,
+    compositor: bool,
+    pending: Vec<Changes>,
+}
+
+impl Wm {
+    // The root window events are selected by the overlay window
+    pub fn new(conn: &xcb::Connection, root: xcb::x::Window, screen_num: usize) -> Wm {
+        // A missing atom never matches a property change, that tracking just stays off
+        let atom = |name: &[u8]| conn.get_atom(name).unwrap_or(xcb::x::ATOM_NONE);
+        let mut wm = Wm {
+            root,
+            net_active_window: atom(b"_NET_ACTIVE_WINDOW"),
+            net_current_desktop: atom(b"_NET_CURRENT_DESKTOP"),
+            net_wm_state: atom(b"_NET_WM_STATE"),
+            net_wm_state_fullscreen: atom(b"_NET_WM_STATE_FULLSCREEN"),
+            net_wm_cm: atom(format!("_NET_WM_CM_S{}", screen_num).as_bytes()),
+            active_window: None,
+            active_class: None,
+            fullscreen: false,
+            desktop: None,
+            compositor: compositor(conn, screen_num as _),
+            pending: Vec::new(),
+        };
+
+        // Compositors announce themselves by owning the selection, XFixes reports new owners
+        let version = conn.wait_for_reply(conn.send_request(&xcb::xfixes::QueryVersion {
+            client_major_version: 4,
+            client_minor_version: 0,
+        }));
+        if version.is_ok() && !wm.net_wm_cm.is_none() {
+            conn.send_request(&xcb::xfixes::SelectSelectionInput {
+                window: root,
+                selection: wm.net_wm_cm,
+                event_mask: xcb::xfixes::SelectionEventMask::SET_SELECTION_OWNER
+                    | xcb::xfixes::SelectionEventMask::SELECTION_WINDOW_DESTROY
+                    | xcb::xfixes::SelectionEventMask::SELECTION_CLIENT_CLOSE,
+            });
+        }
+        wm.update_active_window(conn);
+        wm.desktop = wm.current_desktop(conn);
+        wm
+    }
+
+    // Nothing counts as changed before subscribing
+    pub fn subscribe(&mut self) -> Subscription {
+        self.pending.push(Changes::default());
+        Subscription(self.pending.len() - 1)
+    }
+
+    pub fn take(&mut self, subscription: &Subscription) -> Changes {
+        std::mem::take(&mut self.pending[subscription.0])
+    }
+
+    pub fn active_window(&self) -> Option<xcb::x::Window> {
+        self.active_window
+    }
+
+    pub fn active_class(&self) -> Option<&WindowClass> {
+        self.active_class.as_ref()
+    }
+
+    pub fn fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    pub fn compositor(&self) -> bool {
+        self.compositor
+    }
+
+    pub fn property_changed(
+        &mut self,
+        conn: &xcb::Connection,
+        event: &xcb::x::PropertyNotifyEvent,
+    ) {
+        if event.window() == self.root && event.atom() == self.net_active_window {
+            self.update_active_window(conn);
+        } else if event.window() == self.root && event.atom() == self.net_current_desktop {
+            let desktop = self.current_desktop(conn);
+            if desktop != self.desktop {
+                self.desktop = desktop;
+                self.notify(|changes| changes.desktop = true);
+            }
+        } else if Some(event.window()) == self.active_window && event.atom() == self.net_wm_state {
+            self.update_fullscreen(conn);
+        }
+    }
+
+    pub fn window_moved(&mut self) {
+        self.notify(|changes| changes.geometry = true);
+    }
+
+    pub fn selection_changed(&mut self, event: &xcb::xfixes::SelectionNotifyEvent) {
+        if event.selection() != self.net_wm_cm {
+            return;
+        }
+        let compositor = !event.owner().is_none();
+        if compositor != self.compositor {
+            self.compositor = compositor;
+            self.notify(|changes| changes.compositor = true);
+        }
+    }
+
+    fn notify(&mut self, change: impl Fn(&mut Changes)) {
+        self.pending.iter_mut().for_each(change);
+    }
+
+    fn update_active_window(&mut self, conn: &xcb::Connection) {
+        let active = conn.get_active_window(self.root, self.net_active_window);
+        if active == self.active_window {
+            return;
+        }
+        // Checked, the previous window may be gone already
+        if let Some(window) = self.active_window {
+            let _ =
+                conn.check_request(conn.send_request_checked(&xcb::x::ChangeWindowAttributes {
+                    window,
+                    value_list: &[xcb::x::Cw::EventMask(xcb::x::EventMask::NO_EVENT)],
+                }));
+        }
+        self.active_window = active;
+        self.active_class = active.and_then(|window| conn.get_window_class(window));
+        // Fullscreen is a state of the window itself, not of the root window. Grabs of
+        // popups and drags cross the window the pointer is in, mostly the active one.
+        if let Some(window) = active {
+            conn.send_request(&xcb::x::ChangeWindowAttributes {
+                window,
+                value_list: &[xcb::x::Cw::EventMask(
+                    xcb::x::EventMask::PROPERTY_CHANGE
+                        | xcb::x::EventMask::ENTER_WINDOW
+                        | xcb::x::EventMask::LEAVE_WINDOW,
+                )],
+            });
+        }
+        self.notify(|changes| changes.active_window = true);
+        self.update_fullscreen(conn);
+    }
+
+    fn update_fullscreen(&mut self, conn: &xcb::Connection) {
+        let fullscreen = self.active_window.is_some_and(|window| {
+            conn.wait_for_reply(conn.send_request(&xcb::x::GetProperty {
+                delete: false,
+                window,
+                property: self.net_wm_state,
+                r#type: xcb::x::ATOM_ATOM,
+                long_offset: 0,
+                long_length: 64,
+            }))
+            .is_ok_and(|reply| {
+                reply
+                    .value::<xcb::x::Atom>()
+                    .contains(&self.net_wm_state_fullscreen)
+            })
+        });
+        if fullscreen != self.fullscreen {
+            self.fullscreen = fullscreen;
+            self.notify(|changes| changes.fullscreen = true);
+        }
+    }
+
+    fn current_desktop(&self, conn: &xcb::Connection) -> Option<u32> {
+        conn.wait_for_reply(conn.send_request(&xcb::x::GetProperty {
+            delete: false,
+            window: self.root,
+            property: self.net_current_desktop,
+            r#type: xcb::x::ATOM_CARDINAL,
+            long_offset: 0,
+            long_length: 1,
+        }))
+        .ok()?
+        .value::<u32>()
+        .first()
+        .copied()
+    }
+}
+
+// Compositing managers own the _NET_WM_CM_Sn selection of the screen they manage
+pub fn compositor(conn: &xcb::Connection, screen_num: i32) -> bool {
+    conn.get_atom(format!("_NET_WM_CM_S{}", screen_num).as_bytes())
+        .is_ok_and(|atom| {
+            conn.wait_for_reply(conn.send_request(&xcb::x::GetSelectionOwner { selection: atom }))
+                .is_ok_and(|reply| !reply.owner().is_none())
+        })
+}
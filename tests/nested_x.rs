@@ -0,0 +1,149 @@
+#![cfg(feature = "integration-test")]
+
+// Runs the overlay against a private Xvfb server, polling the pointer picks up
+// the motion injected through XTest.
+
+#[path = "../src/overlay_probe.rs"]
+mod overlay_probe;
+
+use overlay_probe::{drawn, shake, wait_for, warp};
+use std::io::BufRead;
+use std::time::Duration;
+
+struct NestedX {
+    server: std::process::Child,
+    display: String,
+}
+
+impl NestedX {
+    fn start() -> NestedX {
+        let mut server = std::process::Command::new("Xvfb")
+            .args([
+                "-displayfd",
+                "1",
+                "-screen",
+                "0",
+                "1280x1024x24",
+                "-nolisten",
+                "tcp",
+            ])
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .expect("Xvfb is required for the integration tests");
+
+        // Xvfb picks a free display and writes its number once it accepts clients
+        let mut number = String::new();
+        std::io::BufReader::new(server.stdout.take().unwrap())
+            .read_line(&mut number)
+            .unwrap();
+        NestedX {
+            server,
+            display: format!(":{}", number.trim()),
+        }
+    }
+}
+
+impl Drop for NestedX {
+    fn drop(&mut self) {
+        let _ = self.server.kill();
+        let _ = self.server.wait();
+    }
+}
+
+struct Daemon {
+    process: std::process::Child,
+    dir: std::path::PathBuf,
+}
+
+impl Daemon {
+    // The tests run in parallel, each daemon gets its own config and runtime directory
+    fn start(x: &NestedX, test: &str, config: &str) -> Daemon {
+        static STARTED: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "mouse-reveal-test-{}-{}-{}",
+            std::process::id(),
+            test,
+            STARTED.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(dir.join("mouse-reveal")).unwrap();
+        std::fs::write(dir.join("mouse-reveal").join("config.toml"), config).unwrap();
+
+        let process = std::process::Command::new(env!("CARGO_BIN_EXE_dgs-mouse-reveal"))
+            .env("DISPLAY", &x.display)
+            .env("XDG_CONFIG_HOME", &dir)
+            .env("XDG_RUNTIME_DIR", &dir)
+            .spawn()
+            .unwrap();
+        Daemon { process, dir }
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+const CONFIG: &str = r#"
+pointer_only = true
+pointer_poll_hz = 120.0
+refresh_pacing = false
+"#;
+
+fn root(conn: &xcb::Connection) -> xcb::x::Window {
+    conn.get_setup().roots().next().unwrap().root()
+}
+
+// Nothing else creates override-redirect windows on a bare server
+fn overlays(conn: &xcb::Connection) -> Vec<(xcb::x::Window, bool)> {
+    overlay_probe::overlays(conn, root(conn))
+}
+
+#[test]
+fn shaking_reveals_and_hides_the_overlay() {
+    let x = NestedX::start();
+    let _daemon = Daemon::start(&x, "shaking", CONFIG);
+    let (conn, _) =
+        xcb::Connection::connect_with_extensions(Some(&x.display), &[xcb::Extension::Test], &[])
+            .unwrap();
+
+    assert!(
+        wait_for(Duration::from_secs(10), || !overlays(&conn).is_empty()),
+        "overlay window was never created"
+    );
+    assert!(
+        overlays(&conn).iter().all(|(_, mapped)| !mapped),
+        "overlay is mapped before any motion"
+    );
+
+    shake(&conn, root(&conn), (600, 500), Duration::from_millis(700));
+    let revealed = overlays(&conn).into_iter().find(|(_, mapped)| *mapped);
+    let (window, _) = revealed.expect("overlay is not mapped after shaking");
+    assert!(drawn(&conn, window), "overlay is mapped but empty");
+
+    assert!(
+        wait_for(Duration::from_secs(5), || overlays(&conn)
+            .iter()
+            .all(|(_, mapped)| !mapped)),
+        "overlay stays mapped after the motion stopped"
+    );
+}
+
+#[test]
+fn slow_motion_keeps_the_overlay_hidden() {
+    let x = NestedX::start();
+    let _daemon = Daemon::start(&x, "slow_motion", CONFIG);
+    let (conn, _) =
+        xcb::Connection::connect_with_extensions(Some(&x.display), &[xcb::Extension::Test], &[])
+            .unwrap();
+    assert!(wait_for(Duration::from_secs(10), || !overlays(&conn).is_empty()));
+
+    for step in 0..50 {
+        warp(&conn, root(&conn), 300 + step, 500);
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    assert!(overlays(&conn).iter().all(|(_, mapped)| !mapped));
+}